@@ -0,0 +1,544 @@
+//! The subcommands, each returning its output as JSON
+use std::{error::Error, fs::File, io, path::Path, str::FromStr};
+use borsh::BorshDeserialize;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{
+    account::from_account,
+    clock::Clock,
+    hash::Hash,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    system_program, sysvar,
+    transaction::Transaction,
+};
+use tokenlock_cli::{
+    grants::{build_plan, read_grants, Action, Plan, ScheduleParams, Status},
+    report::{release_timeline, write_report, ReportFormat},
+};
+use tokenlock::{
+    instruction::TokenLockInstruction,
+    processor::Processor,
+    types::{CancelMode, CancelProposal, TokenLockAccount},
+};
+
+pub type CommandResult = Result<Value, Box<dyn Error>>;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Display,
+    Json,
+}
+
+pub struct Config {
+    pub rpc_client: RpcClient,
+    /// Signs the transactions and pays their fees
+    pub payer: Keypair,
+    pub dry_run: bool,
+    pub output: OutputFormat,
+}
+
+/// A timelock to fund
+pub struct Grant {
+    pub recipient: Pubkey,
+    pub amount: u32,
+    pub commencement_timestamp: u32,
+    pub schedule_id: u32,
+    pub cancelable_by: Vec<Pubkey>,
+    pub cancel_threshold: u8,
+}
+
+/// A row of a fund-csv file
+#[derive(Deserialize)]
+struct GrantRow {
+    recipient: String,
+    amount: u32,
+    commencement_timestamp: u32,
+    schedule_id: u32,
+    /// Canceler addresses separated by ';'
+    #[serde(default)]
+    cancelable_by: String,
+    #[serde(default)]
+    cancel_threshold: u8,
+}
+
+impl GrantRow {
+    fn into_grant(self) -> Result<Grant, Box<dyn Error>> {
+        let cancelable_by = self.cancelable_by.split(';')
+            .map(str::trim)
+            .filter(|canceler| !canceler.is_empty())
+            .map(Pubkey::from_str)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Grant {
+            recipient: Pubkey::from_str(self.recipient.trim())?,
+            amount: self.amount,
+            commencement_timestamp: self.commencement_timestamp,
+            schedule_id: self.schedule_id,
+            cancelable_by,
+            cancel_threshold: self.cancel_threshold,
+        })
+    }
+}
+
+/// Sends the instructions in one transaction signed by the payer, or only simulates it with --dry-run.
+fn send(config: &Config, instructions: &[Instruction]) -> CommandResult {
+    let (recent_blockhash, _) = config.rpc_client.get_recent_blockhash()?;
+    let transaction = Transaction::new_signed_with_payer(
+        instructions,
+        Some(&config.payer.pubkey()),
+        &[&config.payer],
+        recent_blockhash,
+    );
+    if config.dry_run {
+        let simulation = config.rpc_client.simulate_transaction(&transaction)?.value;
+        return Ok(json!({
+            "dry_run": true,
+            "err": simulation.err.map(|err| err.to_string()),
+            "logs": simulation.logs.unwrap_or_default(),
+        }));
+    }
+    let signature = config.rpc_client.send_and_confirm_transaction(&transaction)?;
+    Ok(json!({ "signature": signature.to_string() }))
+}
+
+/// Fetches the data of a token lock account, checking it belongs to the program.
+fn token_lock_data(config: &Config, token: &Pubkey) -> Result<Vec<u8>, Box<dyn Error>> {
+    let account = config.rpc_client.get_account(token)?;
+    if account.owner != tokenlock::id() {
+        return Err(format!("{} is not a token lock account", token).into());
+    }
+    Ok(account.data)
+}
+
+fn clock(config: &Config) -> Result<Clock, Box<dyn Error>> {
+    let account = config.rpc_client.get_account(&sysvar::clock::id())?;
+    from_account(&account).ok_or_else(|| "invalid clock sysvar".into())
+}
+
+/// The token program owning the mint, the system program for native SOL
+fn token_program_of(config: &Config, mint: &Pubkey) -> Result<Pubkey, Box<dyn Error>> {
+    if mint == &spl_token::native_mint::id() {
+        return Ok(system_program::id());
+    }
+    Ok(config.rpc_client.get_account(mint)?.owner)
+}
+
+/// Where tokens for `owner` go: its associated token account, or the address itself for native SOL
+fn destination_of(owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey) -> Pubkey {
+    if mint == &spl_token::native_mint::id() {
+        return *owner;
+    }
+    Pubkey::find_program_address(
+        &[owner.as_ref(), token_program.as_ref(), mint.as_ref()],
+        &spl_associated_token_account::id(),
+    ).0
+}
+
+fn cancel_threshold_of(account: &TokenLockAccount, recipient: &Pubkey, timelock_index: u32) -> Result<u8, Box<dyn Error>> {
+    let timelock = account.get_timelock(recipient, timelock_index as usize)
+        .ok_or_else(|| format!("{} has no timelock {}", recipient, timelock_index))?;
+    Ok(timelock.cancel_threshold)
+}
+
+pub fn init(config: &Config, mint: &Pubkey, nonce: u8, max_release_delay: u32, min_timelock_amount: u32, timelocks: usize, treasury_authority: Option<Pubkey>) -> CommandResult {
+    let token_program = token_program_of(config, mint)?;
+    let (token, _) = TokenLockAccount::find_address(mint, &config.payer.pubkey(), nonce, &tokenlock::id());
+    let (vault, _) = TokenLockAccount::find_vault(&token, &tokenlock::id());
+    let instruction = TokenLockInstruction::initialize_token_lock(
+        &config.payer.pubkey(),
+        mint,
+        &token_program,
+        nonce,
+        max_release_delay,
+        min_timelock_amount,
+        TokenLockAccount::space(timelocks.max(1)) as u32,
+        treasury_authority,
+    );
+    let mut output = send(config, &[instruction])?;
+    output["token_lock"] = json!(token.to_string());
+    output["vault"] = json!(vault.to_string());
+    Ok(output)
+}
+
+pub fn create_schedule(config: &Config, token: &Pubkey, release_count: u32, delay_until_first_release_in_seconds: u32, initial_release_portion_in_bips: u32, period_between_releases_in_seconds: u32) -> CommandResult {
+    let mut data = token_lock_data(config, token)?;
+    let schedule_id = TokenLockAccount::load_mut(&mut data)?.release_schedules().len();
+    let instruction = TokenLockInstruction::create_release_schedule(
+        token,
+        &config.payer.pubkey(),
+        release_count,
+        delay_until_first_release_in_seconds,
+        initial_release_portion_in_bips,
+        period_between_releases_in_seconds,
+    );
+    let mut output = send(config, &[instruction])?;
+    output["schedule_id"] = json!(schedule_id);
+    Ok(output)
+}
+
+/// The instruction funding a grant from the payer as the timelock at `timelock_index`
+fn fund_instruction(config: &Config, token: &Pubkey, mint: &Pubkey, token_program: &Pubkey, grant: &Grant, source: Option<Pubkey>, timelock_index: usize) -> Instruction {
+    let funder = config.payer.pubkey();
+    TokenLockInstruction::fund_release_schedule(
+        token,
+        &funder,
+        &source.unwrap_or_else(|| destination_of(&funder, mint, token_program)),
+        mint,
+        token_program,
+        &grant.recipient,
+        grant.amount,
+        grant.commencement_timestamp,
+        grant.schedule_id,
+        &grant.cancelable_by,
+        grant.cancel_threshold,
+        timelock_index as u32,
+    )
+}
+
+pub fn fund(config: &Config, token: &Pubkey, grant: &Grant, source: Option<Pubkey>) -> CommandResult {
+    let mut data = token_lock_data(config, token)?;
+    let account = TokenLockAccount::load_mut(&mut data)?;
+    let token_program = token_program_of(config, &account.mint)?;
+    let timelock_index = account.timelocks().len();
+    let mut output = send(config, &[fund_instruction(config, token, &account.mint, &token_program, grant, source, timelock_index)])?;
+    output["recipient"] = json!(grant.recipient.to_string());
+    output["timelock_index"] = json!(timelock_index);
+    Ok(output)
+}
+
+/// Funds the rows of the file one transaction at a time, stopping at the first failure.
+pub fn fund_csv(config: &Config, token: &Pubkey, path: &str) -> CommandResult {
+    let grants = csv::Reader::from_path(path)?
+        .deserialize::<GrantRow>()
+        .map(|row| row.map_err(Box::<dyn Error>::from).and_then(GrantRow::into_grant))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut data = token_lock_data(config, token)?;
+    let account = TokenLockAccount::load_mut(&mut data)?;
+    let token_program = token_program_of(config, &account.mint)?;
+    let mut funded = vec![];
+    for (row, grant) in grants.iter().enumerate() {
+        // simulated rows don't fund anything, each is simulated against the account as it is
+        let timelock_index = account.timelocks().len() + if config.dry_run { 0 } else { funded.len() };
+        let mut output = match send(config, &[fund_instruction(config, token, &account.mint, &token_program, grant, None, timelock_index)]) {
+            Ok(output) => output,
+            Err(err) => {
+                return Ok(json!({ "funded": funded, "failed_row": row + 1, "err": err.to_string() }));
+            }
+        };
+        output["row"] = json!(row + 1);
+        output["recipient"] = json!(grant.recipient.to_string());
+        output["amount"] = json!(grant.amount);
+        let failed = !output["err"].is_null();
+        funded.push(output);
+        if failed {
+            break;
+        }
+    }
+    Ok(json!({ "funded": funded }))
+}
+
+/// Imports a grant file, or resumes the import whose progress is saved in the plan file.
+/// With --dry-run the grants are only checked and the plan is printed.
+pub fn import_grants(config: &Config, token: &Pubkey, file: &str, plan_path: &str) -> CommandResult {
+    let plan_path = Path::new(plan_path);
+    let mut plan = if plan_path.exists() {
+        let plan = Plan::load(plan_path)?;
+        if plan.token != token.to_string() {
+            return Err(format!("{} is the plan of {}", plan_path.display(), plan.token).into());
+        }
+        plan
+    } else {
+        let grants = read_grants(Path::new(file))?;
+        let data = token_lock_data(config, token)?;
+        let timestamp = clock(config)?.unix_timestamp as u32;
+        build_plan(token, &data, timestamp, &grants)?
+    };
+    if config.dry_run {
+        return Ok(json!({ "dry_run": true, "plan": plan }));
+    }
+    plan.save(plan_path)?;
+
+    let mut data = token_lock_data(config, token)?;
+    let mint = TokenLockAccount::load_mut(&mut data)?.mint;
+    let token_program = token_program_of(config, &mint)?;
+    while let Some(idx) = plan.next_step() {
+        // a transaction signed before the import stopped may have landed
+        if let Status::Sent { signature, blockhash } = plan.steps[idx].status.clone() {
+            match config.rpc_client.get_signature_status(&Signature::from_str(&signature)?)? {
+                Some(Ok(())) => {
+                    complete_step(config, token, &mut plan, idx, signature)?;
+                    plan.save(plan_path)?;
+                    continue;
+                }
+                Some(Err(err)) => {
+                    plan.steps[idx].status = Status::Pending;
+                    plan.save(plan_path)?;
+                    return Err(format!("step {} failed: {}", idx + 1, err).into());
+                }
+                None => {
+                    if config.rpc_client.get_fee_calculator_for_blockhash(&Hash::from_str(&blockhash)?)?.is_some() {
+                        return Err(format!("transaction {} of step {} may still land, run the import again later", signature, idx + 1).into());
+                    }
+                }
+            }
+        }
+
+        let instruction = match &plan.steps[idx].action {
+            Action::CreateSchedule { schedule } => {
+                let params = plan.schedules[*schedule].params;
+                TokenLockInstruction::create_release_schedule(
+                    token,
+                    &config.payer.pubkey(),
+                    params.release_count,
+                    params.delay_until_first_release_in_seconds,
+                    params.initial_release_portion_in_bips,
+                    params.period_between_releases_in_seconds,
+                )
+            }
+            Action::Fund { recipient, amount, commencement_timestamp, schedule, cancelable_by, cancel_threshold, .. } => {
+                let grant = Grant {
+                    recipient: Pubkey::from_str(recipient)?,
+                    amount: *amount,
+                    commencement_timestamp: *commencement_timestamp,
+                    schedule_id: plan.schedules[*schedule].schedule_id.ok_or("the schedule of the grant isn't created yet")?,
+                    cancelable_by: cancelable_by.iter().map(|canceler| Pubkey::from_str(canceler)).collect::<Result<_, _>>()?,
+                    cancel_threshold: *cancel_threshold,
+                };
+                let mut data = token_lock_data(config, token)?;
+                let timelock_index = TokenLockAccount::load_mut(&mut data)?.timelocks().len();
+                fund_instruction(config, token, &mint, &token_program, &grant, None, timelock_index)
+            }
+        };
+        let (recent_blockhash, _) = config.rpc_client.get_recent_blockhash()?;
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&config.payer.pubkey()),
+            &[&config.payer],
+            recent_blockhash,
+        );
+        let signature = transaction.signatures[0].to_string();
+        plan.steps[idx].status = Status::Sent { signature: signature.clone(), blockhash: recent_blockhash.to_string() };
+        plan.save(plan_path)?;
+        config.rpc_client.send_and_confirm_transaction(&transaction)?;
+        complete_step(config, token, &mut plan, idx, signature)?;
+        plan.save(plan_path)?;
+    }
+    Ok(json!({ "plan": plan_path.display().to_string(), "steps": plan.steps.len(), "done": plan.done() }))
+}
+
+/// Marks a step done, looking up the id of a schedule it created
+fn complete_step(config: &Config, token: &Pubkey, plan: &mut Plan, idx: usize, signature: String) -> Result<(), Box<dyn Error>> {
+    if let Action::CreateSchedule { schedule } = plan.steps[idx].action {
+        let mut data = token_lock_data(config, token)?;
+        let account = TokenLockAccount::load_mut(&mut data)?;
+        let params = plan.schedules[schedule].params;
+        let schedule_id = account.release_schedules().iter()
+            .rposition(|release_schedule| ScheduleParams::from(release_schedule) == params)
+            .ok_or("created schedule not found")?;
+        plan.schedules[schedule].schedule_id = Some(schedule_id as u32);
+    }
+    plan.steps[idx].status = Status::Done { signature };
+    Ok(())
+}
+
+pub fn balance(config: &Config, token: &Pubkey, owner: Option<Pubkey>) -> CommandResult {
+    let owner = owner.unwrap_or_else(|| config.payer.pubkey());
+    let timestamp = clock(config)?.unix_timestamp as u32;
+    let mut data = token_lock_data(config, token)?;
+    let processor = Processor {
+        account_key: token,
+        account_info: TokenLockAccount::load_mut(&mut data)?,
+        modified: false,
+        timestamp,
+    };
+    Ok(json!({
+        "owner": owner.to_string(),
+        "timestamp": timestamp,
+        "unlocked": processor.unlocked_balance_of(&owner),
+        "locked": processor.locked_balance_of(&owner),
+        "total": processor.balance_of(&owner),
+    }))
+}
+
+pub fn timelocks(config: &Config, token: &Pubkey, recipient: Option<Pubkey>) -> CommandResult {
+    let timestamp = clock(config)?.unix_timestamp as u32;
+    let mut data = token_lock_data(config, token)?;
+    let processor = Processor {
+        account_key: token,
+        account_info: TokenLockAccount::load_mut(&mut data)?,
+        modified: false,
+        timestamp,
+    };
+    let timelocks: Vec<Value> = processor.account_info.timelocks().iter().enumerate()
+        .filter(|(_, timelock)| recipient.map_or(true, |recipient| timelock.recipient == recipient))
+        .map(|(idx, timelock)| json!({
+            "index": idx,
+            "recipient": timelock.recipient.to_string(),
+            "schedule_id": timelock.schedule_id,
+            "commencement_timestamp": timelock.commencement_timestamp,
+            "total_amount": timelock.total_amount,
+            "tokens_transferred": timelock.tokens_transferred,
+            "unlocked": processor.unlocked_balance_of_timelock(&timelock.recipient, idx as u32),
+            "locked": processor.locked_balance_of_timelock(&timelock.recipient, idx as u32),
+            "vesting_stopped_at": timelock.vesting_stopped_at,
+            "cancelable_by": timelock.cancelable_by().iter().map(Pubkey::to_string).collect::<Vec<_>>(),
+            "cancel_threshold": timelock.cancel_threshold,
+        }))
+        .collect();
+    Ok(json!({ "timestamp": timestamp, "timelocks": timelocks }))
+}
+
+/// Writes the release timeline of the lockup, or of one recipient, to `out` or to stdout.
+/// Returns null when the report went to stdout.
+pub fn report(config: &Config, token: &Pubkey, recipient: Option<Pubkey>, format: ReportFormat, out: Option<&str>) -> CommandResult {
+    let mut data = token_lock_data(config, token)?;
+    let account = TokenLockAccount::load_mut(&mut data)?;
+    let events = release_timeline(&account, recipient.as_ref());
+    match out {
+        Some(path) => {
+            write_report(&events, format, File::create(path)?)?;
+            Ok(json!({ "report": path, "releases": events.len() }))
+        }
+        None => {
+            write_report(&events, format, io::stdout())?;
+            Ok(Value::Null)
+        }
+    }
+}
+
+pub fn transfer(config: &Config, token: &Pubkey, to: &Pubkey, value: u32, timelock_index: u32, destination: Option<Pubkey>) -> CommandResult {
+    let mut data = token_lock_data(config, token)?;
+    let account = TokenLockAccount::load_mut(&mut data)?;
+    let token_program = token_program_of(config, &account.mint)?;
+    let instruction = TokenLockInstruction::transfer_timelock(
+        token,
+        &config.payer.pubkey(),
+        &account.mint,
+        &token_program,
+        to,
+        &destination.unwrap_or_else(|| destination_of(to, &account.mint, &token_program)),
+        value,
+        timelock_index,
+    );
+    send(config, &[instruction])
+}
+
+/// Cancels the timelock right away when one canceler is enough, otherwise opens a proposal
+/// the other cancelers approve with `approve`.
+pub fn cancel(config: &Config, token: &Pubkey, target: &Pubkey, timelock_index: u32, reclaim_to: Option<Pubkey>, mode: CancelMode) -> CommandResult {
+    let canceler = config.payer.pubkey();
+    let reclaim_to = reclaim_to.unwrap_or(canceler);
+    let mut data = token_lock_data(config, token)?;
+    let account = TokenLockAccount::load_mut(&mut data)?;
+    let cancel_threshold = cancel_threshold_of(&account, target, timelock_index)?;
+    if cancel_threshold > 1 {
+        let (proposal, _) = CancelProposal::find_address(token, target, timelock_index, &tokenlock::id());
+        let instruction = TokenLockInstruction::propose_cancel(token, &canceler, target, timelock_index, &reclaim_to, mode);
+        let mut output = send(config, &[instruction])?;
+        output["proposal"] = json!(proposal.to_string());
+        output["approvals_needed"] = json!(cancel_threshold - 1);
+        return Ok(output);
+    }
+
+    let token_program = token_program_of(config, &account.mint)?;
+    let instruction = TokenLockInstruction::cancel_timelock(
+        token,
+        &canceler,
+        &account.mint,
+        &token_program,
+        target,
+        &destination_of(target, &account.mint, &token_program),
+        timelock_index,
+        &reclaim_to,
+        &destination_of(&reclaim_to, &account.mint, &token_program),
+        mode,
+    );
+    send(config, &[instruction])
+}
+
+/// Approves a cancellation proposal and executes it in the same transaction if this is the last approval needed.
+pub fn approve(config: &Config, token: &Pubkey, target: &Pubkey, timelock_index: u32) -> CommandResult {
+    let canceler = config.payer.pubkey();
+    let mut data = token_lock_data(config, token)?;
+    let account = TokenLockAccount::load_mut(&mut data)?;
+    let cancel_threshold = cancel_threshold_of(&account, target, timelock_index)?;
+    let (proposal_key, _) = CancelProposal::find_address(token, target, timelock_index, &tokenlock::id());
+    let proposal = CancelProposal::deserialize(&mut &config.rpc_client.get_account_data(&proposal_key)?[..])?;
+
+    let mut instructions = vec![TokenLockInstruction::approve_cancel(token, &canceler, target, timelock_index)];
+    let approvals = proposal.approvals.len() + !proposal.has_approval(&canceler) as usize;
+    let execute = approvals >= cancel_threshold as usize;
+    if execute {
+        let token_program = token_program_of(config, &account.mint)?;
+        instructions.push(TokenLockInstruction::execute_cancel(
+            token,
+            &canceler,
+            &proposal.proposer,
+            &account.mint,
+            &token_program,
+            target,
+            &destination_of(target, &account.mint, &token_program),
+            timelock_index,
+            &destination_of(&proposal.reclaim_token_to, &account.mint, &token_program),
+        ));
+    }
+    let mut output = send(config, &instructions)?;
+    output["approvals"] = json!(approvals);
+    output["executed"] = json!(execute);
+    Ok(output)
+}
+
+/// Withdraws a cancellation proposal the signer opened, or any proposal once it expired.
+pub fn close_proposal(config: &Config, token: &Pubkey, target: &Pubkey, timelock_index: u32) -> CommandResult {
+    let (proposal_key, _) = CancelProposal::find_address(token, target, timelock_index, &tokenlock::id());
+    let proposal = CancelProposal::deserialize(&mut &config.rpc_client.get_account_data(&proposal_key)?[..])?;
+    let signer = config.payer.pubkey();
+    let signers = if proposal.proposer == signer { vec![signer] } else { vec![] };
+    let instruction = TokenLockInstruction::close_cancel_proposal(token, &proposal.proposer, &signers, target, timelock_index);
+    let mut output = send(config, &[instruction])?;
+    output["proposal"] = json!(proposal_key.to_string());
+    output["expires_at"] = json!(proposal.expires_at);
+    Ok(output)
+}
+
+/// Prints the output as JSON, or as indented `key: value` lines
+pub fn print(output: &Value, format: OutputFormat) {
+    match format {
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(output).unwrap()),
+        OutputFormat::Display => print_object(output.as_object().unwrap_or(&Map::new()), 0),
+    }
+}
+
+fn print_object(object: &Map<String, Value>, indent: usize) {
+    for (key, value) in object {
+        match value {
+            Value::Object(nested) => {
+                println!("{:indent$}{}:", "", key, indent = indent);
+                print_object(nested, indent + 2);
+            }
+            Value::Array(items) if items.iter().all(Value::is_object) && !items.is_empty() => {
+                println!("{:indent$}{}:", "", key, indent = indent);
+                for item in items {
+                    println!("{:indent$}-", "", indent = indent + 2);
+                    print_object(item.as_object().unwrap(), indent + 4);
+                }
+            }
+            Value::Array(items) => {
+                let items: Vec<String> = items.iter().map(display_value).collect();
+                println!("{:indent$}{}: {}", "", key, items.join(", "), indent = indent);
+            }
+            _ => println!("{:indent$}{}: {}", "", key, display_value(value), indent = indent),
+        }
+    }
+}
+
+fn display_value(value: &Value) -> String {
+    match value {
+        Value::String(string) => string.clone(),
+        Value::Null => "-".to_string(),
+        _ => value.to_string(),
+    }
+}
@@ -0,0 +1,438 @@
+//! Bulk grant import. Grants are read from a CSV or JSON file and checked off-chain with the
+//! program's own `create_release_schedule` and `fund_release_schedule` against a copy of the
+//! token lock account, so a file is only imported if every grant would succeed. The result is a
+//! plan of transactions whose progress is saved after every step, so an interrupted import resumes
+//! where it stopped instead of funding a grant twice.
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt,
+    fs,
+    io::Read,
+    path::Path,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Deserializer, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use tokenlock::{
+    processor::Processor,
+    solana_program::program_stubs::{set_syscall_stubs, SyscallStubs},
+    types::{ReleaseSchedule, TokenLockAccount},
+};
+
+/// A grant as written in a grant file. In CSV files the cancelers are separated by ';',
+/// in JSON files they can also be a list.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GrantRow {
+    pub recipient: String,
+    pub amount: u32,
+    pub commencement_timestamp: u32,
+    pub release_count: u32,
+    pub delay_until_first_release_in_seconds: u32,
+    pub initial_release_portion_in_bips: u32,
+    pub period_between_releases_in_seconds: u32,
+    #[serde(default, deserialize_with = "deserialize_cancelers")]
+    pub cancelable_by: Vec<String>,
+    #[serde(default)]
+    pub cancel_threshold: u8,
+}
+
+impl GrantRow {
+    pub fn schedule(&self) -> ScheduleParams {
+        ScheduleParams {
+            release_count: self.release_count,
+            delay_until_first_release_in_seconds: self.delay_until_first_release_in_seconds,
+            initial_release_portion_in_bips: self.initial_release_portion_in_bips,
+            period_between_releases_in_seconds: self.period_between_releases_in_seconds,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Cancelers {
+    List(Vec<String>),
+    Joined(String),
+}
+
+fn deserialize_cancelers<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    let cancelers = match Cancelers::deserialize(deserializer)? {
+        Cancelers::List(list) => list,
+        Cancelers::Joined(joined) => joined.split(';').map(str::to_string).collect(),
+    };
+    Ok(cancelers.into_iter().map(|canceler| canceler.trim().to_string()).filter(|canceler| !canceler.is_empty()).collect())
+}
+
+/// Reads a grant file, JSON if its extension is `.json` and CSV with a header row otherwise.
+pub fn read_grants(path: &Path) -> Result<Vec<GrantRow>, Box<dyn Error>> {
+    let file = fs::File::open(path)?;
+    if path.extension().and_then(|extension| extension.to_str()).map_or(false, |extension| extension.eq_ignore_ascii_case("json")) {
+        parse_json(file)
+    } else {
+        parse_csv(file)
+    }
+}
+
+pub fn parse_csv<R: Read>(reader: R) -> Result<Vec<GrantRow>, Box<dyn Error>> {
+    let grants = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader)
+        .deserialize()
+        .collect::<Result<Vec<GrantRow>, _>>()?;
+    Ok(grants)
+}
+
+pub fn parse_json<R: Read>(reader: R) -> Result<Vec<GrantRow>, Box<dyn Error>> {
+    Ok(serde_json::from_reader(reader)?)
+}
+
+/// The parameters of a release schedule, grants with the same parameters share one schedule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ScheduleParams {
+    pub release_count: u32,
+    pub delay_until_first_release_in_seconds: u32,
+    pub initial_release_portion_in_bips: u32,
+    pub period_between_releases_in_seconds: u32,
+}
+
+impl From<ScheduleParams> for ReleaseSchedule {
+    fn from(params: ScheduleParams) -> Self {
+        ReleaseSchedule {
+            release_count: params.release_count,
+            delay_until_first_release_in_seconds: params.delay_until_first_release_in_seconds,
+            initial_release_portion_in_bips: params.initial_release_portion_in_bips,
+            period_between_releases_in_seconds: params.period_between_releases_in_seconds,
+        }
+    }
+}
+
+impl From<&ReleaseSchedule> for ScheduleParams {
+    fn from(schedule: &ReleaseSchedule) -> Self {
+        ScheduleParams {
+            release_count: schedule.release_count,
+            delay_until_first_release_in_seconds: schedule.delay_until_first_release_in_seconds,
+            initial_release_portion_in_bips: schedule.initial_release_portion_in_bips,
+            period_between_releases_in_seconds: schedule.period_between_releases_in_seconds,
+        }
+    }
+}
+
+/// A release schedule the grants of the plan use
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlannedSchedule {
+    pub params: ScheduleParams,
+    /// The id of the schedule on chain, unknown until a schedule the plan creates is created
+    pub schedule_id: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    /// Create `schedules[schedule]` of the plan
+    CreateSchedule { schedule: usize },
+    /// Fund the grant of a row of the grant file with `schedules[schedule]` of the plan
+    Fund {
+        row: usize,
+        recipient: String,
+        amount: u32,
+        commencement_timestamp: u32,
+        schedule: usize,
+        cancelable_by: Vec<String>,
+        cancel_threshold: u8,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Status {
+    Pending,
+    /// Signed and possibly sent, the transaction's status has to be checked before sending it again
+    Sent { signature: String, blockhash: String },
+    Done { signature: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Step {
+    pub action: Action,
+    pub status: Status,
+}
+
+/// The transactions importing a grant file, one step per transaction, and their progress
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Plan {
+    pub token: String,
+    pub schedules: Vec<PlannedSchedule>,
+    pub steps: Vec<Step>,
+}
+
+impl Plan {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Ok(serde_json::from_reader(fs::File::open(path)?)?)
+    }
+
+    /// Writes the plan to a temporary file first, so an interrupted save doesn't lose the progress.
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// The first step that is not done
+    pub fn next_step(&self) -> Option<usize> {
+        self.steps.iter().position(|step| !matches!(step.status, Status::Done { .. }))
+    }
+
+    pub fn done(&self) -> usize {
+        self.steps.iter().filter(|step| matches!(step.status, Status::Done { .. })).count()
+    }
+}
+
+/// Why a row of the grant file can't be imported, row 0 stands for the file or the account as a whole
+#[derive(Debug, Clone, PartialEq)]
+pub struct RowError {
+    pub row: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "row {}: {}", self.row, self.reason)
+    }
+}
+
+/// The rows that failed the preflight
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreflightError(pub Vec<RowError>);
+
+impl fmt::Display for PreflightError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let errors: Vec<String> = self.0.iter().map(RowError::to_string).collect();
+        write!(f, "{} grant(s) failed the preflight\n{}", self.0.len(), errors.join("\n"))
+    }
+}
+
+impl Error for PreflightError {}
+
+/// Collects the program's log messages, which explain why a check failed
+struct LogCapture {
+    messages: Arc<Mutex<Vec<String>>>,
+}
+
+impl SyscallStubs for LogCapture {
+    fn sol_log(&self, message: &str) {
+        self.messages.lock().unwrap().push(message.to_string());
+    }
+}
+
+lazy_static! {
+    /// The syscall stubs are global, only one preflight captures the logs at a time
+    static ref PREFLIGHT: Mutex<()> = Mutex::new(());
+}
+
+/// Checks every grant against a copy of the token lock account at `timestamp` and plans the import.
+/// Schedules with the same parameters are created once, schedules already in the account are reused.
+pub fn build_plan(token: &Pubkey, token_lock_data: &[u8], timestamp: u32, grants: &[GrantRow]) -> Result<Plan, PreflightError> {
+    let _preflight = PREFLIGHT.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let messages = Arc::new(Mutex::new(vec![]));
+    let previous_stubs = set_syscall_stubs(Box::new(LogCapture { messages: messages.clone() }));
+    let plan = check_grants(token, token_lock_data, timestamp, grants, &messages);
+    set_syscall_stubs(previous_stubs);
+    plan
+}
+
+fn check_grants(token: &Pubkey, token_lock_data: &[u8], timestamp: u32, grants: &[GrantRow], messages: &Mutex<Vec<String>>) -> Result<Plan, PreflightError> {
+    let account_error = |reason: String| PreflightError(vec![RowError { row: 0, reason }]);
+    let last_message = || messages.lock().unwrap().pop().unwrap_or_else(|| "rejected".to_string());
+
+    // a copy of the account with room for every grant
+    let mut scratch = token_lock_data.to_vec();
+    let timelock_count = TokenLockAccount::load_mut(&mut scratch)
+        .map_err(|err| account_error(format!("token lock account: {}", err)))?
+        .timelocks()
+        .len();
+    scratch.resize(scratch.len().max(TokenLockAccount::space(timelock_count + grants.len())), 0);
+    let mut processor = Processor {
+        account_key: token,
+        account_info: TokenLockAccount::load_mut(&mut scratch).map_err(|err| account_error(format!("token lock account: {}", err)))?,
+        modified: false,
+        timestamp,
+    };
+
+    let mut schedules: Vec<PlannedSchedule> = vec![];
+    let mut steps = vec![];
+    // the plan's schedule and its id in the copy, or why the schedule is invalid
+    let mut schedule_of: HashMap<ScheduleParams, Result<(usize, u32), String>> = HashMap::new();
+    for (schedule_id, schedule) in processor.account_info.release_schedules().iter().enumerate() {
+        let params = ScheduleParams::from(schedule);
+        schedule_of.entry(params).or_insert_with(|| {
+            schedules.push(PlannedSchedule { params, schedule_id: Some(schedule_id as u32) });
+            Ok((schedules.len() - 1, schedule_id as u32))
+        });
+    }
+
+    let mut errors = vec![];
+    for (idx, grant) in grants.iter().enumerate() {
+        let row = idx + 1;
+        let recipient = match Pubkey::from_str(&grant.recipient) {
+            Ok(recipient) => recipient,
+            Err(err) => {
+                errors.push(RowError { row, reason: format!("recipient {}: {}", grant.recipient, err) });
+                continue;
+            }
+        };
+        let cancelable_by = match grant.cancelable_by.iter().map(|canceler| Pubkey::from_str(canceler)).collect::<Result<Vec<_>, _>>() {
+            Ok(cancelable_by) => cancelable_by,
+            Err(err) => {
+                errors.push(RowError { row, reason: format!("cancelable_by: {}", err) });
+                continue;
+            }
+        };
+
+        let params = grant.schedule();
+        let planned = schedule_of.entry(params).or_insert_with(|| {
+            processor.create_release_schedule(params.into())
+                .map(|schedule_id| {
+                    schedules.push(PlannedSchedule { params, schedule_id: None });
+                    steps.push(Step { action: Action::CreateSchedule { schedule: schedules.len() - 1 }, status: Status::Pending });
+                    (schedules.len() - 1, schedule_id)
+                })
+                .map_err(|_| format!("schedule: {}", last_message()))
+        });
+        let (schedule, schedule_id) = match planned {
+            Ok(planned) => *planned,
+            Err(reason) => {
+                errors.push(RowError { row, reason: reason.clone() });
+                continue;
+            }
+        };
+
+        if processor.fund_release_schedule(&recipient, grant.amount, grant.commencement_timestamp, schedule_id, &cancelable_by, grant.cancel_threshold).is_err() {
+            errors.push(RowError { row, reason: last_message() });
+            continue;
+        }
+        steps.push(Step {
+            action: Action::Fund {
+                row,
+                recipient: recipient.to_string(),
+                amount: grant.amount,
+                commencement_timestamp: grant.commencement_timestamp,
+                schedule,
+                cancelable_by: cancelable_by.iter().map(Pubkey::to_string).collect(),
+                cancel_threshold: grant.cancel_threshold,
+            },
+            status: Status::Pending,
+        });
+    }
+    if !errors.is_empty() {
+        return Err(PreflightError(errors));
+    }
+
+    // create the schedules before funding any grant
+    steps.sort_by_key(|step| !matches!(step.action, Action::CreateSchedule { .. }));
+    Ok(Plan { token: token.to_string(), schedules, steps })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CSV: &str = "recipient,amount,commencement_timestamp,release_count,delay_until_first_release_in_seconds,initial_release_portion_in_bips,period_between_releases_in_seconds,cancelable_by,cancel_threshold
+{owner},1000,1000,4,0,2500,100,{canceler},1
+{other},400,1000,4,0,2500,100,,0
+{owner},600,1000,1,0,10000,0,{canceler};{other},2
+";
+
+    fn token_lock(schedules: &[ScheduleParams]) -> Vec<u8> {
+        let mut data = vec![0; TokenLockAccount::space(1)];
+        let mut account = TokenLockAccount::init(&mut data, &Pubkey::new_unique(), &Pubkey::new_unique(), 0, 1000, 100, None).unwrap();
+        for schedule in schedules {
+            account.add_release_schedule((*schedule).into()).unwrap();
+        }
+        data
+    }
+
+    fn grants(owner: &Pubkey, other: &Pubkey, canceler: &Pubkey) -> Vec<GrantRow> {
+        let csv = CSV.replace("{owner}", &owner.to_string())
+            .replace("{other}", &other.to_string())
+            .replace("{canceler}", &canceler.to_string());
+        parse_csv(csv.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn csv_and_json_rows_match() {
+        let (owner, other, canceler) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let rows = grants(&owner, &other, &canceler);
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[1].cancelable_by, Vec::<String>::new());
+        assert_eq!(rows[2].cancelable_by, vec![canceler.to_string(), other.to_string()]);
+
+        let json = serde_json::to_string(&rows).unwrap();
+        assert_eq!(parse_json(json.as_bytes()).unwrap(), rows);
+        let joined = json.replacen(&format!("[\"{}\",\"{}\"]", canceler, other), &format!("\"{};{}\"", canceler, other), 1);
+        assert_eq!(parse_json(joined.as_bytes()).unwrap(), rows);
+    }
+
+    #[test]
+    fn plan_dedupes_schedules() {
+        let (owner, other, canceler) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let rows = grants(&owner, &other, &canceler);
+        let existing = rows[2].schedule();
+        let plan = build_plan(&Pubkey::new_unique(), &token_lock(&[existing]), 500, &rows).unwrap();
+
+        // the existing one-off schedule is reused, the shared quarterly schedule is created once
+        assert_eq!(plan.schedules, vec![
+            PlannedSchedule { params: existing, schedule_id: Some(0) },
+            PlannedSchedule { params: rows[0].schedule(), schedule_id: None },
+        ]);
+        assert_eq!(plan.steps.len(), 4);
+        assert_eq!(plan.steps[0].action, Action::CreateSchedule { schedule: 1 });
+        let funded: Vec<(usize, usize)> = plan.steps[1..].iter().map(|step| match step.action {
+            Action::Fund { row, schedule, .. } => (row, schedule),
+            _ => panic!("expected a fund step"),
+        }).collect();
+        assert_eq!(funded, vec![(1, 1), (2, 1), (3, 0)]);
+        assert_eq!(plan.next_step(), Some(0));
+    }
+
+    #[test]
+    fn preflight_reports_every_bad_row() {
+        let (owner, other, canceler) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut rows = grants(&owner, &other, &canceler);
+        // below the minimum timelock amount
+        rows[0].amount = 99;
+        // first release after the max release delay
+        rows[1].delay_until_first_release_in_seconds = 1001;
+        // threshold above the cancelers
+        rows[2].cancel_threshold = 3;
+        let mut bad_recipient = rows[0].clone();
+        bad_recipient.recipient = "not an address".to_string();
+        rows.push(bad_recipient);
+
+        let errors = build_plan(&Pubkey::new_unique(), &token_lock(&[]), 500, &rows).unwrap_err().0;
+        assert_eq!(errors.iter().map(|error| error.row).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+        assert_eq!(errors[0].reason, "amount < min funding");
+        assert_eq!(errors[1].reason, "schedule: first release > max delay");
+        assert_eq!(errors[2].reason, "threshold > cancelableBy");
+    }
+
+    #[test]
+    fn plan_progress_survives_a_restart() {
+        let (owner, other, canceler) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut plan = build_plan(&Pubkey::new_unique(), &token_lock(&[]), 500, &grants(&owner, &other, &canceler)).unwrap();
+        plan.steps[0].status = Status::Done { signature: "created".to_string() };
+        plan.schedules[0].schedule_id = Some(0);
+        plan.steps[1].status = Status::Sent { signature: "funding".to_string(), blockhash: "hash".to_string() };
+
+        let path = std::env::temp_dir().join(format!("plan-{}.json", Pubkey::new_unique()));
+        plan.save(&path).unwrap();
+        let loaded = Plan::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, plan);
+        assert_eq!(loaded.next_step(), Some(1));
+        assert_eq!(loaded.done(), 1);
+    }
+}
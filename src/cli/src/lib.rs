@@ -0,0 +1,3 @@
+//! Off-chain tooling shared by the tokenlock-cli commands
+pub mod grants;
+pub mod report;
@@ -0,0 +1,331 @@
+//! Command line tool for operating token lockups
+mod command;
+
+use std::{process::exit, str::FromStr};
+use clap::{crate_description, crate_name, crate_version, value_t_or_exit, values_t, App, AppSettings, Arg, ArgMatches, SubCommand};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::read_keypair_file};
+use tokenlock::types::CancelMode;
+use tokenlock_cli::report::ReportFormat;
+use command::{Config, OutputFormat};
+
+fn is_pubkey(value: String) -> Result<(), String> {
+    Pubkey::from_str(&value).map(|_| ()).map_err(|err| format!("{}: {}", value, err))
+}
+
+fn pubkey_arg(name: &'static str, help: &'static str) -> Arg<'static, 'static> {
+    Arg::with_name(name)
+        .long(name)
+        .value_name("ADDRESS")
+        .takes_value(true)
+        .validator(is_pubkey)
+        .help(help)
+}
+
+fn number_arg(name: &'static str, help: &'static str) -> Arg<'static, 'static> {
+    Arg::with_name(name)
+        .long(name)
+        .value_name("NUMBER")
+        .takes_value(true)
+        .help(help)
+}
+
+fn token_arg() -> Arg<'static, 'static> {
+    pubkey_arg("token", "The token lock account").required(true)
+}
+
+fn pubkey_of(matches: &ArgMatches, name: &str) -> Option<Pubkey> {
+    matches.value_of(name).map(|value| Pubkey::from_str(value).unwrap())
+}
+
+fn default_keypair_path() -> String {
+    let home = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+    format!("{}/.config/solana/id.json", home)
+}
+
+fn main() {
+    let default_keypair = default_keypair_path();
+    let matches = App::new(crate_name!())
+        .about(crate_description!())
+        .version(crate_version!())
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .arg(
+            Arg::with_name("url")
+                .short("u")
+                .long("url")
+                .value_name("URL")
+                .takes_value(true)
+                .global(true)
+                .default_value("http://localhost:8899")
+                .help("JSON RPC URL of the cluster"),
+        )
+        .arg(
+            Arg::with_name("keypair")
+                .short("k")
+                .long("keypair")
+                .value_name("PATH")
+                .takes_value(true)
+                .global(true)
+                .default_value(&default_keypair)
+                .help("Keypair file of the signer, who also pays the fees"),
+        )
+        .arg(
+            Arg::with_name("dry_run")
+                .long("dry-run")
+                .global(true)
+                .help("Simulate the transactions instead of sending them"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .long("output")
+                .value_name("FORMAT")
+                .takes_value(true)
+                .global(true)
+                .possible_values(&["display", "json"])
+                .default_value("display")
+                .help("Output format"),
+        )
+        .subcommand(
+            SubCommand::with_name("init")
+                .about("Create a token lock account and its vault for a mint")
+                .arg(pubkey_arg("mint", "The mint of the locked tokens, the native mint for SOL").required(true))
+                .arg(number_arg("nonce", "Tells apart several lockups of the same mint").default_value("0"))
+                .arg(number_arg("max-release-delay", "Maximum delay until the first release of a schedule in seconds").required(true))
+                .arg(number_arg("min-timelock-amount", "Minimum amount of a timelock").required(true))
+                .arg(number_arg("timelocks", "Number of timelocks to allocate room for, at most 25, the account can't grow").default_value("10"))
+                .arg(pubkey_arg("treasury-authority", "May sweep vault tokens not owed to any timelock")),
+        )
+        .subcommand(
+            SubCommand::with_name("create-schedule")
+                .about("Add a release schedule to a token lock account")
+                .arg(token_arg())
+                .arg(number_arg("release-count", "Number of releases").required(true))
+                .arg(number_arg("delay", "Seconds from the commencement until the first release").required(true))
+                .arg(number_arg("initial-bips", "Portion released at the first release in bips").required(true))
+                .arg(number_arg("period", "Seconds between the releases after the first").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("fund")
+                .about("Lock tokens for a recipient on a release schedule")
+                .arg(token_arg())
+                .arg(pubkey_arg("to", "The recipient of the timelock").required(true))
+                .arg(number_arg("amount", "Amount to lock").required(true))
+                .arg(number_arg("commencement", "Unix timestamp the schedule starts at").required(true))
+                .arg(number_arg("schedule-id", "The release schedule").required(true))
+                .arg(pubkey_arg("canceler", "May cancel the timelock, can be repeated").multiple(true).number_of_values(1))
+                .arg(number_arg("cancel-threshold", "Cancelers needed to cancel").default_value("0"))
+                .arg(pubkey_arg("source", "Token account to fund from, defaults to the signer's associated token account")),
+        )
+        .subcommand(
+            SubCommand::with_name("fund-csv")
+                .about("Fund a timelock for each row of a CSV file")
+                .arg(token_arg())
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .required(true)
+                        .help("CSV with the columns recipient,amount,commencement_timestamp,schedule_id,cancelable_by,cancel_threshold, cancelers separated by ';'"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("import-grants")
+                .about("Check every grant of a CSV or JSON file, then create their schedules and fund them")
+                .arg(token_arg())
+                .arg(
+                    Arg::with_name("file")
+                        .value_name("FILE")
+                        .required(true)
+                        .help("Grants with recipient, amount, commencement_timestamp, the schedule parameters, cancelable_by and cancel_threshold"),
+                )
+                .arg(
+                    Arg::with_name("plan")
+                        .long("plan")
+                        .value_name("PATH")
+                        .takes_value(true)
+                        .help("Where the progress of the import is saved, an existing plan is resumed [default: FILE.plan.json]"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("balance")
+                .about("Show the locked and unlocked balance of an address")
+                .arg(token_arg())
+                .arg(pubkey_arg("owner", "The address to show, defaults to the signer")),
+        )
+        .subcommand(
+            SubCommand::with_name("timelocks")
+                .about("List the timelocks of a token lock account")
+                .arg(token_arg())
+                .arg(pubkey_arg("recipient", "Only list the timelocks of this address")),
+        )
+        .subcommand(
+            SubCommand::with_name("report")
+                .about("Show when each timelock releases how many tokens")
+                .arg(token_arg())
+                .arg(pubkey_arg("recipient", "Only report the timelocks of this address"))
+                .arg(
+                    Arg::with_name("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["csv", "json", "markdown"])
+                        .default_value("markdown")
+                        .help("Format of the release table"),
+                )
+                .arg(
+                    Arg::with_name("out")
+                        .long("out")
+                        .value_name("FILE")
+                        .takes_value(true)
+                        .help("Write the release table to a file instead of stdout"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("transfer")
+                .about("Transfer unlocked tokens out of one of the signer's timelocks")
+                .arg(token_arg())
+                .arg(pubkey_arg("to", "The receiver of the tokens").required(true))
+                .arg(number_arg("amount", "Amount to transfer").required(true))
+                .arg(number_arg("timelock-index", "The signer's timelock").required(true))
+                .arg(pubkey_arg("destination", "Token account receiving the tokens, defaults to the associated token account of --to")),
+        )
+        .subcommand(
+            SubCommand::with_name("cancel")
+                .about("Cancel a timelock, or propose cancelling it if it needs several cancelers")
+                .arg(token_arg())
+                .arg(pubkey_arg("target", "The recipient of the timelock").required(true))
+                .arg(number_arg("timelock-index", "The timelock to cancel").required(true))
+                .arg(pubkey_arg("reclaim-to", "Receives the locked tokens, defaults to the signer"))
+                .arg(
+                    Arg::with_name("mode")
+                        .long("mode")
+                        .takes_value(true)
+                        .possible_values(&["pay-out", "stop-vesting"])
+                        .default_value("pay-out")
+                        .help("Pay the unlocked tokens out, or stop vesting and leave them claimable"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("approve")
+                .about("Approve a cancellation proposal, executing it once it has enough approvals")
+                .arg(token_arg())
+                .arg(pubkey_arg("target", "The recipient of the timelock").required(true))
+                .arg(number_arg("timelock-index", "The timelock the proposal cancels").required(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("close-proposal")
+                .about("Withdraw a cancellation proposal you opened, or any proposal once it expired")
+                .arg(token_arg())
+                .arg(pubkey_arg("target", "The recipient of the timelock").required(true))
+                .arg(number_arg("timelock-index", "The timelock the proposal cancels").required(true)),
+        )
+        .get_matches();
+
+    let keypair_path = matches.value_of("keypair").unwrap();
+    let payer = read_keypair_file(keypair_path).unwrap_or_else(|err| {
+        eprintln!("error: reading keypair file {}: {}", keypair_path, err);
+        exit(1);
+    });
+    let config = Config {
+        rpc_client: RpcClient::new_with_commitment(matches.value_of("url").unwrap().to_string(), CommitmentConfig::confirmed()),
+        payer,
+        dry_run: matches.is_present("dry_run"),
+        output: if matches.value_of("output") == Some("json") { OutputFormat::Json } else { OutputFormat::Display },
+    };
+
+    let result = match matches.subcommand() {
+        ("init", Some(arg_matches)) => command::init(
+            &config,
+            &pubkey_of(arg_matches, "mint").unwrap(),
+            value_t_or_exit!(arg_matches, "nonce", u8),
+            value_t_or_exit!(arg_matches, "max-release-delay", u32),
+            value_t_or_exit!(arg_matches, "min-timelock-amount", u32),
+            value_t_or_exit!(arg_matches, "timelocks", usize),
+            pubkey_of(arg_matches, "treasury-authority"),
+        ),
+        ("create-schedule", Some(arg_matches)) => command::create_schedule(
+            &config,
+            &pubkey_of(arg_matches, "token").unwrap(),
+            value_t_or_exit!(arg_matches, "release-count", u32),
+            value_t_or_exit!(arg_matches, "delay", u32),
+            value_t_or_exit!(arg_matches, "initial-bips", u32),
+            value_t_or_exit!(arg_matches, "period", u32),
+        ),
+        ("fund", Some(arg_matches)) => command::fund(
+            &config,
+            &pubkey_of(arg_matches, "token").unwrap(),
+            &command::Grant {
+                recipient: pubkey_of(arg_matches, "to").unwrap(),
+                amount: value_t_or_exit!(arg_matches, "amount", u32),
+                commencement_timestamp: value_t_or_exit!(arg_matches, "commencement", u32),
+                schedule_id: value_t_or_exit!(arg_matches, "schedule-id", u32),
+                cancelable_by: values_t!(arg_matches, "canceler", Pubkey).unwrap_or_default(),
+                cancel_threshold: value_t_or_exit!(arg_matches, "cancel-threshold", u8),
+            },
+            pubkey_of(arg_matches, "source"),
+        ),
+        ("fund-csv", Some(arg_matches)) => command::fund_csv(
+            &config,
+            &pubkey_of(arg_matches, "token").unwrap(),
+            arg_matches.value_of("file").unwrap(),
+        ),
+        ("import-grants", Some(arg_matches)) => {
+            let file = arg_matches.value_of("file").unwrap();
+            let plan = arg_matches.value_of("plan").map(str::to_string).unwrap_or_else(|| format!("{}.plan.json", file));
+            command::import_grants(&config, &pubkey_of(arg_matches, "token").unwrap(), file, &plan)
+        }
+        ("balance", Some(arg_matches)) => command::balance(
+            &config,
+            &pubkey_of(arg_matches, "token").unwrap(),
+            pubkey_of(arg_matches, "owner"),
+        ),
+        ("timelocks", Some(arg_matches)) => command::timelocks(
+            &config,
+            &pubkey_of(arg_matches, "token").unwrap(),
+            pubkey_of(arg_matches, "recipient"),
+        ),
+        ("report", Some(arg_matches)) => command::report(
+            &config,
+            &pubkey_of(arg_matches, "token").unwrap(),
+            pubkey_of(arg_matches, "recipient"),
+            value_t_or_exit!(arg_matches, "format", ReportFormat),
+            arg_matches.value_of("out"),
+        ),
+        ("transfer", Some(arg_matches)) => command::transfer(
+            &config,
+            &pubkey_of(arg_matches, "token").unwrap(),
+            &pubkey_of(arg_matches, "to").unwrap(),
+            value_t_or_exit!(arg_matches, "amount", u32),
+            value_t_or_exit!(arg_matches, "timelock-index", u32),
+            pubkey_of(arg_matches, "destination"),
+        ),
+        ("cancel", Some(arg_matches)) => command::cancel(
+            &config,
+            &pubkey_of(arg_matches, "token").unwrap(),
+            &pubkey_of(arg_matches, "target").unwrap(),
+            value_t_or_exit!(arg_matches, "timelock-index", u32),
+            pubkey_of(arg_matches, "reclaim-to"),
+            if arg_matches.value_of("mode") == Some("stop-vesting") { CancelMode::StopVesting } else { CancelMode::PayOut },
+        ),
+        ("approve", Some(arg_matches)) => command::approve(
+            &config,
+            &pubkey_of(arg_matches, "token").unwrap(),
+            &pubkey_of(arg_matches, "target").unwrap(),
+            value_t_or_exit!(arg_matches, "timelock-index", u32),
+        ),
+        ("close-proposal", Some(arg_matches)) => command::close_proposal(
+            &config,
+            &pubkey_of(arg_matches, "token").unwrap(),
+            &pubkey_of(arg_matches, "target").unwrap(),
+            value_t_or_exit!(arg_matches, "timelock-index", u32),
+        ),
+        _ => unreachable!(),
+    };
+
+    match result {
+        Ok(output) if output.is_null() => {}
+        Ok(output) => command::print(&output, config.output),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            exit(1);
+        }
+    }
+}
@@ -0,0 +1,228 @@
+//! Vesting timeline reports. Every release of a timelock is listed with its date, the amount it
+//! unlocks and the running total, computed with the program's own `calculate_unlocked` so the
+//! amounts match what unlocks on chain to the smallest unit, including the remainder the last
+//! release unlocks. Transfers out of a timelock don't change its releases and are not listed.
+use std::{error::Error, io::Write, str::FromStr};
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+use tokenlock::{
+    processor::Processor,
+    types::{ReleaseSchedule, Timelock, TokenLockAccount},
+};
+
+/// Tokens of a timelock unlocking at one point in time
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ReleaseEvent {
+    /// Unix timestamp of the release
+    pub timestamp: u32,
+    /// The timestamp as a UTC date
+    pub date: String,
+    pub recipient: String,
+    pub timelock_index: usize,
+    pub schedule_id: u32,
+    /// Number of the release in its schedule, from 1. 0 for the release of a timelock whose vesting was stopped
+    pub release: u32,
+    pub amount: u32,
+    /// Unlocked in the timelock after this release
+    pub unlocked: u32,
+    pub total_amount: u32,
+    /// Unlocked by all releases of the report up to this one
+    pub cumulative: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    Csv,
+    Json,
+    Markdown,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "csv" => Ok(ReportFormat::Csv),
+            "json" => Ok(ReportFormat::Json),
+            "markdown" | "md" => Ok(ReportFormat::Markdown),
+            _ => Err(format!("unknown report format {}", format)),
+        }
+    }
+}
+
+/// The releases of one timelock in time order, leaving out releases that unlock nothing
+pub fn timelock_releases(recipient: &Pubkey, timelock_index: usize, timelock: &Timelock, release_schedule: &ReleaseSchedule) -> Vec<ReleaseEvent> {
+    let event = |timestamp: u32, release: u32, amount: u32, unlocked: u32| ReleaseEvent {
+        timestamp,
+        date: format_date(timestamp),
+        recipient: recipient.to_string(),
+        timelock_index,
+        schedule_id: timelock.schedule_id,
+        release,
+        amount,
+        unlocked,
+        total_amount: timelock.total_amount,
+        cumulative: 0,
+    };
+    // the total of a stopped timelock was cut down to what had unlocked when vesting stopped
+    if timelock.vesting_stopped_at != 0 {
+        return vec![event(timelock.vesting_stopped_at, 0, timelock.total_amount, timelock.total_amount)];
+    }
+
+    let mut events = vec![];
+    let mut unlocked = 0;
+    for release in 0..release_schedule.release_count {
+        let offset = release_schedule.delay_until_first_release_in_seconds as u64
+            + release_schedule.period_between_releases_in_seconds as u64 * release as u64;
+        let timestamp = (timelock.commencement_timestamp as u64 + offset).min(u32::MAX as u64) as u32;
+        let unlocked_now = Processor::calculate_unlocked(timelock.commencement_timestamp, timestamp, timelock.total_amount, release_schedule);
+        if unlocked_now > unlocked {
+            events.push(event(timestamp, release + 1, unlocked_now - unlocked, unlocked_now));
+            unlocked = unlocked_now;
+        }
+    }
+    events
+}
+
+/// The releases of every timelock of the account, or of one recipient's timelocks, in time order
+/// with their running total
+pub fn release_timeline(account: &TokenLockAccount, recipient: Option<&Pubkey>) -> Vec<ReleaseEvent> {
+    let mut events: Vec<ReleaseEvent> = account.timelocks().iter().enumerate()
+        .filter(|(_, timelock)| timelock.total_amount > 0 && recipient.map_or(true, |recipient| timelock.recipient == *recipient))
+        .flat_map(|(idx, timelock)| match account.release_schedules().get(timelock.schedule_id as usize) {
+            Some(release_schedule) => timelock_releases(&timelock.recipient, idx, timelock, release_schedule),
+            None => vec![],
+        })
+        .collect();
+    events.sort_by_key(|event| (event.timestamp, event.timelock_index));
+
+    let mut cumulative = 0;
+    for event in &mut events {
+        cumulative += event.amount as u64;
+        event.cumulative = cumulative;
+    }
+    events
+}
+
+pub fn write_report<W: Write>(events: &[ReleaseEvent], format: ReportFormat, mut writer: W) -> Result<(), Box<dyn Error>> {
+    match format {
+        ReportFormat::Csv => {
+            let mut csv_writer = csv::Writer::from_writer(writer);
+            for event in events {
+                csv_writer.serialize(event)?;
+            }
+            csv_writer.flush()?;
+        }
+        ReportFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, events)?;
+            writeln!(writer)?;
+        }
+        ReportFormat::Markdown => {
+            writeln!(writer, "| Date (UTC) | Recipient | Timelock | Release | Amount | Unlocked | Total | Cumulative |")?;
+            writeln!(writer, "|---|---|---:|---:|---:|---:|---:|---:|")?;
+            for event in events {
+                writeln!(
+                    writer,
+                    "| {} | {} | {} | {} | {} | {} | {} | {} |",
+                    event.date, event.recipient, event.timelock_index, event.release, event.amount, event.unlocked, event.total_amount, event.cumulative,
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Formats a unix timestamp as `YYYY-MM-DD HH:MM:SS` in UTC
+pub fn format_date(timestamp: u32) -> String {
+    let days = (timestamp / 86400) as i64;
+    let seconds = timestamp % 86400;
+    // days since 1970-01-01 to a proleptic Gregorian date, counted in 400 year eras from 0000-03-01
+    let z = days + 719468;
+    let era = z / 146097;
+    let day_of_era = z - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day, seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_are_utc() {
+        assert_eq!(format_date(0), "1970-01-01 00:00:00");
+        assert_eq!(format_date(951782400), "2000-02-29 00:00:00");
+        assert_eq!(format_date(1640995199), "2021-12-31 23:59:59");
+        assert_eq!(format_date(u32::MAX), "2106-02-07 06:28:15");
+    }
+
+    #[test]
+    fn releases_match_calculate_unlocked() {
+        let recipient = Pubkey::new_unique();
+        let release_schedule = ReleaseSchedule {
+            release_count: 4,
+            delay_until_first_release_in_seconds: 100,
+            initial_release_portion_in_bips: 1000,
+            period_between_releases_in_seconds: 50,
+        };
+        let timelock = Timelock::new(0, 1000, 1001);
+        let events = timelock_releases(&recipient, 0, &timelock, &release_schedule);
+
+        // 10% at the cliff, a third of the rest each period, the last release gets the remainder
+        let amounts: Vec<(u32, u32, u32)> = events.iter().map(|event| (event.timestamp, event.amount, event.unlocked)).collect();
+        assert_eq!(amounts, vec![(1100, 100, 100), (1150, 300, 400), (1200, 300, 700), (1250, 301, 1001)]);
+        for event in &events {
+            assert_eq!(Processor::calculate_unlocked(1000, event.timestamp, 1001, &release_schedule), event.unlocked);
+            assert_eq!(Processor::calculate_unlocked(1000, event.timestamp - 1, 1001, &release_schedule), event.unlocked - event.amount);
+        }
+
+        // a schedule without an initial release has no event at the cliff
+        let release_schedule = ReleaseSchedule { initial_release_portion_in_bips: 0, ..release_schedule };
+        let events = timelock_releases(&recipient, 0, &timelock, &release_schedule);
+        assert_eq!(events.iter().map(|event| event.release).collect::<Vec<_>>(), vec![2, 3, 4]);
+        assert_eq!(events.iter().map(|event| event.amount).sum::<u32>(), 1001);
+    }
+
+    #[test]
+    fn timeline_of_a_lockup() {
+        let alice = Pubkey::new_unique();
+        let bob = Pubkey::new_unique();
+        let mut data = vec![0; TokenLockAccount::space(3)];
+        let mut account = TokenLockAccount::init(&mut data, &Pubkey::new_unique(), &Pubkey::new_unique(), 0, 1000, 1, None).unwrap();
+        account.add_release_schedule(ReleaseSchedule {
+            release_count: 2,
+            delay_until_first_release_in_seconds: 0,
+            initial_release_portion_in_bips: 5000,
+            period_between_releases_in_seconds: 100,
+        }).unwrap();
+        account.add_timelock(&alice, Timelock::new(0, 1000, 10)).unwrap();
+        account.add_timelock(&bob, Timelock::new(0, 1050, 21)).unwrap();
+        let mut stopped = Timelock::new(0, 0, 7);
+        stopped.vesting_stopped_at = 1075;
+        account.add_timelock(&alice, stopped).unwrap();
+
+        let events = release_timeline(&account, None);
+        let rows: Vec<(u32, usize, u32, u64)> = events.iter().map(|event| (event.timestamp, event.timelock_index, event.amount, event.cumulative)).collect();
+        assert_eq!(rows, vec![(1000, 0, 5, 5), (1050, 1, 10, 15), (1075, 2, 7, 22), (1100, 0, 5, 27), (1150, 1, 11, 38)]);
+
+        let events = release_timeline(&account, Some(&alice));
+        assert_eq!(events.iter().map(|event| event.cumulative).collect::<Vec<_>>(), vec![5, 12, 17]);
+
+        let mut markdown = vec![];
+        write_report(&events, ReportFormat::Markdown, &mut markdown).unwrap();
+        let markdown = String::from_utf8(markdown).unwrap();
+        assert_eq!(markdown.lines().count(), 2 + events.len());
+        assert!(markdown.contains(&format!("| 1970-01-01 00:16:40 | {} | 0 | 1 | 5 | 5 | 10 | 5 |", alice)));
+
+        let mut csv = vec![];
+        write_report(&events, ReportFormat::Csv, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(csv.starts_with("timestamp,date,recipient,timelock_index,schedule_id,release,amount,unlocked,total_amount,cumulative\n"));
+        assert_eq!(csv.lines().count(), 1 + events.len());
+    }
+}
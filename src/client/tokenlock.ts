@@ -16,6 +16,11 @@ import path from 'path';
 import * as borsh from 'borsh';
 
 import {getPayer, getRpcUrl, createKeypairFromFile} from './utils';
+import {
+  encodeInstruction,
+  TokenLockInstruction,
+  TokenLockInstructionCreateReleaseSchedule,
+} from './tokenlock_schema';
 
 /**
  * Connection to the network
@@ -68,45 +73,6 @@ class GreetingAccount {
   }
 }
 
-class TokenLockAccount {
-  release_schedules =  [];
-  timelocks = new Map();
-  total_tokens_unlocked = new Map();
-  allowances = new Map();
-  counter = 0;
-  constructor(fields: {counter: number} | undefined = undefined) {
-    if (fields) {
-      this.counter = fields.counter;
-    }
-  }
-}
-
-
-// pub release_schedules: Vec<ReleaseSchedule>,
-// pub timelocks: HashMap<Pubkey, Vec<Timelock>>,
-// pub total_tokens_unlocked: HashMap<Pubkey, u32>,
-// pub allowances: HashMap<Pubkey, HashMap<Pubkey, u32>>,
-
-/**
- * Borsh schema definition for greeting accounts
- */
-const TokenLockSchema = new Map([
-  [TokenLockAccount, 
-    {
-      kind: 'struct', 
-      fields: [
-        ['release_schedules', 'u32'],
-        ['timelocks', 'u32'],
-        ['total_tokens_unlocked', 'u32'],
-        ['allowances', 'u32'],
-        ['counter', 'u32']
-      ]
-    }
-  ],
-]);
-
-
-
 /**
  * Borsh schema definition for greeting accounts
  */
@@ -242,9 +208,17 @@ export async function sayHello(): Promise<void> {
   const instruction = new TransactionInstruction({
     keys: [{pubkey: greetedPubkey, isSigner: false, isWritable: true}],
     programId,
-    //data: Buffer.alloc(0), // All instructions are hellos
-    //data: Buffer.from([0]), // All instructions are hellos
-    data: Buffer.from([1, 2, 0, 0, 0, 3, 0, 0, 0, 4, 0,0,0, 5,0,0,0]), // All instructions are hellos
+    data: encodeInstruction(
+      new TokenLockInstruction({
+        enum: 'CreateReleaseSchedule',
+        CreateReleaseSchedule: new TokenLockInstructionCreateReleaseSchedule({
+          0: 2,
+          1: 3,
+          2: 4,
+          3: 5,
+        }),
+      }),
+    ),
   });
   await sendAndConfirmTransaction(
     connection,
@@ -0,0 +1,876 @@
+// Generated from the Rust types of the program by `npm run generate:idl`, do not edit.
+// See idl/tokenlock.json for the IDL it is generated from.
+/* eslint-disable @typescript-eslint/ban-types */
+
+import {PublicKey} from '@solana/web3.js';
+import BN from 'bn.js';
+import * as borsh from 'borsh';
+
+export const PROGRAM_ID = new PublicKey('FZiJXJ5ZhMvYDE5JjRs9P6vQP5TxbKVP63y3WgDVQUQb');
+
+export const TOKEN_LOCK_SEED = 'tokenlock';
+export const VAULT_SEED = 'vault';
+export const CANCEL_PROPOSAL_SEED = 'cancel';
+export const METADATA_SEED = 'metadata';
+export const MINT_AUTHORITY_SEED = 'mint';
+export const TOKEN_LOCK_DISCRIMINATOR = 'tokenlck';
+export const VERSION = 1;
+export const MAX_RELEASE_SCHEDULES = 32;
+export const MAX_CANCELABLE_BY = 10;
+export const MAX_APPROVALS = 10;
+export const SURPLUS_CLAIM_DELAY = 604800;
+export const TOKEN_LOCK_HEADER_LEN = 176;
+export const TIMELOCK_LEN = 376;
+export const RELEASE_SCHEDULE_LEN = 16;
+export const RELEASE_SCHEDULES_OFFSET = 176;
+export const TIMELOCKS_OFFSET = 688;
+
+class Struct {
+  constructor(properties: Record<string, unknown>) {
+    Object.assign(this, properties);
+  }
+}
+
+class Enum extends Struct {
+  declare enum: string;
+}
+
+export class BurnSourceUnlocked extends Struct {}
+
+export class BurnSourceLocked extends Struct {}
+
+export class BurnSource extends Enum {
+  declare Unlocked?: BurnSourceUnlocked;
+  declare Locked?: BurnSourceLocked;
+}
+
+export class CancelModePayOut extends Struct {}
+
+export class CancelModeStopVesting extends Struct {}
+
+export class CancelMode extends Enum {
+  declare PayOut?: CancelModePayOut;
+  declare StopVesting?: CancelModeStopVesting;
+}
+
+export class CancelProposal extends Struct {
+  declare target: Uint8Array;
+  declare timelock_index: number;
+  declare reclaim_token_to: Uint8Array;
+  declare mode: CancelMode;
+  declare proposer: Uint8Array;
+  declare expires_at: number;
+  declare approvals: Uint8Array[];
+}
+
+export class ReleaseSchedule extends Struct {
+  declare release_count: number;
+  declare delay_until_first_release_in_seconds: number;
+  declare initial_release_portion_in_bips: number;
+  declare period_between_releases_in_seconds: number;
+}
+
+export class TokenMetadata extends Struct {
+  declare name: string;
+  declare symbol: string;
+  declare uri: string;
+}
+
+export class TokenLockHeader extends Struct {
+  declare discriminator: Uint8Array;
+  declare mint: Uint8Array;
+  declare treasury_authority: Uint8Array;
+  declare original_funder: Uint8Array;
+  declare authority: Uint8Array;
+  declare version: number;
+  declare nonce: number;
+  declare has_treasury_authority: number;
+  declare has_original_funder: number;
+  declare counter: number;
+  declare max_release_delay: number;
+  declare min_timelock_amount: number;
+  declare release_schedule_count: number;
+  declare timelock_count: number;
+  declare surplus_claimable_at: number;
+  declare surplus_claim_amount: BN;
+  declare _reserved: Uint8Array;
+}
+
+export class Timelock extends Struct {
+  declare schedule_id: number;
+  declare commencement_timestamp: number;
+  declare tokens_transferred: number;
+  declare total_amount: number;
+  declare vesting_stopped_at: number;
+  declare recipient: Uint8Array;
+  declare cancelable_by: Uint8Array; // [publicKey; 10]
+  declare cancelable_by_count: number;
+  declare cancel_threshold: number;
+  declare _reserved: Uint8Array;
+}
+
+export class TokenLockInstructionGreeting extends Struct {}
+
+export class TokenLockInstructionCreateReleaseSchedule extends Struct {
+  declare 0: number;
+  declare 1: number;
+  declare 2: number;
+  declare 3: number;
+}
+
+export class TokenLockInstructionTransferTimelockOwnership extends Struct {
+  declare timelock_index: number;
+  declare new_owner: Uint8Array;
+}
+
+export class TokenLockInstructionSplitTimelock extends Struct {
+  declare timelock_index: number;
+  declare amount: number;
+  declare to: Uint8Array;
+}
+
+export class TokenLockInstructionMergeTimelocks extends Struct {
+  declare timelock_index: number;
+  declare other_timelock_index: number;
+}
+
+export class TokenLockInstructionCancelTimelock extends Struct {
+  declare target: Uint8Array;
+  declare timelock_index: number;
+  declare reclaim_token_to: Uint8Array;
+  declare mode: CancelMode;
+}
+
+export class TokenLockInstructionProposeCancel extends Struct {
+  declare target: Uint8Array;
+  declare timelock_index: number;
+  declare reclaim_token_to: Uint8Array;
+  declare mode: CancelMode;
+}
+
+export class TokenLockInstructionApproveCancel extends Struct {}
+
+export class TokenLockInstructionExecuteCancel extends Struct {}
+
+export class TokenLockInstructionCloseCancelProposal extends Struct {}
+
+export class TokenLockInstructionAddCanceler extends Struct {
+  declare target: Uint8Array;
+  declare timelock_index: number;
+  declare canceler: Uint8Array;
+}
+
+export class TokenLockInstructionRemoveCanceler extends Struct {
+  declare target: Uint8Array;
+  declare timelock_index: number;
+  declare canceler: Uint8Array;
+}
+
+export class TokenLockInstructionRenounceCancel extends Struct {
+  declare target: Uint8Array;
+  declare timelock_index: number;
+}
+
+export class TokenLockInstructionLogCancelStatus extends Struct {
+  declare target: Uint8Array;
+  declare timelock_index: number;
+}
+
+export class TokenLockInstructionBurn extends Struct {
+  declare amount: number;
+  declare source: BurnSource;
+}
+
+export class TokenLockInstructionInitializeToken extends Struct {
+  declare decimals: number;
+  declare total_supply: BN;
+  declare metadata: TokenMetadata;
+}
+
+export class TokenLockInstructionInitializeTokenLock extends Struct {
+  declare nonce: number;
+  declare max_release_delay: number;
+  declare min_timelock_amount: number;
+  declare space: number;
+  declare treasury_authority: Uint8Array | undefined;
+}
+
+export class TokenLockInstructionFundReleaseSchedule extends Struct {
+  declare to: Uint8Array;
+  declare amount: number;
+  declare commencement_timestamp: number;
+  declare schedule_id: number;
+  declare cancelable_by: Uint8Array[];
+  declare cancel_threshold: number;
+  declare timelock_index: number;
+}
+
+export class TokenLockInstructionTransferTimelock extends Struct {
+  declare to: Uint8Array;
+  declare value: number;
+  declare timelock_index: number;
+}
+
+export class TokenLockInstructionReconcile extends Struct {}
+
+export class TokenLockInstructionClaimSurplus extends Struct {}
+
+export class TokenLockInstructionSweepSurplus extends Struct {
+  declare amount: BN;
+}
+
+export class TokenLockInstructionMigrate extends Struct {
+  declare from_version: number;
+  declare seed: string;
+}
+
+export class TokenLockInstruction extends Enum {
+  declare Greeting?: TokenLockInstructionGreeting;
+  declare CreateReleaseSchedule?: TokenLockInstructionCreateReleaseSchedule;
+  declare TransferTimelockOwnership?: TokenLockInstructionTransferTimelockOwnership;
+  declare SplitTimelock?: TokenLockInstructionSplitTimelock;
+  declare MergeTimelocks?: TokenLockInstructionMergeTimelocks;
+  declare CancelTimelock?: TokenLockInstructionCancelTimelock;
+  declare ProposeCancel?: TokenLockInstructionProposeCancel;
+  declare ApproveCancel?: TokenLockInstructionApproveCancel;
+  declare ExecuteCancel?: TokenLockInstructionExecuteCancel;
+  declare CloseCancelProposal?: TokenLockInstructionCloseCancelProposal;
+  declare AddCanceler?: TokenLockInstructionAddCanceler;
+  declare RemoveCanceler?: TokenLockInstructionRemoveCanceler;
+  declare RenounceCancel?: TokenLockInstructionRenounceCancel;
+  declare LogCancelStatus?: TokenLockInstructionLogCancelStatus;
+  declare Burn?: TokenLockInstructionBurn;
+  declare InitializeToken?: TokenLockInstructionInitializeToken;
+  declare InitializeTokenLock?: TokenLockInstructionInitializeTokenLock;
+  declare FundReleaseSchedule?: TokenLockInstructionFundReleaseSchedule;
+  declare TransferTimelock?: TokenLockInstructionTransferTimelock;
+  declare Reconcile?: TokenLockInstructionReconcile;
+  declare ClaimSurplus?: TokenLockInstructionClaimSurplus;
+  declare SweepSurplus?: TokenLockInstructionSweepSurplus;
+  declare Migrate?: TokenLockInstructionMigrate;
+}
+
+export const SCHEMA = new Map<Function, unknown>([
+  [
+    BurnSourceUnlocked,
+    {
+      kind: 'struct',
+      fields: [
+      ],
+    },
+  ],
+  [
+    BurnSourceLocked,
+    {
+      kind: 'struct',
+      fields: [
+      ],
+    },
+  ],
+  [
+    BurnSource,
+    {
+      kind: 'enum',
+      field: 'enum',
+      values: [
+        ['Unlocked', BurnSourceUnlocked],
+        ['Locked', BurnSourceLocked],
+      ],
+    },
+  ],
+  [
+    CancelModePayOut,
+    {
+      kind: 'struct',
+      fields: [
+      ],
+    },
+  ],
+  [
+    CancelModeStopVesting,
+    {
+      kind: 'struct',
+      fields: [
+      ],
+    },
+  ],
+  [
+    CancelMode,
+    {
+      kind: 'enum',
+      field: 'enum',
+      values: [
+        ['PayOut', CancelModePayOut],
+        ['StopVesting', CancelModeStopVesting],
+      ],
+    },
+  ],
+  [
+    CancelProposal,
+    {
+      kind: 'struct',
+      fields: [
+        ['target', [32]],
+        ['timelock_index', 'u32'],
+        ['reclaim_token_to', [32]],
+        ['mode', CancelMode],
+        ['proposer', [32]],
+        ['expires_at', 'u32'],
+        ['approvals', [[32]]],
+      ],
+    },
+  ],
+  [
+    ReleaseSchedule,
+    {
+      kind: 'struct',
+      fields: [
+        ['release_count', 'u32'],
+        ['delay_until_first_release_in_seconds', 'u32'],
+        ['initial_release_portion_in_bips', 'u32'],
+        ['period_between_releases_in_seconds', 'u32'],
+      ],
+    },
+  ],
+  [
+    TokenMetadata,
+    {
+      kind: 'struct',
+      fields: [
+        ['name', 'string'],
+        ['symbol', 'string'],
+        ['uri', 'string'],
+      ],
+    },
+  ],
+  [
+    TokenLockHeader,
+    {
+      kind: 'struct',
+      fields: [
+        ['discriminator', [8]],
+        ['mint', [32]],
+        ['treasury_authority', [32]],
+        ['original_funder', [32]],
+        ['authority', [32]],
+        ['version', 'u8'],
+        ['nonce', 'u8'],
+        ['has_treasury_authority', 'u8'],
+        ['has_original_funder', 'u8'],
+        ['counter', 'u32'],
+        ['max_release_delay', 'u32'],
+        ['min_timelock_amount', 'u32'],
+        ['release_schedule_count', 'u32'],
+        ['timelock_count', 'u32'],
+        ['surplus_claimable_at', 'u32'],
+        ['surplus_claim_amount', 'u64'],
+        ['_reserved', [4]],
+      ],
+    },
+  ],
+  [
+    Timelock,
+    {
+      kind: 'struct',
+      fields: [
+        ['schedule_id', 'u32'],
+        ['commencement_timestamp', 'u32'],
+        ['tokens_transferred', 'u32'],
+        ['total_amount', 'u32'],
+        ['vesting_stopped_at', 'u32'],
+        ['recipient', [32]],
+        ['cancelable_by', [320]],
+        ['cancelable_by_count', 'u8'],
+        ['cancel_threshold', 'u8'],
+        ['_reserved', [2]],
+      ],
+    },
+  ],
+  [
+    TokenLockInstructionGreeting,
+    {
+      kind: 'struct',
+      fields: [
+      ],
+    },
+  ],
+  [
+    TokenLockInstructionCreateReleaseSchedule,
+    {
+      kind: 'struct',
+      fields: [
+        ['0', 'u32'],
+        ['1', 'u32'],
+        ['2', 'u32'],
+        ['3', 'u32'],
+      ],
+    },
+  ],
+  [
+    TokenLockInstructionTransferTimelockOwnership,
+    {
+      kind: 'struct',
+      fields: [
+        ['timelock_index', 'u32'],
+        ['new_owner', [32]],
+      ],
+    },
+  ],
+  [
+    TokenLockInstructionSplitTimelock,
+    {
+      kind: 'struct',
+      fields: [
+        ['timelock_index', 'u32'],
+        ['amount', 'u32'],
+        ['to', [32]],
+      ],
+    },
+  ],
+  [
+    TokenLockInstructionMergeTimelocks,
+    {
+      kind: 'struct',
+      fields: [
+        ['timelock_index', 'u32'],
+        ['other_timelock_index', 'u32'],
+      ],
+    },
+  ],
+  [
+    TokenLockInstructionCancelTimelock,
+    {
+      kind: 'struct',
+      fields: [
+        ['target', [32]],
+        ['timelock_index', 'u32'],
+        ['reclaim_token_to', [32]],
+        ['mode', CancelMode],
+      ],
+    },
+  ],
+  [
+    TokenLockInstructionProposeCancel,
+    {
+      kind: 'struct',
+      fields: [
+        ['target', [32]],
+        ['timelock_index', 'u32'],
+        ['reclaim_token_to', [32]],
+        ['mode', CancelMode],
+      ],
+    },
+  ],
+  [
+    TokenLockInstructionApproveCancel,
+    {
+      kind: 'struct',
+      fields: [
+      ],
+    },
+  ],
+  [
+    TokenLockInstructionExecuteCancel,
+    {
+      kind: 'struct',
+      fields: [
+      ],
+    },
+  ],
+  [
+    TokenLockInstructionCloseCancelProposal,
+    {
+      kind: 'struct',
+      fields: [
+      ],
+    },
+  ],
+  [
+    TokenLockInstructionAddCanceler,
+    {
+      kind: 'struct',
+      fields: [
+        ['target', [32]],
+        ['timelock_index', 'u32'],
+        ['canceler', [32]],
+      ],
+    },
+  ],
+  [
+    TokenLockInstructionRemoveCanceler,
+    {
+      kind: 'struct',
+      fields: [
+        ['target', [32]],
+        ['timelock_index', 'u32'],
+        ['canceler', [32]],
+      ],
+    },
+  ],
+  [
+    TokenLockInstructionRenounceCancel,
+    {
+      kind: 'struct',
+      fields: [
+        ['target', [32]],
+        ['timelock_index', 'u32'],
+      ],
+    },
+  ],
+  [
+    TokenLockInstructionLogCancelStatus,
+    {
+      kind: 'struct',
+      fields: [
+        ['target', [32]],
+        ['timelock_index', 'u32'],
+      ],
+    },
+  ],
+  [
+    TokenLockInstructionBurn,
+    {
+      kind: 'struct',
+      fields: [
+        ['amount', 'u32'],
+        ['source', BurnSource],
+      ],
+    },
+  ],
+  [
+    TokenLockInstructionInitializeToken,
+    {
+      kind: 'struct',
+      fields: [
+        ['decimals', 'u8'],
+        ['total_supply', 'u64'],
+        ['metadata', TokenMetadata],
+      ],
+    },
+  ],
+  [
+    TokenLockInstructionInitializeTokenLock,
+    {
+      kind: 'struct',
+      fields: [
+        ['nonce', 'u8'],
+        ['max_release_delay', 'u32'],
+        ['min_timelock_amount', 'u32'],
+        ['space', 'u32'],
+        ['treasury_authority', {kind: 'option', type: [32]}],
+      ],
+    },
+  ],
+  [
+    TokenLockInstructionFundReleaseSchedule,
+    {
+      kind: 'struct',
+      fields: [
+        ['to', [32]],
+        ['amount', 'u32'],
+        ['commencement_timestamp', 'u32'],
+        ['schedule_id', 'u32'],
+        ['cancelable_by', [[32]]],
+        ['cancel_threshold', 'u8'],
+        ['timelock_index', 'u32'],
+      ],
+    },
+  ],
+  [
+    TokenLockInstructionTransferTimelock,
+    {
+      kind: 'struct',
+      fields: [
+        ['to', [32]],
+        ['value', 'u32'],
+        ['timelock_index', 'u32'],
+      ],
+    },
+  ],
+  [
+    TokenLockInstructionReconcile,
+    {
+      kind: 'struct',
+      fields: [
+      ],
+    },
+  ],
+  [
+    TokenLockInstructionClaimSurplus,
+    {
+      kind: 'struct',
+      fields: [
+      ],
+    },
+  ],
+  [
+    TokenLockInstructionSweepSurplus,
+    {
+      kind: 'struct',
+      fields: [
+        ['amount', 'u64'],
+      ],
+    },
+  ],
+  [
+    TokenLockInstructionMigrate,
+    {
+      kind: 'struct',
+      fields: [
+        ['from_version', 'u8'],
+        ['seed', 'string'],
+      ],
+    },
+  ],
+  [
+    TokenLockInstruction,
+    {
+      kind: 'enum',
+      field: 'enum',
+      values: [
+        ['Greeting', TokenLockInstructionGreeting],
+        ['CreateReleaseSchedule', TokenLockInstructionCreateReleaseSchedule],
+        ['TransferTimelockOwnership', TokenLockInstructionTransferTimelockOwnership],
+        ['SplitTimelock', TokenLockInstructionSplitTimelock],
+        ['MergeTimelocks', TokenLockInstructionMergeTimelocks],
+        ['CancelTimelock', TokenLockInstructionCancelTimelock],
+        ['ProposeCancel', TokenLockInstructionProposeCancel],
+        ['ApproveCancel', TokenLockInstructionApproveCancel],
+        ['ExecuteCancel', TokenLockInstructionExecuteCancel],
+        ['CloseCancelProposal', TokenLockInstructionCloseCancelProposal],
+        ['AddCanceler', TokenLockInstructionAddCanceler],
+        ['RemoveCanceler', TokenLockInstructionRemoveCanceler],
+        ['RenounceCancel', TokenLockInstructionRenounceCancel],
+        ['LogCancelStatus', TokenLockInstructionLogCancelStatus],
+        ['Burn', TokenLockInstructionBurn],
+        ['InitializeToken', TokenLockInstructionInitializeToken],
+        ['InitializeTokenLock', TokenLockInstructionInitializeTokenLock],
+        ['FundReleaseSchedule', TokenLockInstructionFundReleaseSchedule],
+        ['TransferTimelock', TokenLockInstructionTransferTimelock],
+        ['Reconcile', TokenLockInstructionReconcile],
+        ['ClaimSurplus', TokenLockInstructionClaimSurplus],
+        ['SweepSurplus', TokenLockInstructionSweepSurplus],
+        ['Migrate', TokenLockInstructionMigrate],
+      ],
+    },
+  ],
+]);
+
+export function encodeInstruction(instruction: TokenLockInstruction): Buffer {
+  return Buffer.from(borsh.serialize(SCHEMA, instruction));
+}
+
+function decodeRecords<T>(
+  data: Buffer,
+  classType: {new (properties: Record<string, unknown>): T},
+  offset: number,
+  stride: number,
+  count: number,
+): T[] {
+  const records: T[] = [];
+  for (let idx = 0; idx < count; idx++) {
+    const start = offset + idx * stride;
+    records.push(borsh.deserialize(SCHEMA, classType, data.slice(start, start + stride)));
+  }
+  return records;
+}
+
+export interface TokenLockAccount {
+  header: TokenLockHeader;
+  release_schedules: ReleaseSchedule[];
+  timelocks: Timelock[];
+}
+
+export function decodeTokenLockAccount(data: Buffer): TokenLockAccount {
+  const header = borsh.deserialize(SCHEMA, TokenLockHeader, data.slice(0, TOKEN_LOCK_HEADER_LEN));
+  if (Buffer.from(header.discriminator).toString() !== TOKEN_LOCK_DISCRIMINATOR || header.version !== VERSION) {
+    throw new Error('Not a TokenLockAccount in the current layout');
+  }
+  return {
+    header,
+    release_schedules: decodeRecords(data, ReleaseSchedule, RELEASE_SCHEDULES_OFFSET, RELEASE_SCHEDULE_LEN, header.release_schedule_count),
+    timelocks: decodeRecords(data, Timelock, TIMELOCKS_OFFSET, TIMELOCK_LEN, header.timelock_count),
+  };
+}
+
+export function decodeCancelProposal(data: Buffer): CancelProposal {
+  return borsh.deserializeUnchecked(SCHEMA, CancelProposal, data);
+}
+
+export function decodeTokenMetadata(data: Buffer): TokenMetadata {
+  return borsh.deserializeUnchecked(SCHEMA, TokenMetadata, data);
+}
+
+export const ERRORS: {name: string; code: number; msg: string}[] = [
+  {name: 'InvalidArgument', code: 8589934592, msg: 'The arguments provided to a program instruction where invalid'},
+  {name: 'InvalidInstructionData', code: 12884901888, msg: 'An instruction\'s data contents was invalid'},
+  {name: 'InvalidAccountData', code: 17179869184, msg: 'An account\'s data contents was invalid'},
+  {name: 'AccountDataTooSmall', code: 21474836480, msg: 'An account\'s data was too small'},
+  {name: 'InsufficientFunds', code: 25769803776, msg: 'An account\'s balance was too small to complete the instruction'},
+  {name: 'IncorrectProgramId', code: 30064771072, msg: 'The account did not have the expected program id'},
+  {name: 'MissingRequiredSignature', code: 34359738368, msg: 'A signature was required but not found'},
+  {name: 'AccountAlreadyInitialized', code: 38654705664, msg: 'An initialize instruction was sent to an account that has already been initialized'},
+  {name: 'NotEnoughAccountKeys', code: 47244640256, msg: 'The instruction expected additional account keys'},
+  {name: 'InvalidSeeds', code: 60129542144, msg: 'Provided seeds do not result in a valid address'},
+];
+
+export const EVENTS: {name: string; format: string; fields: [string, string][]}[] = [
+  {
+    name: 'TokenInitialized',
+    format: 'TokenInitialized {mint} {treasury} {total_supply}',
+    fields: [['mint', 'publicKey'], ['treasury', 'publicKey'], ['total_supply', 'u64']],
+  },
+  {
+    name: 'TokenLockInitialized',
+    format: 'TokenLockInitialized {token_lock} {mint} {nonce}',
+    fields: [['token_lock', 'publicKey'], ['mint', 'publicKey'], ['nonce', 'u8']],
+  },
+  {
+    name: 'TokenLockMigrated',
+    format: 'TokenLockMigrated {token_lock} {from_version} {to_version}',
+    fields: [['token_lock', 'publicKey'], ['from_version', 'u8'], ['to_version', 'u8']],
+  },
+  {
+    name: 'TimelockMigrated',
+    format: 'TimelockMigrated {recipient} {old_index} {new_index}',
+    fields: [['recipient', 'publicKey'], ['old_index', 'u32'], ['new_index', 'u32']],
+  },
+  {
+    name: 'ReleaseScheduleCreated',
+    format: 'ReleaseScheduleCreated {schedule_id}',
+    fields: [['schedule_id', 'u32']],
+  },
+  {
+    name: 'ScheduleFunded',
+    format: 'ScheduleFunded {to} {schedule_id} {amount}',
+    fields: [['to', 'publicKey'], ['schedule_id', 'u32'], ['amount', 'u32']],
+  },
+  {
+    name: 'TimelockCommencing',
+    format: 'timelock {timelock_index} commencing {commencement_timestamp}',
+    fields: [['timelock_index', 'u32'], ['commencement_timestamp', 'u32']],
+  },
+  {
+    name: 'Transfer',
+    format: 'Transfer {from} {to} {value}',
+    fields: [['from', 'publicKey'], ['to', 'publicKey'], ['value', 'u32']],
+  },
+  {
+    name: 'TimelockOwnershipTransferred',
+    format: 'TimelockOwnershipTransferred {owner} {timelock_index} {new_owner}',
+    fields: [['owner', 'publicKey'], ['timelock_index', 'u32'], ['new_owner', 'publicKey']],
+  },
+  {
+    name: 'TimelockSplit',
+    format: 'TimelockSplit {owner} {timelock_index} {amount}',
+    fields: [['owner', 'publicKey'], ['timelock_index', 'u32'], ['amount', 'u32']],
+  },
+  {
+    name: 'TimelocksMerged',
+    format: 'TimelocksMerged {owner} {timelock_index} {other_timelock_index}',
+    fields: [['owner', 'publicKey'], ['timelock_index', 'u32'], ['other_timelock_index', 'u32']],
+  },
+  {
+    name: 'Burned',
+    format: 'Burned {recipient} {amount}',
+    fields: [['recipient', 'publicKey'], ['amount', 'u32']],
+  },
+  {
+    name: 'TimelockCanceled',
+    format: 'TimelockCanceled {canceler} {target} {timelock_index}',
+    fields: [['canceler', 'publicKey'], ['target', 'publicKey'], ['timelock_index', 'u32']],
+  },
+  {
+    name: 'CancelProposed',
+    format: 'CancelProposed {canceler} {target} {timelock_index}',
+    fields: [['canceler', 'publicKey'], ['target', 'publicKey'], ['timelock_index', 'u32']],
+  },
+  {
+    name: 'CancelApproved',
+    format: 'CancelApproved {canceler} {target} {timelock_index}',
+    fields: [['canceler', 'publicKey'], ['target', 'publicKey'], ['timelock_index', 'u32']],
+  },
+  {
+    name: 'CancelProposalClosed',
+    format: 'CancelProposalClosed {proposer} {target} {timelock_index}',
+    fields: [['proposer', 'publicKey'], ['target', 'publicKey'], ['timelock_index', 'u32']],
+  },
+  {
+    name: 'CancelerAdded',
+    format: 'CancelerAdded {target} {timelock_index} {canceler}',
+    fields: [['target', 'publicKey'], ['timelock_index', 'u32'], ['canceler', 'publicKey']],
+  },
+  {
+    name: 'CancelerRemoved',
+    format: 'CancelerRemoved {target} {timelock_index} {canceler}',
+    fields: [['target', 'publicKey'], ['timelock_index', 'u32'], ['canceler', 'publicKey']],
+  },
+  {
+    name: 'CancelRenounced',
+    format: 'CancelRenounced {target} {timelock_index} {canceler}',
+    fields: [['target', 'publicKey'], ['timelock_index', 'u32'], ['canceler', 'publicKey']],
+  },
+  {
+    name: 'SurplusClaimed',
+    format: 'SurplusClaimed {funder} {amount} claimable at {claimable_at}',
+    fields: [['funder', 'publicKey'], ['amount', 'u64'], ['claimable_at', 'u32']],
+  },
+  {
+    name: 'SurplusSwept',
+    format: 'SurplusSwept {authority} {amount}',
+    fields: [['authority', 'publicKey'], ['amount', 'u64']],
+  },
+];
+
+export type EventValue = PublicKey | BN | number;
+
+export interface Event {
+  name: string;
+  fields: Record<string, EventValue>;
+}
+
+function parseEventValue(type: string, word: string): EventValue | undefined {
+  if (type === 'publicKey') {
+    try {
+      return new PublicKey(word);
+    } catch (err) {
+      return undefined;
+    }
+  }
+  if (!/^[0-9]+$/.test(word)) {
+    return undefined;
+  }
+  return type === 'u64' ? new BN(word, 10) : Number(word);
+}
+
+/**
+ * Parses a message the program logged, without the `Program log: ` prefix.
+ * Messages that aren't events, like error messages, give `undefined`.
+ */
+export function parseEvent(message: string): Event | undefined {
+  const words = message.split(' ');
+  for (const event of EVENTS) {
+    const format = event.format.split(' ');
+    if (format.length !== words.length) {
+      continue;
+    }
+    const fields: Record<string, EventValue> = {};
+    let field = 0;
+    const matches = format.every((word, idx) => {
+      if (!word.startsWith('{')) {
+        return word === words[idx];
+      }
+      const [name, type] = event.fields[field++];
+      const value = parseEventValue(type, words[idx]);
+      if (value === undefined) {
+        return false;
+      }
+      fields[name] = value;
+      return true;
+    });
+    if (matches) {
+      return {name: event.name, fields};
+    }
+  }
+  return undefined;
+}
@@ -0,0 +1,124 @@
+//! Reads the doc comments of `TokenLockInstruction`, which describe each instruction and list the accounts it expects
+use std::collections::HashMap;
+
+const INSTRUCTION_SOURCE: &str = include_str!("../../program-rust/src/instruction.rs");
+
+/// An entry of the "Accounts expected:" list of an instruction
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountDoc {
+    pub index: usize,
+    pub writable: bool,
+    pub signer: bool,
+    /// Stands for any number of accounts, like the cancelers of a timelock
+    pub variadic: bool,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VariantDoc {
+    /// The lines before the account list
+    pub docs: Vec<String>,
+    pub accounts: Vec<AccountDoc>,
+}
+
+/// The docs of the variants of `TokenLockInstruction` by variant name
+pub fn instruction_docs() -> HashMap<String, VariantDoc> {
+    enum_docs(INSTRUCTION_SOURCE, "TokenLockInstruction")
+}
+
+/// The docs of the variants of enum `name` in `source`. Only the comments on the variants are read,
+/// not those on their fields.
+pub fn enum_docs(source: &str, name: &str) -> HashMap<String, VariantDoc> {
+    let declaration = format!("pub enum {} {{", name);
+    let mut docs = HashMap::new();
+    let mut comment = vec![];
+    for line in source.lines().skip_while(|line| !line.starts_with(&declaration)).skip(1) {
+        if line.starts_with('}') {
+            break;
+        }
+        if let Some(text) = line.strip_prefix("    ///") {
+            comment.push(text.strip_prefix(' ').unwrap_or(text));
+            continue;
+        }
+        if line.starts_with("    ") && !line.starts_with("     ") {
+            let variant: String = line.trim().chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect();
+            if !variant.is_empty() {
+                docs.insert(variant, variant_doc(&comment));
+            }
+        }
+        comment.clear();
+    }
+    docs
+}
+
+fn variant_doc(comment: &[&str]) -> VariantDoc {
+    let split = comment.iter().position(|line| line.trim() == "Accounts expected:").unwrap_or(comment.len());
+    let mut docs: Vec<String> = comment[..split].iter().map(|line| line.to_string()).collect();
+    while docs.last().map_or(false, |line| line.is_empty()) {
+        docs.pop();
+    }
+    let accounts = comment.iter().skip(split + 1).filter_map(|line| account_doc(line)).collect();
+    VariantDoc { docs, accounts }
+}
+
+/// Parses an account entry like ``2. ..`[signer]` Cancelers of the timelock``
+fn account_doc(line: &str) -> Option<AccountDoc> {
+    let (index, rest) = line.trim().split_once(". ")?;
+    let (variadic, rest) = match rest.strip_prefix("..") {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+    let (flags, description) = rest.strip_prefix("`[")?.split_once("]`")?;
+    let flags: Vec<&str> = flags.split(',').map(str::trim).collect();
+    Some(AccountDoc {
+        index: index.parse().ok()?,
+        writable: flags.contains(&"writable"),
+        signer: flags.contains(&"signer"),
+        variadic,
+        description: description.trim().to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_variant_docs() {
+        let source = r#"
+pub enum Example {
+    Unit,
+    /// Does a thing.
+    ///
+    /// Accounts expected:
+    ///   0. `[writable]` The token lock account
+    ///   1. `[signer, writable]` The owner
+    ///   2. ..`[signer]` Cancelers
+    Named {
+        /// Not a variant doc
+        amount: u32,
+    },
+}
+"#;
+        let docs = enum_docs(source, "Example");
+        assert_eq!(docs["Unit"], VariantDoc::default());
+        assert_eq!(docs["Named"].docs, vec!["Does a thing.".to_string()]);
+        assert_eq!(docs["Named"].accounts, vec![
+            AccountDoc { index: 0, writable: true, signer: false, variadic: false, description: "The token lock account".to_string() },
+            AccountDoc { index: 1, writable: true, signer: true, variadic: false, description: "The owner".to_string() },
+            AccountDoc { index: 2, writable: false, signer: true, variadic: true, description: "Cancelers".to_string() },
+        ]);
+        assert!(!docs.contains_key("amount"));
+    }
+
+    #[test]
+    fn every_instruction_lists_its_accounts() {
+        let docs = instruction_docs();
+        assert_eq!(docs["CreateReleaseSchedule"].accounts.len(), 2);
+        assert_eq!(docs["FundReleaseSchedule"].accounts.len(), 6);
+        for (name, doc) in docs.iter().filter(|(name, _)| name.as_str() != "Greeting") {
+            assert!(!doc.accounts.is_empty(), "{} has no accounts listed", name);
+            assert!(doc.accounts.iter().enumerate().all(|(idx, account)| account.index == idx), "{} numbers its accounts out of order", name);
+        }
+    }
+}
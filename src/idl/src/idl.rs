@@ -0,0 +1,399 @@
+//! Builds the IDL of the program: the instructions and the borsh encoded accounts from their borsh schemas,
+//! the zero-copy token lock account from the field tables of `types.rs`, and the errors and events of the processor.
+//!
+//! Types are written as `"u32"`, `"string"`, `"publicKey"`, `{"option": T}`, `{"vec": T}`, `{"array": [T, N]}`
+//! or `{"defined": "Name"}` for a type listed under `types`.
+use std::collections::{BTreeSet, HashMap};
+use borsh::{schema::{Declaration, Definition, Fields}, BorshSchema};
+use serde_json::{json, Value};
+use tokenlock::{
+    instruction::TokenLockInstruction,
+    solana_program::program_error::ProgramError,
+    types::{CancelProposal, ReleaseSchedule, Timelock, TokenLockAccount, TokenLockHeader, TokenMetadata},
+};
+use crate::docs::instruction_docs;
+
+/// What the processor logs with `msg!` when it changes state: the event name, the message with `{field}`
+/// standing for a logged value, and the types of the values in order.
+const EVENTS: &[(&str, &str, &[&str])] = &[
+    ("TokenInitialized", "TokenInitialized {mint} {treasury} {total_supply}", &["publicKey", "publicKey", "u64"]),
+    ("TokenLockInitialized", "TokenLockInitialized {token_lock} {mint} {nonce}", &["publicKey", "publicKey", "u8"]),
+    ("TokenLockMigrated", "TokenLockMigrated {token_lock} {from_version} {to_version}", &["publicKey", "u8", "u8"]),
+    ("TimelockMigrated", "TimelockMigrated {recipient} {old_index} {new_index}", &["publicKey", "u32", "u32"]),
+    ("ReleaseScheduleCreated", "ReleaseScheduleCreated {schedule_id}", &["u32"]),
+    ("ScheduleFunded", "ScheduleFunded {to} {schedule_id} {amount}", &["publicKey", "u32", "u32"]),
+    ("TimelockCommencing", "timelock {timelock_index} commencing {commencement_timestamp}", &["u32", "u32"]),
+    ("Transfer", "Transfer {from} {to} {value}", &["publicKey", "publicKey", "u32"]),
+    ("TimelockOwnershipTransferred", "TimelockOwnershipTransferred {owner} {timelock_index} {new_owner}", &["publicKey", "u32", "publicKey"]),
+    ("TimelockSplit", "TimelockSplit {owner} {timelock_index} {amount}", &["publicKey", "u32", "u32"]),
+    ("TimelocksMerged", "TimelocksMerged {owner} {timelock_index} {other_timelock_index}", &["publicKey", "u32", "u32"]),
+    ("Burned", "Burned {recipient} {amount}", &["publicKey", "u32"]),
+    ("TimelockCanceled", "TimelockCanceled {canceler} {target} {timelock_index}", &["publicKey", "publicKey", "u32"]),
+    ("CancelProposed", "CancelProposed {canceler} {target} {timelock_index}", &["publicKey", "publicKey", "u32"]),
+    ("CancelApproved", "CancelApproved {canceler} {target} {timelock_index}", &["publicKey", "publicKey", "u32"]),
+    ("CancelProposalClosed", "CancelProposalClosed {proposer} {target} {timelock_index}", &["publicKey", "publicKey", "u32"]),
+    ("CancelerAdded", "CancelerAdded {target} {timelock_index} {canceler}", &["publicKey", "u32", "publicKey"]),
+    ("CancelerRemoved", "CancelerRemoved {target} {timelock_index} {canceler}", &["publicKey", "u32", "publicKey"]),
+    ("CancelRenounced", "CancelRenounced {target} {timelock_index} {canceler}", &["publicKey", "u32", "publicKey"]),
+    ("SurplusClaimed", "SurplusClaimed {funder} {amount} claimable at {claimable_at}", &["publicKey", "u64", "u32"]),
+    ("SurplusSwept", "SurplusSwept {authority} {amount}", &["publicKey", "u64"]),
+];
+
+/// The errors the program returns, it has no custom ones
+fn errors() -> Vec<ProgramError> {
+    vec![
+        ProgramError::InvalidArgument,
+        ProgramError::InvalidInstructionData,
+        ProgramError::InvalidAccountData,
+        ProgramError::AccountDataTooSmall,
+        ProgramError::InsufficientFunds,
+        ProgramError::IncorrectProgramId,
+        ProgramError::MissingRequiredSignature,
+        ProgramError::AccountAlreadyInitialized,
+        ProgramError::NotEnoughAccountKeys,
+        ProgramError::InvalidSeeds,
+    ]
+}
+
+/// The borsh definitions of the instruction and the borsh encoded account types
+struct Schema {
+    definitions: HashMap<Declaration, Definition>,
+}
+
+impl Schema {
+    fn new() -> Self {
+        let mut definitions = HashMap::new();
+        TokenLockInstruction::add_definitions_recursively(&mut definitions);
+        ReleaseSchedule::add_definitions_recursively(&mut definitions);
+        CancelProposal::add_definitions_recursively(&mut definitions);
+        TokenMetadata::add_definitions_recursively(&mut definitions);
+        Schema { definitions }
+    }
+
+    fn type_of(&self, declaration: &str) -> Value {
+        if declaration == "Pubkey" {
+            return json!("publicKey");
+        }
+        match self.definitions.get(declaration) {
+            None => json!(declaration),
+            Some(Definition::Array { length, elements }) => json!({ "array": [self.type_of(elements), length] }),
+            Some(Definition::Sequence { elements }) => json!({ "vec": self.type_of(elements) }),
+            Some(Definition::Enum { variants }) if declaration.starts_with("Option<") => json!({ "option": self.type_of(&variants[1].1) }),
+            Some(_) => json!({ "defined": declaration }),
+        }
+    }
+
+    fn fields(&self, declaration: &str) -> Vec<Value> {
+        match self.definitions.get(declaration) {
+            Some(Definition::Struct { fields: Fields::NamedFields(fields) }) => fields.iter()
+                .map(|(name, declaration)| json!({ "name": name, "type": self.type_of(declaration) }))
+                .collect(),
+            Some(Definition::Struct { fields: Fields::UnnamedFields(fields) }) => fields.iter().enumerate()
+                .map(|(idx, declaration)| json!({ "name": idx.to_string(), "type": self.type_of(declaration) }))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    fn variants(&self, declaration: &str) -> &[(String, Declaration)] {
+        match self.definitions.get(declaration) {
+            Some(Definition::Enum { variants }) => variants,
+            _ => &[],
+        }
+    }
+
+    /// The structs and enums the instructions and accounts are made of, by name
+    fn types(&self) -> Vec<Value> {
+        let variant_structs: BTreeSet<&str> = self.definitions.iter()
+            .filter_map(|(declaration, definition)| match definition {
+                Definition::Enum { variants } if !declaration.starts_with("Option<") => {
+                    Some(variants.iter().map(|(_, declaration)| declaration.as_str()))
+                }
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        let instruction = TokenLockInstruction::declaration();
+        let mut names: Vec<&Declaration> = self.definitions.iter()
+            .filter(|(declaration, definition)| {
+                matches!(definition, Definition::Struct { .. } | Definition::Enum { .. })
+                    && !declaration.starts_with("Option<")
+                    && declaration.as_str() != "Pubkey"
+                    && **declaration != instruction
+                    && !variant_structs.contains(declaration.as_str())
+            })
+            .map(|(declaration, _)| declaration)
+            .collect();
+        names.sort();
+        names.into_iter()
+            .map(|name| match self.definitions[name] {
+                Definition::Enum { ref variants } => json!({
+                    "name": name,
+                    "kind": "enum",
+                    "variants": variants.iter()
+                        .map(|(variant, declaration)| json!({ "name": variant, "fields": self.fields(declaration) }))
+                        .collect::<Vec<_>>(),
+                }),
+                _ => json!({ "name": name, "kind": "struct", "fields": self.fields(name) }),
+            })
+            .collect()
+    }
+}
+
+/// Parses the type notation of the zero-copy field lists, `[T; N]` for arrays
+fn zero_copy_type(notation: &str) -> Value {
+    match notation.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')).and_then(|rest| rest.split_once("; ")) {
+        Some((elements, length)) => json!({ "array": [zero_copy_type(elements), length.parse::<usize>().unwrap()] }),
+        None => json!(notation),
+    }
+}
+
+/// Bytes a zero-copy field of `type` takes
+pub fn size_of(r#type: &Value) -> usize {
+    if let Some([elements, length]) = r#type.get("array").and_then(Value::as_array).map(Vec::as_slice) {
+        return size_of(elements) * length.as_u64().unwrap() as usize;
+    }
+    match r#type.as_str() {
+        Some("u8") => 1,
+        Some("u16") => 2,
+        Some("u32") => 4,
+        Some("u64") => 8,
+        Some("publicKey") => 32,
+        _ => panic!("{} has no fixed size", r#type),
+    }
+}
+
+/// A struct stored in place, with the offset of each field
+fn zero_copy_struct(name: &str, fields: &[(&str, &str)], size: usize) -> Value {
+    let mut offset = 0;
+    let fields: Vec<Value> = fields.iter()
+        .map(|(field, notation)| {
+            let r#type = zero_copy_type(notation);
+            let field = json!({ "name": field, "type": r#type, "offset": offset });
+            offset += size_of(&r#type);
+            field
+        })
+        .collect();
+    assert_eq!(offset, size, "the fields of {} don't add up to its size", name);
+    json!({ "name": name, "kind": "struct", "layout": "zeroCopy", "size": size, "fields": fields })
+}
+
+fn instructions(schema: &Schema) -> Vec<Value> {
+    let docs = instruction_docs();
+    schema.variants(&TokenLockInstruction::declaration()).iter().enumerate()
+        .map(|(discriminant, (name, declaration))| {
+            let doc = docs.get(name).cloned().unwrap_or_default();
+            json!({
+                "name": name,
+                "discriminant": discriminant,
+                "docs": doc.docs,
+                "accounts": doc.accounts.iter()
+                    .map(|account| json!({
+                        "index": account.index,
+                        "writable": account.writable,
+                        "signer": account.signer,
+                        "variadic": account.variadic,
+                        "description": account.description,
+                    }))
+                    .collect::<Vec<_>>(),
+                "args": schema.fields(declaration),
+            })
+        })
+        .collect()
+}
+
+fn accounts() -> Vec<Value> {
+    vec![
+        json!({
+            "name": "TokenLockAccount",
+            "layout": "zeroCopy",
+            "discriminator": String::from_utf8(TokenLockAccount::DISCRIMINATOR.to_vec()).unwrap(),
+            "version": TokenLockAccount::VERSION,
+            "sections": [
+                { "name": "header", "type": { "defined": "TokenLockHeader" }, "offset": 0 },
+                {
+                    "name": "release_schedules",
+                    "type": { "defined": "ReleaseSchedule" },
+                    "offset": TokenLockAccount::RELEASE_SCHEDULES_OFFSET,
+                    "stride": ReleaseSchedule::LEN,
+                    "capacity": TokenLockAccount::MAX_RELEASE_SCHEDULES,
+                    "count": "release_schedule_count",
+                },
+                {
+                    "name": "timelocks",
+                    "type": { "defined": "Timelock" },
+                    "offset": TokenLockAccount::TIMELOCKS_OFFSET,
+                    "stride": Timelock::LEN,
+                    "count": "timelock_count",
+                },
+            ],
+        }),
+        json!({ "name": "CancelProposal", "layout": "borsh", "type": { "defined": "CancelProposal" }, "size": CancelProposal::LEN }),
+        json!({ "name": "TokenMetadata", "layout": "borsh", "type": { "defined": "TokenMetadata" } }),
+    ]
+}
+
+fn constants() -> Vec<Value> {
+    let seed = |seed: &[u8]| String::from_utf8(seed.to_vec()).unwrap();
+    vec![
+        json!({ "name": "TOKEN_LOCK_SEED", "type": "string", "value": seed(TokenLockAccount::SEED) }),
+        json!({ "name": "VAULT_SEED", "type": "string", "value": seed(TokenLockAccount::VAULT_SEED) }),
+        json!({ "name": "CANCEL_PROPOSAL_SEED", "type": "string", "value": seed(CancelProposal::SEED) }),
+        json!({ "name": "METADATA_SEED", "type": "string", "value": seed(TokenMetadata::SEED) }),
+        json!({ "name": "MINT_AUTHORITY_SEED", "type": "string", "value": seed(TokenMetadata::MINT_AUTHORITY_SEED) }),
+        json!({ "name": "TOKEN_LOCK_DISCRIMINATOR", "type": "string", "value": seed(&TokenLockAccount::DISCRIMINATOR) }),
+        json!({ "name": "VERSION", "type": "u8", "value": TokenLockAccount::VERSION }),
+        json!({ "name": "MAX_RELEASE_SCHEDULES", "type": "u32", "value": TokenLockAccount::MAX_RELEASE_SCHEDULES }),
+        json!({ "name": "MAX_CANCELABLE_BY", "type": "u32", "value": Timelock::MAX_CANCELABLE_BY }),
+        json!({ "name": "MAX_APPROVALS", "type": "u32", "value": CancelProposal::MAX_APPROVALS }),
+        json!({ "name": "SURPLUS_CLAIM_DELAY", "type": "u32", "value": TokenLockAccount::SURPLUS_CLAIM_DELAY }),
+    ]
+}
+
+fn events() -> Vec<Value> {
+    EVENTS.iter()
+        .map(|(name, format, types)| {
+            let fields: Vec<&str> = format.split(' ')
+                .filter_map(|word| word.strip_prefix('{').and_then(|word| word.strip_suffix('}')))
+                .collect();
+            assert_eq!(fields.len(), types.len(), "{} logs {} values", name, fields.len());
+            json!({
+                "name": name,
+                "format": format,
+                "fields": fields.iter().zip(types.iter())
+                    .map(|(field, r#type)| json!({ "name": field, "type": r#type }))
+                    .collect::<Vec<_>>(),
+            })
+        })
+        .collect()
+}
+
+/// The IDL of the program
+pub fn build() -> Value {
+    let schema = Schema::new();
+    let mut types = schema.types();
+    types.push(zero_copy_struct("TokenLockHeader", TokenLockHeader::FIELDS, TokenLockHeader::LEN));
+    types.push(zero_copy_struct("Timelock", Timelock::FIELDS, Timelock::LEN));
+    json!({
+        "name": "tokenlock",
+        "programId": tokenlock::id().to_string(),
+        "constants": constants(),
+        "instructions": instructions(&schema),
+        "accounts": accounts(),
+        "types": types,
+        "errors": errors().into_iter()
+            .map(|error| json!({ "name": format!("{:?}", error), "code": u64::from(error.clone()), "msg": error.to_string() }))
+            .collect::<Vec<_>>(),
+        "events": events(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use borsh::BorshSerialize;
+    use tokenlock::{solana_program::pubkey::Pubkey, types::SurplusClaim};
+
+    fn field<'a>(idl: &'a Value, r#type: &str, name: &str) -> &'a Value {
+        idl["types"].as_array().unwrap().iter()
+            .find(|candidate| candidate["name"] == r#type).unwrap()["fields"].as_array().unwrap().iter()
+            .find(|field| field["name"] == name).unwrap()
+    }
+
+    fn read(data: &[u8], field: &Value) -> Vec<u8> {
+        let offset = field["offset"].as_u64().unwrap() as usize;
+        data[offset..offset + size_of(&field["type"])].to_vec()
+    }
+
+    #[test]
+    fn zero_copy_offsets_match_the_account_layout() {
+        let idl = build();
+        let mint = Pubkey::new_unique();
+        let treasury = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let canceler = Pubkey::new_unique();
+        let mut data = vec![0; TokenLockAccount::space(1)];
+        {
+            let mut account = TokenLockAccount::init(&mut data, &mint, &authority, 7, 1000, 25, Some(treasury)).unwrap();
+            account.set_surplus_claim(Some(SurplusClaim { amount: 1 << 40, claimable_at: 99 }));
+            let mut timelock = Timelock::new(3, 500, 800);
+            timelock.set_cancelable_by(&[recipient, canceler]);
+            timelock.set_cancel_threshold(2);
+            account.add_timelock(&recipient, timelock).unwrap();
+        }
+
+        assert_eq!(read(&data, field(&idl, "TokenLockHeader", "discriminator")), TokenLockAccount::DISCRIMINATOR);
+        assert_eq!(read(&data, field(&idl, "TokenLockHeader", "mint")), mint.to_bytes());
+        assert_eq!(read(&data, field(&idl, "TokenLockHeader", "treasury_authority")), treasury.to_bytes());
+        assert_eq!(read(&data, field(&idl, "TokenLockHeader", "authority")), authority.to_bytes());
+        assert_eq!(read(&data, field(&idl, "TokenLockHeader", "has_treasury_authority")), [1]);
+        assert_eq!(read(&data, field(&idl, "TokenLockHeader", "version")), [TokenLockAccount::VERSION]);
+        assert_eq!(read(&data, field(&idl, "TokenLockHeader", "nonce")), [7]);
+        assert_eq!(read(&data, field(&idl, "TokenLockHeader", "max_release_delay")), 1000u32.to_le_bytes());
+        assert_eq!(read(&data, field(&idl, "TokenLockHeader", "min_timelock_amount")), 25u32.to_le_bytes());
+        assert_eq!(read(&data, field(&idl, "TokenLockHeader", "timelock_count")), 1u32.to_le_bytes());
+        assert_eq!(read(&data, field(&idl, "TokenLockHeader", "surplus_claimable_at")), 99u32.to_le_bytes());
+        assert_eq!(read(&data, field(&idl, "TokenLockHeader", "surplus_claim_amount")), (1u64 << 40).to_le_bytes());
+
+        let timelock = &data[TokenLockAccount::TIMELOCKS_OFFSET..];
+        assert_eq!(read(timelock, field(&idl, "Timelock", "schedule_id")), 3u32.to_le_bytes());
+        assert_eq!(read(timelock, field(&idl, "Timelock", "commencement_timestamp")), 500u32.to_le_bytes());
+        assert_eq!(read(timelock, field(&idl, "Timelock", "total_amount")), 800u32.to_le_bytes());
+        assert_eq!(read(timelock, field(&idl, "Timelock", "recipient")), recipient.to_bytes());
+        assert_eq!(read(timelock, field(&idl, "Timelock", "cancelable_by"))[32..64], canceler.to_bytes());
+        assert_eq!(read(timelock, field(&idl, "Timelock", "cancelable_by_count")), [2]);
+        assert_eq!(read(timelock, field(&idl, "Timelock", "cancel_threshold")), [2]);
+    }
+
+    /// Logged by the greeting and the status reports, which change no state
+    const NOT_EVENTS: &[&str] = &["Greeted", "CancelStatus", "Reconcile"];
+
+    #[test]
+    fn events_match_the_program_logs() {
+        let processor = include_str!("../../program-rust/src/processor.rs");
+        let logged: BTreeSet<&str> = processor.split("msg!(\"").skip(1)
+            .filter_map(|rest| rest.split('"').next())
+            .collect();
+        let events: BTreeSet<String> = EVENTS.iter()
+            .map(|(_, format, _)| format.split(' ')
+                .map(|word| if word.starts_with('{') && word.ends_with('}') { "{}" } else { word })
+                .collect::<Vec<_>>()
+                .join(" "))
+            .collect();
+        for event in &events {
+            assert!(logged.contains(event.as_str()), "the program never logs {}", event);
+        }
+        for format in logged {
+            let name = format.split(' ').next().unwrap();
+            if format.contains("{}") && name.starts_with(char::is_uppercase) && !NOT_EVENTS.contains(&name) {
+                assert!(events.contains(format), "{} is missing from EVENTS", format);
+            }
+        }
+    }
+
+    #[test]
+    fn instructions_follow_the_enum() {
+        let idl = build();
+        let instructions = idl["instructions"].as_array().unwrap();
+        let create = &instructions[1];
+        assert_eq!(create["name"], "CreateReleaseSchedule");
+        assert_eq!(create["args"].as_array().unwrap().len(), 4);
+        assert_eq!(create["accounts"][0]["writable"], true);
+
+        let data = TokenLockInstruction::InitializeTokenLock {
+            nonce: 1,
+            max_release_delay: 2,
+            min_timelock_amount: 3,
+            space: 4,
+            treasury_authority: None,
+        }.try_to_vec().unwrap();
+        let initialize = instructions.iter().find(|instruction| instruction["name"] == "InitializeTokenLock").unwrap();
+        assert_eq!(initialize["discriminant"], data[0]);
+        assert_eq!(initialize["args"][4]["type"], json!({ "option": "publicKey" }));
+        let fund = instructions.iter().find(|instruction| instruction["name"] == "FundReleaseSchedule").unwrap();
+        assert_eq!(fund["args"][4]["type"], json!({ "vec": "publicKey" }));
+        let cancel = instructions.iter().find(|instruction| instruction["name"] == "CancelTimelock").unwrap();
+        assert_eq!(cancel["args"][3]["type"], json!({ "defined": "CancelMode" }));
+    }
+}
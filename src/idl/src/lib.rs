@@ -0,0 +1,44 @@
+//! Exports the interface of the token lock program, its instructions, accounts, errors and events, as an IDL
+//! and as a TypeScript borsh schema generated from it, so clients don't mirror the Rust types by hand.
+pub mod docs;
+pub mod idl;
+pub mod typescript;
+
+use std::path::{Path, PathBuf};
+
+/// Where the IDL is checked in, relative to the repository root
+pub const IDL_PATH: &str = "idl/tokenlock.json";
+/// Where the TypeScript schema is checked in, relative to the repository root
+pub const TYPESCRIPT_PATH: &str = "src/client/tokenlock_schema.ts";
+
+pub fn repository_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../..")
+}
+
+/// The generated files with their paths relative to the repository root
+pub fn generate() -> Vec<(&'static str, String)> {
+    let idl = idl::build();
+    vec![
+        (IDL_PATH, format!("{}\n", serde_json::to_string_pretty(&idl).unwrap())),
+        (TYPESCRIPT_PATH, typescript::generate(&idl)),
+    ]
+}
+
+/// The generated files whose checked in version differs from what the Rust types give
+pub fn outdated() -> Vec<&'static str> {
+    generate().into_iter()
+        .filter(|(path, content)| std::fs::read_to_string(repository_root().join(path)).ok().as_ref() != Some(content))
+        .map(|(path, _)| path)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_files_are_up_to_date() {
+        let outdated = outdated();
+        assert!(outdated.is_empty(), "{} changed, run `npm run generate:idl`", outdated.join(", "));
+    }
+}
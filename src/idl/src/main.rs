@@ -0,0 +1,22 @@
+//! Writes the IDL and the TypeScript schema of the token lock program, or with `--check` fails if the
+//! checked in files are out of date
+use std::{env, fs, process::exit};
+use tokenlock_idl::{generate, outdated, repository_root};
+
+fn main() {
+    if env::args().skip(1).any(|arg| arg == "--check") {
+        let outdated = outdated();
+        for path in &outdated {
+            eprintln!("{} is out of date", path);
+        }
+        exit(if outdated.is_empty() { 0 } else { 1 });
+    }
+    for (path, content) in generate() {
+        let path = repository_root().join(path);
+        if let Err(err) = fs::create_dir_all(path.parent().unwrap()).and_then(|_| fs::write(&path, content)) {
+            eprintln!("{}: {}", path.display(), err);
+            exit(1);
+        }
+        println!("wrote {}", path.display());
+    }
+}
@@ -0,0 +1,353 @@
+//! Generates a TypeScript module from the IDL: a class and a borsh schema entry for every type, for the
+//! `borsh` npm package, and decoders for the accounts and the events.
+use std::fmt::Write;
+use serde_json::Value;
+
+/// `TokenLockHeader` or `release_schedules` to `TOKEN_LOCK_HEADER` or `RELEASE_SCHEDULES`
+fn constant_case(name: &str) -> String {
+    let mut constant = String::new();
+    for (idx, c) in name.chars().enumerate() {
+        if c.is_uppercase() && idx > 0 {
+            constant.push('_');
+        }
+        constant.push(c.to_ascii_uppercase());
+    }
+    constant
+}
+
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// Arrays of bytes or public keys are read as a single byte array,
+/// the borsh package has no fixed size arrays of other types
+fn byte_array_length(r#type: &Value) -> Option<u64> {
+    let array = r#type.get("array")?.as_array()?;
+    let length = array[1].as_u64()?;
+    match array[0].as_str()? {
+        "u8" => Some(length),
+        "publicKey" => Some(length * 32),
+        elements => panic!("arrays of {} are not supported", elements),
+    }
+}
+
+fn ts_type(r#type: &Value) -> String {
+    if let Some(name) = r#type.as_str() {
+        return match name {
+            "u8" | "u16" | "u32" => "number".to_string(),
+            "u64" | "u128" => "BN".to_string(),
+            "string" => "string".to_string(),
+            "publicKey" => "Uint8Array".to_string(),
+            _ => panic!("unsupported type {}", name),
+        };
+    }
+    if byte_array_length(r#type).is_some() {
+        return "Uint8Array".to_string();
+    }
+    if let Some(inner) = r#type.get("option") {
+        return format!("{} | undefined", ts_type(inner));
+    }
+    if let Some(elements) = r#type.get("vec") {
+        return format!("{}[]", ts_type(elements));
+    }
+    r#type["defined"].as_str().unwrap().to_string()
+}
+
+fn schema_type(r#type: &Value) -> String {
+    if let Some(name) = r#type.as_str() {
+        return match name {
+            "publicKey" => "[32]".to_string(),
+            name => quote(name),
+        };
+    }
+    if let Some(length) = byte_array_length(r#type) {
+        return format!("[{}]", length);
+    }
+    if let Some(inner) = r#type.get("option") {
+        return format!("{{kind: 'option', type: {}}}", schema_type(inner));
+    }
+    if let Some(elements) = r#type.get("vec") {
+        return format!("[{}]", schema_type(elements));
+    }
+    r#type["defined"].as_str().unwrap().to_string()
+}
+
+fn fields(value: &Value) -> &[Value] {
+    value.as_array().map(Vec::as_slice).unwrap_or(&[])
+}
+
+/// Writes the class of a struct and collects its schema entry
+fn write_struct(out: &mut String, schema: &mut Vec<String>, name: &str, struct_fields: &[Value]) {
+    if struct_fields.is_empty() {
+        writeln!(out, "export class {} extends Struct {{}}\n", name).unwrap();
+    } else {
+        writeln!(out, "export class {} extends Struct {{", name).unwrap();
+        for field in struct_fields {
+            let comment = match byte_array_length(&field["type"]) {
+                Some(_) if field["type"]["array"][0] != "u8" => format!(" // [{}; {}]", field["type"]["array"][0].as_str().unwrap(), field["type"]["array"][1]),
+                _ => String::new(),
+            };
+            writeln!(out, "  declare {}: {};{}", field["name"].as_str().unwrap(), ts_type(&field["type"]), comment).unwrap();
+        }
+        writeln!(out, "}}\n").unwrap();
+    }
+    let mut entry = format!("  [\n    {},\n    {{\n      kind: 'struct',\n      fields: [\n", name);
+    for field in struct_fields {
+        writeln!(entry, "        [{}, {}],", quote(field["name"].as_str().unwrap()), schema_type(&field["type"])).unwrap();
+    }
+    entry.push_str("      ],\n    },\n  ],\n");
+    schema.push(entry);
+}
+
+/// Writes a class per variant and the class of the enum, which holds the variant named by its `enum` field
+fn write_enum(out: &mut String, schema: &mut Vec<String>, name: &str, variants: &[Value], fields_key: &str) {
+    for variant in variants {
+        write_struct(out, schema, &format!("{}{}", name, variant["name"].as_str().unwrap()), fields(&variant[fields_key]));
+    }
+    writeln!(out, "export class {} extends Enum {{", name).unwrap();
+    for variant in variants {
+        let variant = variant["name"].as_str().unwrap();
+        writeln!(out, "  declare {}?: {}{};", variant, name, variant).unwrap();
+    }
+    writeln!(out, "}}\n").unwrap();
+
+    let mut entry = format!("  [\n    {},\n    {{\n      kind: 'enum',\n      field: 'enum',\n      values: [\n", name);
+    for variant in variants {
+        let variant = variant["name"].as_str().unwrap();
+        writeln!(entry, "        [{}, {}{}],", quote(variant), name, variant).unwrap();
+    }
+    entry.push_str("      ],\n    },\n  ],\n");
+    schema.push(entry);
+}
+
+const PRELUDE: &str = "class Struct {
+  constructor(properties: Record<string, unknown>) {
+    Object.assign(this, properties);
+  }
+}
+
+class Enum extends Struct {
+  declare enum: string;
+}
+
+";
+
+const DECODE_RECORDS: &str = "function decodeRecords<T>(
+  data: Buffer,
+  classType: {new (properties: Record<string, unknown>): T},
+  offset: number,
+  stride: number,
+  count: number,
+): T[] {
+  const records: T[] = [];
+  for (let idx = 0; idx < count; idx++) {
+    const start = offset + idx * stride;
+    records.push(borsh.deserialize(SCHEMA, classType, data.slice(start, start + stride)));
+  }
+  return records;
+}
+
+";
+
+const PARSE_EVENT: &str = "export type EventValue = PublicKey | BN | number;
+
+export interface Event {
+  name: string;
+  fields: Record<string, EventValue>;
+}
+
+function parseEventValue(type: string, word: string): EventValue | undefined {
+  if (type === 'publicKey') {
+    try {
+      return new PublicKey(word);
+    } catch (err) {
+      return undefined;
+    }
+  }
+  if (!/^[0-9]+$/.test(word)) {
+    return undefined;
+  }
+  return type === 'u64' ? new BN(word, 10) : Number(word);
+}
+
+/**
+ * Parses a message the program logged, without the `Program log: ` prefix.
+ * Messages that aren't events, like error messages, give `undefined`.
+ */
+export function parseEvent(message: string): Event | undefined {
+  const words = message.split(' ');
+  for (const event of EVENTS) {
+    const format = event.format.split(' ');
+    if (format.length !== words.length) {
+      continue;
+    }
+    const fields: Record<string, EventValue> = {};
+    let field = 0;
+    const matches = format.every((word, idx) => {
+      if (!word.startsWith('{')) {
+        return word === words[idx];
+      }
+      const [name, type] = event.fields[field++];
+      const value = parseEventValue(type, words[idx]);
+      if (value === undefined) {
+        return false;
+      }
+      fields[name] = value;
+      return true;
+    });
+    if (matches) {
+      return {name: event.name, fields};
+    }
+  }
+  return undefined;
+}
+";
+
+/// The TypeScript module of the IDL
+pub fn generate(idl: &Value) -> String {
+    let mut out = String::new();
+    out.push_str("// Generated from the Rust types of the program by `npm run generate:idl`, do not edit.\n");
+    out.push_str("// See idl/tokenlock.json for the IDL it is generated from.\n");
+    out.push_str("/* eslint-disable @typescript-eslint/ban-types */\n\n");
+    out.push_str("import {PublicKey} from '@solana/web3.js';\nimport BN from 'bn.js';\nimport * as borsh from 'borsh';\n\n");
+    writeln!(out, "export const PROGRAM_ID = new PublicKey({});\n", quote(idl["programId"].as_str().unwrap())).unwrap();
+
+    for constant in fields(&idl["constants"]) {
+        let value = match &constant["value"] {
+            Value::String(text) => quote(text),
+            value => value.to_string(),
+        };
+        writeln!(out, "export const {} = {};", constant["name"].as_str().unwrap(), value).unwrap();
+    }
+    let types = fields(&idl["types"]);
+    for r#type in types.iter().filter(|r#type| r#type.get("size").is_some()) {
+        writeln!(out, "export const {}_LEN = {};", constant_case(r#type["name"].as_str().unwrap()), r#type["size"]).unwrap();
+    }
+    let zero_copy_accounts: Vec<&Value> = fields(&idl["accounts"]).iter().filter(|account| account["layout"] == "zeroCopy").collect();
+    for account in &zero_copy_accounts {
+        for section in fields(&account["sections"]).iter().filter(|section| section["offset"] != 0) {
+            let record = section["type"]["defined"].as_str().unwrap();
+            if !types.iter().any(|r#type| r#type["name"] == record && r#type.get("size").is_some()) {
+                writeln!(out, "export const {}_LEN = {};", constant_case(record), section["stride"]).unwrap();
+            }
+            writeln!(out, "export const {}_OFFSET = {};", constant_case(section["name"].as_str().unwrap()), section["offset"]).unwrap();
+        }
+    }
+    out.push('\n');
+    out.push_str(PRELUDE);
+
+    let mut schema = vec![];
+    for r#type in types {
+        let name = r#type["name"].as_str().unwrap();
+        if r#type["kind"] == "enum" {
+            write_enum(&mut out, &mut schema, name, fields(&r#type["variants"]), "fields");
+        } else {
+            write_struct(&mut out, &mut schema, name, fields(&r#type["fields"]));
+        }
+    }
+    write_enum(&mut out, &mut schema, "TokenLockInstruction", fields(&idl["instructions"]), "args");
+
+    out.push_str("export const SCHEMA = new Map<Function, unknown>([\n");
+    for entry in schema {
+        out.push_str(&entry);
+    }
+    out.push_str("]);\n\n");
+
+    out.push_str("export function encodeInstruction(instruction: TokenLockInstruction): Buffer {\n");
+    out.push_str("  return Buffer.from(borsh.serialize(SCHEMA, instruction));\n}\n\n");
+
+    if !zero_copy_accounts.is_empty() {
+        out.push_str(DECODE_RECORDS);
+    }
+    for account in fields(&idl["accounts"]) {
+        let name = account["name"].as_str().unwrap();
+        if account["layout"] == "zeroCopy" {
+            write_zero_copy_decoder(&mut out, name, fields(&account["sections"]));
+        } else {
+            let r#type = account["type"]["defined"].as_str().unwrap();
+            // borsh accounts are allocated for their largest size and may end in unused bytes
+            writeln!(out, "export function decode{}(data: Buffer): {} {{", name, r#type).unwrap();
+            writeln!(out, "  return borsh.deserializeUnchecked(SCHEMA, {}, data);\n}}\n", r#type).unwrap();
+        }
+    }
+
+    out.push_str("export const ERRORS: {name: string; code: number; msg: string}[] = [\n");
+    for error in fields(&idl["errors"]) {
+        writeln!(
+            out,
+            "  {{name: {}, code: {}, msg: {}}},",
+            quote(error["name"].as_str().unwrap()),
+            error["code"],
+            quote(error["msg"].as_str().unwrap()),
+        ).unwrap();
+    }
+    out.push_str("];\n\n");
+
+    out.push_str("export const EVENTS: {name: string; format: string; fields: [string, string][]}[] = [\n");
+    for event in fields(&idl["events"]) {
+        let event_fields: Vec<String> = fields(&event["fields"]).iter()
+            .map(|field| format!("[{}, {}]", quote(field["name"].as_str().unwrap()), quote(field["type"].as_str().unwrap())))
+            .collect();
+        writeln!(out, "  {{").unwrap();
+        writeln!(out, "    name: {},", quote(event["name"].as_str().unwrap())).unwrap();
+        writeln!(out, "    format: {},", quote(event["format"].as_str().unwrap())).unwrap();
+        writeln!(out, "    fields: [{}],", event_fields.join(", ")).unwrap();
+        writeln!(out, "  }},").unwrap();
+    }
+    out.push_str("];\n\n");
+    out.push_str(PARSE_EVENT);
+    out
+}
+
+/// The first section of a zero-copy account is its header, it holds the counts of the record sections
+fn write_zero_copy_decoder(out: &mut String, name: &str, sections: &[Value]) {
+    let (header, records) = sections.split_first().expect("a zero-copy account starts with its header");
+    let header_name = header["name"].as_str().unwrap();
+    let header_type = header["type"]["defined"].as_str().unwrap();
+    writeln!(out, "export interface {} {{", name).unwrap();
+    writeln!(out, "  {}: {};", header_name, header_type).unwrap();
+    for section in records {
+        writeln!(out, "  {}: {}[];", section["name"].as_str().unwrap(), section["type"]["defined"].as_str().unwrap()).unwrap();
+    }
+    writeln!(out, "}}\n").unwrap();
+
+    writeln!(out, "export function decode{}(data: Buffer): {} {{", name, name).unwrap();
+    writeln!(out, "  const {} = borsh.deserialize(SCHEMA, {}, data.slice(0, {}_LEN));", header_name, header_type, constant_case(header_type)).unwrap();
+    writeln!(out, "  if (Buffer.from({}.discriminator).toString() !== TOKEN_LOCK_DISCRIMINATOR || {}.version !== VERSION) {{", header_name, header_name).unwrap();
+    writeln!(out, "    throw new Error('Not a {} in the current layout');\n  }}", name).unwrap();
+    writeln!(out, "  return {{\n    {},", header_name).unwrap();
+    for section in records {
+        let section_name = section["name"].as_str().unwrap();
+        let record = section["type"]["defined"].as_str().unwrap();
+        writeln!(
+            out,
+            "    {}: decodeRecords(data, {}, {}_OFFSET, {}_LEN, {}.{}),",
+            section_name,
+            record,
+            constant_case(section_name),
+            constant_case(record),
+            header_name,
+            section["count"].as_str().unwrap(),
+        ).unwrap();
+    }
+    writeln!(out, "  }};\n}}\n").unwrap();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn types_map_to_the_borsh_package() {
+        assert_eq!(constant_case("TokenLockHeader"), "TOKEN_LOCK_HEADER");
+        assert_eq!(constant_case("release_schedules"), "RELEASE_SCHEDULES");
+        assert_eq!(schema_type(&json!("u32")), "'u32'");
+        assert_eq!(schema_type(&json!({ "option": "publicKey" })), "{kind: 'option', type: [32]}");
+        assert_eq!(schema_type(&json!({ "vec": "publicKey" })), "[[32]]");
+        assert_eq!(schema_type(&json!({ "array": ["publicKey", 10] })), "[320]");
+        assert_eq!(schema_type(&json!({ "defined": "CancelMode" })), "CancelMode");
+        assert_eq!(ts_type(&json!({ "option": "publicKey" })), "Uint8Array | undefined");
+        assert_eq!(ts_type(&json!("u64")), "BN");
+    }
+}
@@ -0,0 +1,193 @@
+//! The events the program logs, one `msg!` line each, e.g. `Transfer <from> <to> <value>`
+use std::str::FromStr;
+use solana_sdk::pubkey::Pubkey;
+
+const LOG_PREFIX: &str = "Program log: ";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    TokenInitialized { mint: Pubkey, treasury: Pubkey, total_supply: u64 },
+    TokenLockInitialized { token_lock: Pubkey, mint: Pubkey, nonce: u8 },
+    TokenLockMigrated { token_lock: Pubkey, from_version: u8, to_version: u8 },
+    TimelockMigrated { recipient: Pubkey, old_index: u32, new_index: u32 },
+    ReleaseScheduleCreated { schedule_id: u32 },
+    /// `amount` is what the vault received
+    ScheduleFunded { to: Pubkey, schedule_id: u32, amount: u32 },
+    /// Logged right after `ScheduleFunded`
+    TimelockCommencing { timelock_index: u32, commencement_timestamp: u32 },
+    Transfer { from: Pubkey, to: Pubkey, value: u32 },
+    TimelockOwnershipTransferred { owner: Pubkey, timelock_index: u32, new_owner: Pubkey },
+    TimelockSplit { owner: Pubkey, timelock_index: u32, amount: u32 },
+    TimelocksMerged { owner: Pubkey, timelock_index: u32, other_timelock_index: u32 },
+    Burned { recipient: Pubkey, amount: u32 },
+    TimelockCanceled { canceler: Pubkey, target: Pubkey, timelock_index: u32 },
+    CancelProposed { canceler: Pubkey, target: Pubkey, timelock_index: u32 },
+    CancelApproved { canceler: Pubkey, target: Pubkey, timelock_index: u32 },
+    CancelProposalClosed { proposer: Pubkey, target: Pubkey, timelock_index: u32 },
+    CancelerAdded { target: Pubkey, timelock_index: u32, canceler: Pubkey },
+    CancelerRemoved { target: Pubkey, timelock_index: u32, canceler: Pubkey },
+    CancelRenounced { target: Pubkey, timelock_index: u32, canceler: Pubkey },
+    SurplusClaimed { funder: Pubkey, amount: u64, claimable_at: u32 },
+    SurplusSwept { authority: Pubkey, amount: u64 },
+}
+
+fn arg<T: FromStr>(args: &[&str], idx: usize) -> Option<T> {
+    args.get(idx)?.parse().ok()
+}
+
+impl Event {
+    /// Parses a message the program logged, without the `Program log: ` prefix.
+    /// Messages that aren't events, like error messages, give `None`.
+    pub fn parse(message: &str) -> Option<Event> {
+        let words: Vec<&str> = message.split(' ').collect();
+        let (name, args) = words.split_first()?;
+        let event = match *name {
+            "TokenInitialized" => Event::TokenInitialized { mint: arg(args, 0)?, treasury: arg(args, 1)?, total_supply: arg(args, 2)? },
+            "TokenLockInitialized" => Event::TokenLockInitialized { token_lock: arg(args, 0)?, mint: arg(args, 1)?, nonce: arg(args, 2)? },
+            "TokenLockMigrated" => Event::TokenLockMigrated { token_lock: arg(args, 0)?, from_version: arg(args, 1)?, to_version: arg(args, 2)? },
+            "TimelockMigrated" => Event::TimelockMigrated { recipient: arg(args, 0)?, old_index: arg(args, 1)?, new_index: arg(args, 2)? },
+            "ReleaseScheduleCreated" => Event::ReleaseScheduleCreated { schedule_id: arg(args, 0)? },
+            "ScheduleFunded" => Event::ScheduleFunded { to: arg(args, 0)?, schedule_id: arg(args, 1)?, amount: arg(args, 2)? },
+            "timelock" if args.get(1) == Some(&"commencing") => Event::TimelockCommencing { timelock_index: arg(args, 0)?, commencement_timestamp: arg(args, 2)? },
+            "Transfer" => Event::Transfer { from: arg(args, 0)?, to: arg(args, 1)?, value: arg(args, 2)? },
+            "TimelockOwnershipTransferred" => Event::TimelockOwnershipTransferred { owner: arg(args, 0)?, timelock_index: arg(args, 1)?, new_owner: arg(args, 2)? },
+            "TimelockSplit" => Event::TimelockSplit { owner: arg(args, 0)?, timelock_index: arg(args, 1)?, amount: arg(args, 2)? },
+            "TimelocksMerged" => Event::TimelocksMerged { owner: arg(args, 0)?, timelock_index: arg(args, 1)?, other_timelock_index: arg(args, 2)? },
+            "Burned" => Event::Burned { recipient: arg(args, 0)?, amount: arg(args, 1)? },
+            "TimelockCanceled" => Event::TimelockCanceled { canceler: arg(args, 0)?, target: arg(args, 1)?, timelock_index: arg(args, 2)? },
+            "CancelProposed" => Event::CancelProposed { canceler: arg(args, 0)?, target: arg(args, 1)?, timelock_index: arg(args, 2)? },
+            "CancelApproved" => Event::CancelApproved { canceler: arg(args, 0)?, target: arg(args, 1)?, timelock_index: arg(args, 2)? },
+            "CancelProposalClosed" => Event::CancelProposalClosed { proposer: arg(args, 0)?, target: arg(args, 1)?, timelock_index: arg(args, 2)? },
+            "CancelerAdded" => Event::CancelerAdded { target: arg(args, 0)?, timelock_index: arg(args, 1)?, canceler: arg(args, 2)? },
+            "CancelerRemoved" => Event::CancelerRemoved { target: arg(args, 0)?, timelock_index: arg(args, 1)?, canceler: arg(args, 2)? },
+            "CancelRenounced" => Event::CancelRenounced { target: arg(args, 0)?, timelock_index: arg(args, 1)?, canceler: arg(args, 2)? },
+            // SurplusClaimed <funder> <amount> claimable at <timestamp>
+            "SurplusClaimed" => Event::SurplusClaimed { funder: arg(args, 0)?, amount: arg(args, 1)?, claimable_at: arg(args, 4)? },
+            "SurplusSwept" => Event::SurplusSwept { authority: arg(args, 0)?, amount: arg(args, 1)? },
+            _ => return None,
+        };
+        Some(event)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::TokenInitialized { .. } => "TokenInitialized",
+            Event::TokenLockInitialized { .. } => "TokenLockInitialized",
+            Event::TokenLockMigrated { .. } => "TokenLockMigrated",
+            Event::TimelockMigrated { .. } => "TimelockMigrated",
+            Event::ReleaseScheduleCreated { .. } => "ReleaseScheduleCreated",
+            Event::ScheduleFunded { .. } => "ScheduleFunded",
+            Event::TimelockCommencing { .. } => "TimelockCommencing",
+            Event::Transfer { .. } => "Transfer",
+            Event::TimelockOwnershipTransferred { .. } => "TimelockOwnershipTransferred",
+            Event::TimelockSplit { .. } => "TimelockSplit",
+            Event::TimelocksMerged { .. } => "TimelocksMerged",
+            Event::Burned { .. } => "Burned",
+            Event::TimelockCanceled { .. } => "TimelockCanceled",
+            Event::CancelProposed { .. } => "CancelProposed",
+            Event::CancelApproved { .. } => "CancelApproved",
+            Event::CancelProposalClosed { .. } => "CancelProposalClosed",
+            Event::CancelerAdded { .. } => "CancelerAdded",
+            Event::CancelerRemoved { .. } => "CancelerRemoved",
+            Event::CancelRenounced { .. } => "CancelRenounced",
+            Event::SurplusClaimed { .. } => "SurplusClaimed",
+            Event::SurplusSwept { .. } => "SurplusSwept",
+        }
+    }
+}
+
+/// Splits the log of a transaction by top-level instruction. For each instruction of the transaction
+/// the messages `program_id` logged itself while running it, leaving out the logs of programs it invoked.
+pub fn instruction_logs(logs: &[String], program_id: &Pubkey) -> Vec<Vec<String>> {
+    let program_id = program_id.to_string();
+    let mut instructions: Vec<Vec<String>> = vec![];
+    let mut stack: Vec<String> = vec![];
+    for line in logs {
+        if let Some(message) = line.strip_prefix(LOG_PREFIX) {
+            if stack.last() == Some(&program_id) {
+                if let Some(messages) = instructions.last_mut() {
+                    messages.push(message.to_string());
+                }
+            }
+            continue;
+        }
+        let words: Vec<&str> = line.split(' ').collect();
+        match words.as_slice() {
+            ["Program", program, "invoke", depth] => {
+                if *depth == "[1]" {
+                    instructions.push(vec![]);
+                }
+                stack.push(program.to_string());
+            }
+            ["Program", program, "success"] if stack.last().map(String::as_str) == Some(*program) => {
+                stack.pop();
+            }
+            ["Program", program, "failed:", ..] if stack.last().map(String::as_str) == Some(*program) => {
+                stack.pop();
+            }
+            _ => {}
+        }
+    }
+    instructions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_events() {
+        let to = Pubkey::new_unique();
+        let from = Pubkey::new_unique();
+        assert_eq!(Event::parse(&format!("ScheduleFunded {} 2 990", to)), Some(Event::ScheduleFunded { to, schedule_id: 2, amount: 990 }));
+        assert_eq!(Event::parse("timelock 7 commencing 1640995200"), Some(Event::TimelockCommencing { timelock_index: 7, commencement_timestamp: 1640995200 }));
+        assert_eq!(Event::parse(&format!("Transfer {} {} 25", from, to)), Some(Event::Transfer { from, to, value: 25 }));
+        assert_eq!(
+            Event::parse(&format!("SurplusClaimed {} 40 claimable at 5000", from)),
+            Some(Event::SurplusClaimed { funder: from, amount: 40, claimable_at: 5000 }),
+        );
+        assert_eq!(Event::parse("amount < min funding"), None);
+        assert_eq!(Event::parse("Transfer not-an-address"), None);
+    }
+
+    /// A value of `type` the way the program logs it
+    fn sample(r#type: &str) -> String {
+        match r#type {
+            "publicKey" => Pubkey::new_unique().to_string(),
+            _ => "7".to_string(),
+        }
+    }
+
+    #[test]
+    fn every_event_of_the_idl_parses() {
+        let idl: serde_json::Value = serde_json::from_str(include_str!("../../../idl/tokenlock.json")).unwrap();
+        for event in idl["events"].as_array().unwrap() {
+            let mut types = event["fields"].as_array().unwrap().iter().map(|field| field["type"].as_str().unwrap());
+            let message = event["format"].as_str().unwrap().split(' ')
+                .map(|word| if word.starts_with('{') { sample(types.next().unwrap()) } else { word.to_string() })
+                .collect::<Vec<_>>()
+                .join(" ");
+            assert_eq!(Event::parse(&message).as_ref().map(Event::name), event["name"].as_str(), "{}", message);
+        }
+    }
+
+    #[test]
+    fn logs_are_split_by_instruction() {
+        let program_id = Pubkey::new_unique();
+        let token_program = Pubkey::new_unique();
+        let logs: Vec<String> = vec![
+            format!("Program {} invoke [1]", token_program),
+            "Program log: Instruction: Transfer".to_string(),
+            format!("Program {} success", token_program),
+            format!("Program {} invoke [1]", program_id),
+            format!("Program {} invoke [2]", token_program),
+            "Program log: Instruction: Transfer".to_string(),
+            format!("Program {} consumed 2000 of 190000 compute units", token_program),
+            format!("Program {} success", token_program),
+            "Program log: Transfer a b 5".to_string(),
+            format!("Program {} consumed 9000 of 200000 compute units", program_id),
+            format!("Program {} success", program_id),
+        ];
+        assert_eq!(instruction_logs(&logs, &program_id), vec![vec![], vec!["Transfer a b 5".to_string()]]);
+    }
+}
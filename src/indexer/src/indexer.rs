@@ -0,0 +1,156 @@
+//! Feeds the store from RPC history or from a ledger dump
+use std::{error::Error, io::BufRead, str::FromStr};
+use solana_client::{
+    rpc_client::GetConfirmedSignaturesForAddress2Config,
+    rpc_config::RpcTransactionConfig,
+};
+use solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey, signature::Signature};
+use solana_transaction_status::{EncodedConfirmedTransaction, UiTransactionEncoding};
+use tokenlock::client::TokenLockClient;
+use crate::{
+    store::Store,
+    transaction::{decode_transaction, ProgramTransaction},
+};
+
+/// Signatures fetched per `getSignaturesForAddress` request
+const SIGNATURE_PAGE: usize = 1000;
+/// Signatures checked per `getSignatureStatuses` request
+const STATUS_PAGE: usize = 256;
+
+/// Decodes a transaction in the `getTransaction` format, which has to be base64 encoded.
+/// Failed transactions changed nothing and give `None`.
+pub fn decode_encoded_transaction(program_id: &Pubkey, encoded: &EncodedConfirmedTransaction) -> Option<ProgramTransaction> {
+    let meta = encoded.transaction.meta.as_ref()?;
+    if meta.err.is_some() {
+        return None;
+    }
+    let transaction = encoded.transaction.transaction.decode()?;
+    let signature = transaction.signatures.first()?.to_string();
+    let logs = meta.log_messages.clone().unwrap_or_default();
+    Some(decode_transaction(program_id, signature, encoded.slot, encoded.block_time, &transaction.message, &logs))
+}
+
+/// Indexes a ledger dump, one `getTransaction` result in base64 encoding per line, oldest first.
+/// The dump is taken as finalized. Returns the number of transactions indexed.
+pub fn import_dump<R: BufRead>(store: &mut Store, program_id: &Pubkey, reader: R) -> Result<usize, Box<dyn Error>> {
+    let mut indexed = 0;
+    let mut last_slot = None;
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let encoded: EncodedConfirmedTransaction = serde_json::from_str(&line)?;
+        last_slot = Some(encoded.slot);
+        if let Some(transaction) = decode_encoded_transaction(program_id, &encoded) {
+            if store.apply(&transaction)? {
+                indexed += 1;
+            }
+        }
+    }
+    if let Some(slot) = last_slot {
+        if store.finalized_slot()?.map_or(true, |finalized| finalized < slot) {
+            store.set_finalized_slot(slot)?;
+        }
+    }
+    Ok(indexed)
+}
+
+/// What a sync changed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SyncReport {
+    /// Transactions removed because they are no longer on the chain
+    pub rolled_back: usize,
+    pub indexed: usize,
+    pub finalized_slot: u64,
+}
+
+/// Keeps a store in step with the cluster. Transactions are indexed once confirmed, the ones
+/// not finalized yet are checked again on every sync and rolled back with everything after them
+/// if they were dropped in a fork.
+pub struct Indexer {
+    pub store: Store,
+    client: TokenLockClient,
+}
+
+impl Indexer {
+    pub fn new(store: Store, client: TokenLockClient) -> Self {
+        Indexer { store, client }
+    }
+
+    pub fn sync(&mut self) -> Result<SyncReport, Box<dyn Error>> {
+        let program_id = *self.client.program_id();
+        // taken first, everything fetched afterwards is at least this far along
+        let finalized_slot = self.client.rpc_client().get_slot_with_commitment(CommitmentConfig::finalized())?;
+        let rolled_back = self.roll_back_dropped()?;
+
+        let until = self.store.last_signature()?.map(|signature| Signature::from_str(&signature)).transpose()?;
+        let mut signatures = vec![];
+        let mut before = None;
+        loop {
+            let page = self.client.rpc_client().get_signatures_for_address_with_config(
+                &program_id,
+                GetConfirmedSignaturesForAddress2Config {
+                    before,
+                    until,
+                    limit: Some(SIGNATURE_PAGE),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                },
+            )?;
+            let last = match page.last() {
+                Some(last) => Signature::from_str(&last.signature)?,
+                None => break,
+            };
+            before = Some(last);
+            signatures.extend(page.into_iter().filter(|status| status.err.is_none()).map(|status| status.signature));
+        }
+
+        // newest first from the RPC
+        let mut indexed = 0;
+        for signature in signatures.iter().rev() {
+            let encoded = self.client.rpc_client().get_transaction_with_config(
+                &Signature::from_str(signature)?,
+                RpcTransactionConfig {
+                    encoding: Some(UiTransactionEncoding::Base64),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                },
+            )?;
+            if let Some(transaction) = decode_encoded_transaction(&program_id, &encoded) {
+                if self.store.apply(&transaction)? {
+                    indexed += 1;
+                }
+            }
+        }
+        self.store.set_finalized_slot(finalized_slot)?;
+        self.snapshot_timelocks()?;
+        Ok(SyncReport { rolled_back, indexed, finalized_slot })
+    }
+
+    /// Rolls back from the oldest unfinalized transaction that is no longer on the chain in the slot
+    /// it was indexed at. Returns the number of transactions removed.
+    fn roll_back_dropped(&mut self) -> Result<usize, Box<dyn Error>> {
+        let unfinalized = self.store.unfinalized()?;
+        for page in unfinalized.chunks(STATUS_PAGE) {
+            let signatures = page.iter().map(|(signature, _)| Signature::from_str(signature)).collect::<Result<Vec<_>, _>>()?;
+            let statuses = self.client.rpc_client().get_signature_statuses_with_history(&signatures)?.value;
+            let dropped = page.iter().zip(statuses).find(|((_, slot), status)| {
+                !matches!(status, Some(status) if status.slot == *slot && status.err.is_none())
+            });
+            if let Some(((_, slot), _)) = dropped {
+                return Ok(self.store.rollback(*slot)?);
+            }
+        }
+        Ok(0)
+    }
+
+    /// Replaces the timelocks of every indexed lockup with their current state
+    fn snapshot_timelocks(&mut self) -> Result<(), Box<dyn Error>> {
+        let slot = self.client.rpc_client().get_slot()?;
+        for token_lock in self.store.token_locks()? {
+            let token_lock = Pubkey::from_str(&token_lock)?;
+            let timelocks = self.client.get_timelocks(&token_lock, None)?;
+            self.store.replace_timelocks(&token_lock, slot, &timelocks)?;
+        }
+        Ok(())
+    }
+}
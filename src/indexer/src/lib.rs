@@ -0,0 +1,6 @@
+//! Replays the token lock program's transactions, from RPC history or a ledger dump, into a SQLite
+//! database of lockups, schedules, grants, transfers and cancellations.
+pub mod events;
+pub mod indexer;
+pub mod store;
+pub mod transaction;
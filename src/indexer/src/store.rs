@@ -0,0 +1,484 @@
+//! The SQLite database of lockup history. Every row records the slot and signature of the transaction
+//! it comes from, so the rows of transactions that left the chain in a fork can be rolled back.
+use std::path::Path;
+use rusqlite::{params, Connection, OptionalExtension, Transaction as DbTransaction};
+use solana_sdk::pubkey::Pubkey;
+use tokenlock::{instruction::TokenLockInstruction, types::{CancelMode, Timelock}};
+use crate::{
+    events::Event,
+    transaction::{ProgramInstruction, ProgramTransaction},
+};
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS transactions (
+    signature TEXT PRIMARY KEY,
+    slot INTEGER NOT NULL,
+    block_time INTEGER
+);
+CREATE TABLE IF NOT EXISTS logs (
+    signature TEXT NOT NULL,
+    slot INTEGER NOT NULL,
+    instruction INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    token_lock TEXT,
+    event TEXT,
+    message TEXT NOT NULL,
+    PRIMARY KEY (signature, instruction, position)
+);
+CREATE TABLE IF NOT EXISTS token_locks (
+    address TEXT PRIMARY KEY,
+    mint TEXT NOT NULL,
+    nonce INTEGER NOT NULL,
+    max_release_delay INTEGER NOT NULL,
+    min_timelock_amount INTEGER NOT NULL,
+    treasury_authority TEXT,
+    slot INTEGER NOT NULL,
+    signature TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS schedules (
+    token_lock TEXT NOT NULL,
+    schedule_id INTEGER,
+    release_count INTEGER NOT NULL,
+    delay_until_first_release_in_seconds INTEGER NOT NULL,
+    initial_release_portion_in_bips INTEGER NOT NULL,
+    period_between_releases_in_seconds INTEGER NOT NULL,
+    slot INTEGER NOT NULL,
+    signature TEXT NOT NULL,
+    instruction INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS grants (
+    token_lock TEXT NOT NULL,
+    timelock_index INTEGER,
+    recipient TEXT NOT NULL,
+    funder TEXT,
+    schedule_id INTEGER NOT NULL,
+    commencement_timestamp INTEGER NOT NULL,
+    amount INTEGER NOT NULL,
+    received INTEGER,
+    cancelable_by TEXT NOT NULL,
+    cancel_threshold INTEGER NOT NULL,
+    slot INTEGER NOT NULL,
+    signature TEXT NOT NULL,
+    instruction INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS transfers (
+    token_lock TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    from_address TEXT,
+    to_address TEXT,
+    timelock_index INTEGER,
+    other_timelock_index INTEGER,
+    amount INTEGER,
+    slot INTEGER NOT NULL,
+    signature TEXT NOT NULL,
+    instruction INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS cancellations (
+    token_lock TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    canceler TEXT,
+    target TEXT,
+    timelock_index INTEGER,
+    reclaim_to TEXT,
+    mode TEXT,
+    slot INTEGER NOT NULL,
+    signature TEXT NOT NULL,
+    instruction INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS timelocks (
+    token_lock TEXT NOT NULL,
+    timelock_index INTEGER NOT NULL,
+    recipient TEXT NOT NULL,
+    schedule_id INTEGER NOT NULL,
+    commencement_timestamp INTEGER NOT NULL,
+    total_amount INTEGER NOT NULL,
+    tokens_transferred INTEGER NOT NULL,
+    vesting_stopped_at INTEGER NOT NULL,
+    cancelable_by TEXT NOT NULL,
+    cancel_threshold INTEGER NOT NULL,
+    slot INTEGER NOT NULL,
+    PRIMARY KEY (token_lock, timelock_index)
+);
+CREATE TABLE IF NOT EXISTS sync_state (
+    key TEXT PRIMARY KEY,
+    value INTEGER NOT NULL
+);
+";
+
+/// Tables whose rows come from a transaction, with the slot it landed in
+const TABLES: [&str; 8] = ["transactions", "logs", "token_locks", "schedules", "grants", "transfers", "cancellations", "timelocks"];
+
+fn addresses(addresses: &[Pubkey]) -> String {
+    addresses.iter().map(Pubkey::to_string).collect::<Vec<_>>().join(",")
+}
+
+fn mode(mode: CancelMode) -> &'static str {
+    match mode {
+        CancelMode::PayOut => "pay_out",
+        CancelMode::StopVesting => "stop_vesting",
+    }
+}
+
+pub struct Store {
+    connection: Connection,
+}
+
+impl Store {
+    pub fn open<P: AsRef<Path>>(path: P) -> rusqlite::Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> rusqlite::Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> rusqlite::Result<Self> {
+        connection.execute_batch(SCHEMA)?;
+        Ok(Store { connection })
+    }
+
+    /// For queries of the indexed history
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// Adds the rows of a transaction. Returns false if it was indexed before.
+    pub fn apply(&mut self, transaction: &ProgramTransaction) -> rusqlite::Result<bool> {
+        let db = self.connection.transaction()?;
+        let inserted = db.execute(
+            "INSERT OR IGNORE INTO transactions (signature, slot, block_time) VALUES (?1, ?2, ?3)",
+            params![transaction.signature, transaction.slot as i64, transaction.block_time],
+        )?;
+        if inserted == 0 {
+            return Ok(false);
+        }
+        for instruction in &transaction.instructions {
+            let token_lock = instruction.token_lock().map(Pubkey::to_string);
+            for (position, message) in instruction.logs.iter().enumerate() {
+                db.execute(
+                    "INSERT INTO logs (signature, slot, instruction, position, token_lock, event, message) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    params![
+                        transaction.signature,
+                        transaction.slot as i64,
+                        instruction.index as i64,
+                        position as i64,
+                        token_lock,
+                        Event::parse(message).map(|event| event.name()),
+                        message,
+                    ],
+                )?;
+            }
+            if let Some(token_lock) = token_lock {
+                apply_instruction(&db, transaction, instruction, &token_lock)?;
+            }
+        }
+        db.commit()?;
+        Ok(true)
+    }
+
+    /// Deletes everything indexed from transactions at `slot` or later and returns the number of
+    /// transactions removed. Their timelock snapshots go as well, the next sync takes new ones.
+    pub fn rollback(&mut self, slot: u64) -> rusqlite::Result<usize> {
+        let db = self.connection.transaction()?;
+        let removed = db.query_row("SELECT COUNT(*) FROM transactions WHERE slot >= ?1", params![slot as i64], |row| row.get::<_, i64>(0))?;
+        for table in TABLES.iter() {
+            db.execute(&format!("DELETE FROM {} WHERE slot >= ?1", table), params![slot as i64])?;
+        }
+        let finalized = get_state(&db, "finalized_slot")?;
+        if finalized.map_or(false, |finalized| finalized >= slot) {
+            set_state(&db, "finalized_slot", slot.saturating_sub(1))?;
+        }
+        db.commit()?;
+        Ok(removed as usize)
+    }
+
+    /// The slot up to which the indexed transactions are finalized and can't be rolled back
+    pub fn finalized_slot(&self) -> rusqlite::Result<Option<u64>> {
+        get_state(&self.connection, "finalized_slot")
+    }
+
+    pub fn set_finalized_slot(&self, slot: u64) -> rusqlite::Result<()> {
+        set_state(&self.connection, "finalized_slot", slot)
+    }
+
+    /// Signatures and slots of the indexed transactions that are not finalized yet, oldest first
+    pub fn unfinalized(&self) -> rusqlite::Result<Vec<(String, u64)>> {
+        let finalized = self.finalized_slot()?.map_or(-1, |slot| slot as i64);
+        let mut statement = self.connection.prepare("SELECT signature, slot FROM transactions WHERE slot > ?1 ORDER BY slot, rowid")?;
+        let rows = statement.query_map(params![finalized], |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as u64)))?;
+        rows.collect()
+    }
+
+    /// The signature of the newest indexed transaction, history is fetched from there on
+    pub fn last_signature(&self) -> rusqlite::Result<Option<String>> {
+        self.connection
+            .query_row("SELECT signature FROM transactions ORDER BY slot DESC, rowid DESC LIMIT 1", params![], |row| row.get(0))
+            .optional()
+    }
+
+    /// Addresses of the indexed token lock accounts
+    pub fn token_locks(&self) -> rusqlite::Result<Vec<String>> {
+        let mut statement = self.connection.prepare("SELECT address FROM token_locks ORDER BY slot, rowid")?;
+        let rows = statement.query_map(params![], |row| row.get(0))?;
+        rows.collect()
+    }
+
+    /// Replaces the timelocks of a token lock account with their state read at `slot`
+    pub fn replace_timelocks(&mut self, token_lock: &Pubkey, slot: u64, timelocks: &[(usize, Timelock)]) -> rusqlite::Result<()> {
+        let db = self.connection.transaction()?;
+        db.execute("DELETE FROM timelocks WHERE token_lock = ?1", params![token_lock.to_string()])?;
+        for (idx, timelock) in timelocks {
+            db.execute(
+                "INSERT INTO timelocks (token_lock, timelock_index, recipient, schedule_id, commencement_timestamp, total_amount,
+                    tokens_transferred, vesting_stopped_at, cancelable_by, cancel_threshold, slot)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                params![
+                    token_lock.to_string(),
+                    *idx as i64,
+                    timelock.recipient.to_string(),
+                    timelock.schedule_id,
+                    timelock.commencement_timestamp,
+                    timelock.total_amount,
+                    timelock.tokens_transferred,
+                    timelock.vesting_stopped_at,
+                    addresses(timelock.cancelable_by()),
+                    timelock.cancel_threshold,
+                    slot as i64,
+                ],
+            )?;
+        }
+        db.commit()
+    }
+}
+
+fn get_state(connection: &Connection, key: &str) -> rusqlite::Result<Option<u64>> {
+    connection
+        .query_row("SELECT value FROM sync_state WHERE key = ?1", params![key], |row| row.get::<_, i64>(0))
+        .optional()
+        .map(|value| value.map(|value| value as u64))
+}
+
+fn set_state(connection: &Connection, key: &str, value: u64) -> rusqlite::Result<()> {
+    connection.execute("INSERT OR REPLACE INTO sync_state (key, value) VALUES (?1, ?2)", params![key, value as i64])?;
+    Ok(())
+}
+
+/// Adds the history rows of one instruction, taking what the instruction data leaves out from its events
+fn apply_instruction(db: &DbTransaction, transaction: &ProgramTransaction, instruction: &ProgramInstruction, token_lock: &str) -> rusqlite::Result<()> {
+    let slot = transaction.slot as i64;
+    let signature = &transaction.signature;
+    let index = instruction.index as i64;
+    let account = |idx: usize| instruction.accounts.get(idx).map(Pubkey::to_string);
+    let transfer = |kind: &str, from: Option<String>, to: Option<String>, timelock_index: Option<u32>, other_timelock_index: Option<u32>, amount: Option<u64>| {
+        db.execute(
+            "INSERT INTO transfers (token_lock, kind, from_address, to_address, timelock_index, other_timelock_index, amount, slot, signature, instruction)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![token_lock, kind, from, to, timelock_index, other_timelock_index, amount.map(|amount| amount as i64), slot, signature, index],
+        )
+    };
+    let cancellation = |kind: &str, canceler: Option<String>, target: Option<String>, timelock_index: Option<u32>, reclaim_to: Option<String>, cancel_mode: Option<CancelMode>| {
+        db.execute(
+            "INSERT INTO cancellations (token_lock, kind, canceler, target, timelock_index, reclaim_to, mode, slot, signature, instruction)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![token_lock, kind, canceler, target, timelock_index, reclaim_to, cancel_mode.map(mode), slot, signature, index],
+        )
+    };
+
+    match instruction.instruction.as_ref() {
+        Some(TokenLockInstruction::InitializeTokenLock { nonce, max_release_delay, min_timelock_amount, treasury_authority, .. }) => {
+            db.execute(
+                "INSERT OR REPLACE INTO token_locks (address, mint, nonce, max_release_delay, min_timelock_amount, treasury_authority, slot, signature)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![token_lock, account(2), nonce, max_release_delay, min_timelock_amount, treasury_authority.map(|authority| authority.to_string()), slot, signature],
+            )?;
+        }
+        Some(TokenLockInstruction::CreateReleaseSchedule(release_count, delay, initial_bips, period)) => {
+            let schedule_id = instruction.find_event(|event| match event {
+                Event::ReleaseScheduleCreated { schedule_id } => Some(*schedule_id),
+                _ => None,
+            });
+            db.execute(
+                "INSERT INTO schedules (token_lock, schedule_id, release_count, delay_until_first_release_in_seconds, initial_release_portion_in_bips,
+                    period_between_releases_in_seconds, slot, signature, instruction)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![token_lock, schedule_id, release_count, delay, initial_bips, period, slot, signature, index],
+            )?;
+        }
+        Some(TokenLockInstruction::FundReleaseSchedule { to, amount, commencement_timestamp, schedule_id, cancelable_by, cancel_threshold, .. }) => {
+            let received = instruction.find_event(|event| match event {
+                Event::ScheduleFunded { amount, .. } => Some(*amount),
+                _ => None,
+            });
+            let timelock_index = instruction.find_event(|event| match event {
+                Event::TimelockCommencing { timelock_index, .. } => Some(*timelock_index),
+                _ => None,
+            });
+            db.execute(
+                "INSERT INTO grants (token_lock, timelock_index, recipient, funder, schedule_id, commencement_timestamp, amount, received,
+                    cancelable_by, cancel_threshold, slot, signature, instruction)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+                params![
+                    token_lock, timelock_index, to.to_string(), account(1), schedule_id, commencement_timestamp, amount, received,
+                    addresses(cancelable_by), cancel_threshold, slot, signature, index,
+                ],
+            )?;
+        }
+        Some(TokenLockInstruction::TransferTimelock { to, value, timelock_index }) => {
+            transfer("transfer", account(1), Some(to.to_string()), Some(*timelock_index), None, Some(*value as u64))?;
+        }
+        Some(TokenLockInstruction::TransferTimelockOwnership { timelock_index, new_owner }) => {
+            transfer("ownership", account(1), Some(new_owner.to_string()), Some(*timelock_index), None, None)?;
+            // the open cancel proposal of the timelock is closed with the move
+            let closed = instruction.find_event(|event| match event {
+                Event::CancelProposalClosed { proposer, target, .. } => Some((proposer.to_string(), target.to_string())),
+                _ => None,
+            });
+            if let Some((proposer, target)) = closed {
+                cancellation("close", Some(proposer), Some(target), Some(*timelock_index), None, None)?;
+            }
+        }
+        Some(TokenLockInstruction::SplitTimelock { timelock_index, amount, to }) => {
+            transfer("split", account(1), Some(to.to_string()), Some(*timelock_index), None, Some(*amount as u64))?;
+        }
+        Some(TokenLockInstruction::MergeTimelocks { timelock_index, other_timelock_index }) => {
+            transfer("merge", account(1), account(1), Some(*timelock_index), Some(*other_timelock_index), None)?;
+        }
+        Some(TokenLockInstruction::Burn { amount, .. }) => {
+            transfer("burn", account(1), None, None, None, Some(*amount as u64))?;
+        }
+        Some(TokenLockInstruction::CancelTimelock { target, timelock_index, reclaim_token_to, mode }) => {
+            cancellation("cancel", account(1), Some(target.to_string()), Some(*timelock_index), Some(reclaim_token_to.to_string()), Some(*mode))?;
+        }
+        Some(TokenLockInstruction::ProposeCancel { target, timelock_index, reclaim_token_to, mode }) => {
+            cancellation("propose", account(1), Some(target.to_string()), Some(*timelock_index), Some(reclaim_token_to.to_string()), Some(*mode))?;
+        }
+        Some(TokenLockInstruction::ApproveCancel) => {
+            let approved = instruction.find_event(|event| match event {
+                Event::CancelApproved { target, timelock_index, .. } => Some((target.to_string(), *timelock_index)),
+                _ => None,
+            });
+            cancellation("approve", account(1), approved.as_ref().map(|(target, _)| target.clone()), approved.map(|(_, idx)| idx), None, None)?;
+        }
+        Some(TokenLockInstruction::CloseCancelProposal) => {
+            let closed = instruction.find_event(|event| match event {
+                Event::CancelProposalClosed { target, timelock_index, .. } => Some((target.to_string(), *timelock_index)),
+                _ => None,
+            });
+            cancellation("close", account(2), closed.as_ref().map(|(target, _)| target.clone()), closed.map(|(_, idx)| idx), None, None)?;
+        }
+        Some(TokenLockInstruction::ExecuteCancel) => {
+            // the reclaim address and mode are in the closed proposal account, see its `propose` row
+            let canceled = instruction.find_event(|event| match event {
+                Event::TimelockCanceled { canceler, target, timelock_index } => Some((canceler.to_string(), target.to_string(), *timelock_index)),
+                _ => None,
+            });
+            cancellation(
+                "execute",
+                canceled.as_ref().map(|(canceler, _, _)| canceler.clone()),
+                canceled.as_ref().map(|(_, target, _)| target.clone()),
+                canceled.map(|(_, _, idx)| idx),
+                None,
+                None,
+            )?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn instruction(index: usize, accounts: Vec<Pubkey>, instruction: TokenLockInstruction, logs: Vec<String>) -> ProgramInstruction {
+        ProgramInstruction {
+            index,
+            accounts,
+            instruction: Some(instruction),
+            events: logs.iter().filter_map(|message| Event::parse(message)).collect(),
+            logs,
+        }
+    }
+
+    fn count(store: &Store, table: &str) -> i64 {
+        store.connection().query_row(&format!("SELECT COUNT(*) FROM {}", table), params![], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn history_is_indexed_and_rolled_back() {
+        let mut store = Store::open_in_memory().unwrap();
+        let token = Pubkey::new_unique();
+        let funder = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+
+        let fund = ProgramTransaction {
+            signature: "fund".to_string(),
+            slot: 10,
+            block_time: Some(1000),
+            instructions: vec![
+                instruction(0, vec![token], TokenLockInstruction::CreateReleaseSchedule(4, 0, 2500, 100), vec!["ReleaseScheduleCreated 0".to_string()]),
+                instruction(
+                    1,
+                    vec![token, funder],
+                    TokenLockInstruction::FundReleaseSchedule {
+                        to: recipient,
+                        amount: 1000,
+                        commencement_timestamp: 900,
+                        schedule_id: 0,
+                        cancelable_by: vec![funder],
+                        cancel_threshold: 1,
+                        timelock_index: 0,
+                    },
+                    vec![format!("ScheduleFunded {} 0 990", recipient), "timelock 0 commencing 900".to_string()],
+                ),
+            ],
+        };
+        let cancel = ProgramTransaction {
+            signature: "cancel".to_string(),
+            slot: 12,
+            block_time: Some(1010),
+            instructions: vec![instruction(
+                0,
+                vec![token, funder],
+                TokenLockInstruction::CancelTimelock { target: recipient, timelock_index: 0, reclaim_token_to: funder, mode: CancelMode::PayOut },
+                vec![format!("TimelockCanceled {} {} 0", funder, recipient)],
+            )],
+        };
+        assert!(store.apply(&fund).unwrap());
+        assert!(store.apply(&cancel).unwrap());
+        assert!(!store.apply(&cancel).unwrap());
+
+        let grant: (Option<u32>, u32, Option<u32>, String) = store.connection()
+            .query_row("SELECT timelock_index, amount, received, cancelable_by FROM grants", params![], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
+            .unwrap();
+        assert_eq!(grant, (Some(0), 1000, Some(990), funder.to_string()));
+        let schedule_id: Option<u32> = store.connection().query_row("SELECT schedule_id FROM schedules", params![], |row| row.get(0)).unwrap();
+        assert_eq!(schedule_id, Some(0));
+        assert_eq!(count(&store, "cancellations"), 1);
+        assert_eq!(count(&store, "logs"), 4);
+        assert_eq!(store.last_signature().unwrap(), Some("cancel".to_string()));
+
+        store.set_finalized_slot(10).unwrap();
+        assert_eq!(store.unfinalized().unwrap(), vec![("cancel".to_string(), 12)]);
+
+        // the cancellation was on a fork that got dropped
+        assert_eq!(store.rollback(11).unwrap(), 1);
+        assert_eq!(count(&store, "cancellations"), 0);
+        assert_eq!(count(&store, "grants"), 1);
+        assert_eq!(store.last_signature().unwrap(), Some("fund".to_string()));
+        assert_eq!(store.finalized_slot().unwrap(), Some(10));
+        assert!(store.apply(&cancel).unwrap());
+    }
+
+    #[test]
+    fn timelock_snapshots_are_replaced() {
+        let mut store = Store::open_in_memory().unwrap();
+        let token = Pubkey::new_unique();
+        let mut timelock = Timelock::new(0, 100, 50);
+        timelock.recipient = Pubkey::new_unique();
+        store.replace_timelocks(&token, 5, &[(0, timelock), (1, timelock)]).unwrap();
+        store.replace_timelocks(&token, 6, &[(1, timelock)]).unwrap();
+        let index: i64 = store.connection().query_row("SELECT timelock_index FROM timelocks", params![], |row| row.get(0)).unwrap();
+        assert_eq!(index, 1);
+    }
+}
@@ -0,0 +1,104 @@
+//! The token lock instructions of a transaction, decoded together with the events they logged
+use borsh::BorshDeserialize;
+use solana_sdk::{message::Message, pubkey::Pubkey};
+use tokenlock::instruction::TokenLockInstruction;
+use crate::events::{instruction_logs, Event};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgramInstruction {
+    /// Position among the top-level instructions of the transaction
+    pub index: usize,
+    pub accounts: Vec<Pubkey>,
+    /// `None` if the data doesn't decode, the program rejects such instructions
+    pub instruction: Option<TokenLockInstruction>,
+    /// The messages the program logged running the instruction
+    pub logs: Vec<String>,
+    /// The events among `logs`
+    pub events: Vec<Event>,
+}
+
+impl ProgramInstruction {
+    /// The token lock account, the first account of every instruction but `InitializeToken`
+    pub fn token_lock(&self) -> Option<&Pubkey> {
+        match self.instruction {
+            Some(TokenLockInstruction::InitializeToken { .. }) | Some(TokenLockInstruction::Greeting) | None => None,
+            Some(_) => self.accounts.first(),
+        }
+    }
+
+    /// The first event `select` picks
+    pub fn find_event<T>(&self, select: impl Fn(&Event) -> Option<T>) -> Option<T> {
+        self.events.iter().find_map(select)
+    }
+}
+
+/// A successful transaction of the program
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgramTransaction {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub instructions: Vec<ProgramInstruction>,
+}
+
+/// Picks the top-level instructions of `program_id` out of a transaction message and matches
+/// them with the events in the transaction's log. Instructions the program runs through
+/// cross-program invocations are not picked up.
+pub fn decode_transaction(program_id: &Pubkey, signature: String, slot: u64, block_time: Option<i64>, message: &Message, logs: &[String]) -> ProgramTransaction {
+    let logs = instruction_logs(logs, program_id);
+    let instructions = message.instructions.iter().enumerate()
+        .filter(|(_, instruction)| message.account_keys.get(instruction.program_id_index as usize) == Some(program_id))
+        .map(|(index, instruction)| {
+            let logs = logs.get(index).cloned().unwrap_or_default();
+            ProgramInstruction {
+                index,
+                accounts: instruction.accounts.iter().filter_map(|&account| message.account_keys.get(account as usize).copied()).collect(),
+                instruction: TokenLockInstruction::try_from_slice(&instruction.data).ok(),
+                events: logs.iter().filter_map(|message| Event::parse(message)).collect(),
+                logs,
+            }
+        })
+        .collect();
+    ProgramTransaction { signature, slot, block_time, instructions }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::{instruction::Instruction, system_instruction};
+
+    #[test]
+    fn decode_program_instructions() {
+        let program_id = tokenlock::id();
+        let payer = Pubkey::new_unique();
+        let token = Pubkey::new_unique();
+        let message = Message::new(
+            &[
+                system_instruction::transfer(&payer, &token, 1),
+                TokenLockInstruction::create_release_schedule(&token, &payer, 4, 0, 2500, 100),
+                Instruction::new_with_bytes(program_id, &[200], vec![]),
+            ],
+            Some(&payer),
+        );
+        let logs: Vec<String> = vec![
+            "Program 11111111111111111111111111111111 invoke [1]".to_string(),
+            "Program 11111111111111111111111111111111 success".to_string(),
+            format!("Program {} invoke [1]", program_id),
+            "Program log: ReleaseScheduleCreated 3".to_string(),
+            format!("Program {} success", program_id),
+            format!("Program {} invoke [1]", program_id),
+            format!("Program {} failed: invalid instruction data", program_id),
+        ];
+        let transaction = decode_transaction(&program_id, "sig".to_string(), 10, None, &message, &logs);
+        assert_eq!(transaction.instructions.len(), 2);
+
+        let create = &transaction.instructions[0];
+        assert_eq!(create.index, 1);
+        assert_eq!(create.instruction, Some(TokenLockInstruction::CreateReleaseSchedule(4, 0, 2500, 100)));
+        assert_eq!(create.token_lock(), Some(&token));
+        assert_eq!(create.events, vec![Event::ReleaseScheduleCreated { schedule_id: 3 }]);
+
+        assert_eq!(transaction.instructions[1].instruction, None);
+        assert_eq!(transaction.instructions[1].token_lock(), None);
+    }
+}
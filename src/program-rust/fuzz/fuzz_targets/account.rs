@@ -0,0 +1,31 @@
+//! Loads arbitrary account data as each kind of account the program reads. Malformed data has to be
+//! rejected with an error, never a panic, and what loads has to stay within its bounds.
+#![no_main]
+use borsh::BorshDeserialize;
+use libfuzzer_sys::fuzz_target;
+use tokenlock::solana_program::pubkey::Pubkey;
+use tokenlock::{
+    migration::{stored_version, v0},
+    types::{CancelProposal, Timelock, TokenLockAccount, TokenMetadata},
+};
+
+fuzz_target!(|data: &[u8]| {
+    let mut data = data.to_vec();
+    if stored_version(&data) == 0 {
+        if let Ok(account) = v0::TokenLockAccount::unpack(&data) {
+            let mut migrated = vec![0; account.migrated_space()];
+            let mut lockup = TokenLockAccount::init(&mut migrated, &Pubkey::default(), &Pubkey::default(), 0, 0, 0, None).unwrap();
+            let _ = (account.migrate(&mut lockup), account.outstanding());
+        }
+    } else if let Ok(account) = TokenLockAccount::load_mut(&mut data) {
+        let _ = (account.is_native(), account.treasury_authority(), account.original_funder(), account.surplus_claim());
+        assert!(account.release_schedules().len() <= TokenLockAccount::MAX_RELEASE_SCHEDULES);
+        assert!(account.timelocks().len() <= account.timelock_capacity());
+        for (idx, timelock) in account.timelocks().iter().enumerate() {
+            assert!(account.get_timelock(&timelock.recipient, idx).is_some());
+            assert!(timelock.cancelable_by().len() <= Timelock::MAX_CANCELABLE_BY);
+        }
+    }
+    let _ = CancelProposal::try_from_slice(&data);
+    let _ = TokenMetadata::try_from_slice(&data);
+});
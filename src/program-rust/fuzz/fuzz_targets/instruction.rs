@@ -0,0 +1,12 @@
+//! Decodes arbitrary instruction data like the processor does. Malformed data has to give an error,
+//! never a panic, and whatever decodes has to be the only encoding of its instruction.
+#![no_main]
+use borsh::{BorshDeserialize, BorshSerialize};
+use libfuzzer_sys::fuzz_target;
+use tokenlock::instruction::TokenLockInstruction;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(instruction) = TokenLockInstruction::try_from_slice(data) {
+        assert_eq!(instruction.try_to_vec().unwrap(), data);
+    }
+});
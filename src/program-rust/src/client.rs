@@ -0,0 +1,244 @@
+//! Typed reads of lockup state over RPC, built with the `client` feature
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind, Result as ClientResult},
+    rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType},
+};
+use solana_program::{clock::Clock, pubkey::Pubkey, sysvar};
+use solana_sdk::account::{from_account, Account};
+use crate::{
+    processor::Processor,
+    types::{ReleaseSchedule, SurplusClaim, Timelock, TokenLockAccount},
+};
+
+/// Timelocks with their indices in the token lock account
+pub type IndexedTimelocks = Vec<(usize, Timelock)>;
+
+/// The settings of a lockup, the header of its token lock account
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenLockConfig {
+    pub mint: Pubkey,
+    pub nonce: u8,
+    pub version: u8,
+    pub is_native: bool,
+    pub max_release_delay: u32,
+    pub min_timelock_amount: u32,
+    pub treasury_authority: Option<Pubkey>,
+    pub original_funder: Option<Pubkey>,
+    pub surplus_claim: Option<SurplusClaim>,
+    pub release_schedule_count: usize,
+    pub timelock_count: usize,
+    /// Number of timelocks the account has room for, it can't be funded beyond that
+    pub timelock_capacity: usize,
+}
+
+impl TokenLockConfig {
+    fn of(account: &TokenLockAccount) -> Self {
+        TokenLockConfig {
+            mint: account.mint,
+            nonce: account.nonce,
+            version: account.version,
+            is_native: account.is_native(),
+            max_release_delay: account.max_release_delay,
+            min_timelock_amount: account.min_timelock_amount,
+            treasury_authority: account.treasury_authority(),
+            original_funder: account.original_funder(),
+            surplus_claim: account.surplus_claim(),
+            release_schedule_count: account.release_schedules().len(),
+            timelock_count: account.timelocks().len(),
+            timelock_capacity: account.timelock_capacity(),
+        }
+    }
+}
+
+/// The balance of a recipient across its timelocks at a point in time
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Balance {
+    /// Unix timestamp the balance was calculated for
+    pub timestamp: u32,
+    pub unlocked: u64,
+    pub locked: u64,
+}
+
+impl Balance {
+    pub fn total(&self) -> u64 {
+        self.unlocked + self.locked
+    }
+}
+
+/// Matches token lock accounts in the current layout
+pub fn discriminator_filter() -> RpcFilterType {
+    memcmp(0, &TokenLockAccount::DISCRIMINATOR)
+}
+
+/// Matches token lock accounts of a mint
+pub fn mint_filter(mint: &Pubkey) -> RpcFilterType {
+    memcmp(TokenLockAccount::DISCRIMINATOR.len(), mint.as_ref())
+}
+
+fn memcmp(offset: usize, bytes: &[u8]) -> RpcFilterType {
+    RpcFilterType::Memcmp(Memcmp {
+        offset,
+        bytes: MemcmpEncodedBytes::Binary(bs58::encode(bytes).into_string()),
+        encoding: None,
+    })
+}
+
+fn account_error(address: &Pubkey, reason: impl std::fmt::Display) -> ClientError {
+    ClientErrorKind::Custom(format!("token lock account {}: {}", address, reason)).into()
+}
+
+/// Reads token lock accounts over RPC and decodes them, so callers never handle the raw layout
+pub struct TokenLockClient {
+    rpc_client: RpcClient,
+    program_id: Pubkey,
+}
+
+impl TokenLockClient {
+    pub fn new(rpc_client: RpcClient) -> Self {
+        Self::with_program_id(rpc_client, crate::id())
+    }
+
+    /// For a deployment of the program at another address
+    pub fn with_program_id(rpc_client: RpcClient, program_id: Pubkey) -> Self {
+        TokenLockClient { rpc_client, program_id }
+    }
+
+    pub fn rpc_client(&self) -> &RpcClient {
+        &self.rpc_client
+    }
+
+    pub fn program_id(&self) -> &Pubkey {
+        &self.program_id
+    }
+
+    /// The data of a token lock account owned by the program
+    pub fn get_account_data(&self, token: &Pubkey) -> ClientResult<Vec<u8>> {
+        let account = self.rpc_client.get_account(token)?;
+        if account.owner != self.program_id {
+            return Err(account_error(token, format!("owned by {}", account.owner)));
+        }
+        Ok(account.data)
+    }
+
+    /// Loads a token lock account and passes the decoded view to `read`
+    pub fn read<T>(&self, token: &Pubkey, read: impl FnOnce(&TokenLockAccount) -> T) -> ClientResult<T> {
+        let mut data = self.get_account_data(token)?;
+        let account = TokenLockAccount::load_mut(&mut data).map_err(|err| account_error(token, err))?;
+        Ok(read(&account))
+    }
+
+    pub fn get_config(&self, token: &Pubkey) -> ClientResult<TokenLockConfig> {
+        self.read(token, TokenLockConfig::of)
+    }
+
+    /// The release schedules, indexed by their schedule id
+    pub fn get_schedules(&self, token: &Pubkey) -> ClientResult<Vec<ReleaseSchedule>> {
+        self.read(token, |account| account.release_schedules().to_vec())
+    }
+
+    /// The timelocks with their indices, all of them or those of one recipient
+    pub fn get_timelocks(&self, token: &Pubkey, recipient: Option<&Pubkey>) -> ClientResult<IndexedTimelocks> {
+        self.read(token, |account| {
+            account.timelocks().iter().enumerate()
+                .filter(|(_, timelock)| recipient.map_or(true, |recipient| timelock.recipient == *recipient))
+                .map(|(idx, timelock)| (idx, *timelock))
+                .collect()
+        })
+    }
+
+    /// The unix timestamp of the cluster clock
+    pub fn get_timestamp(&self) -> ClientResult<u32> {
+        let account = self.rpc_client.get_account(&sysvar::clock::id())?;
+        let clock: Clock = from_account(&account).ok_or_else(|| ClientError::from(ClientErrorKind::Custom("invalid clock sysvar".to_string())))?;
+        Ok(clock.unix_timestamp as u32)
+    }
+
+    /// The balance of a recipient as the program calculates it at `at`, or at the cluster's time
+    pub fn balance_of(&self, token: &Pubkey, recipient: &Pubkey, at: Option<u32>) -> ClientResult<Balance> {
+        let timestamp = match at {
+            Some(timestamp) => timestamp,
+            None => self.get_timestamp()?,
+        };
+        let mut data = self.get_account_data(token)?;
+        let processor = Processor {
+            account_key: token,
+            account_info: TokenLockAccount::load_mut(&mut data).map_err(|err| account_error(token, err))?,
+            modified: false,
+            timestamp,
+        };
+        Ok(Balance {
+            timestamp,
+            unlocked: processor.unlocked_balance_of(recipient),
+            locked: processor.locked_balance_of(recipient),
+        })
+    }
+
+    /// Accounts of the program with the token lock discriminator that match `filters`
+    pub fn get_token_locks_with_filters(&self, filters: Vec<RpcFilterType>) -> ClientResult<Vec<(Pubkey, Account)>> {
+        let mut all_filters = vec![discriminator_filter()];
+        all_filters.extend(filters);
+        self.rpc_client.get_program_accounts_with_config(
+            &self.program_id,
+            RpcProgramAccountsConfig {
+                filters: Some(all_filters),
+                account_config: RpcAccountInfoConfig {
+                    encoding: Some(UiAccountEncoding::Base64),
+                    ..RpcAccountInfoConfig::default()
+                },
+                with_context: None,
+            },
+        )
+    }
+
+    /// Every lockup of the program, or those of one mint, skipping accounts that don't decode
+    pub fn get_token_locks(&self, mint: Option<&Pubkey>) -> ClientResult<Vec<(Pubkey, TokenLockConfig)>> {
+        let filters = mint.map(mint_filter).into_iter().collect();
+        Ok(self.get_token_locks_with_filters(filters)?.into_iter()
+            .filter_map(|(address, mut account)| {
+                TokenLockAccount::load_mut(&mut account.data).ok().map(|token_lock| (address, TokenLockConfig::of(&token_lock)))
+            })
+            .collect())
+    }
+
+    /// The lockups `recipient` has timelocks in, with those timelocks. A recipient's timelocks can sit at
+    /// any index and there is no index of recipients, so this fetches and decodes every token lock account
+    /// of the program. Services asking often should keep their own index, like `src/indexer` does.
+    pub fn get_token_locks_of(&self, recipient: &Pubkey) -> ClientResult<Vec<(Pubkey, IndexedTimelocks)>> {
+        Ok(self.get_token_locks_with_filters(vec![])?.into_iter()
+            .filter_map(|(address, mut account)| {
+                let token_lock = TokenLockAccount::load_mut(&mut account.data).ok()?;
+                let timelocks: IndexedTimelocks = token_lock.timelocks_of(recipient).map(|(idx, timelock)| (idx, *timelock)).collect();
+                if timelocks.is_empty() { None } else { Some((address, timelocks)) }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memcmp_matches(filter: &RpcFilterType, data: &[u8]) -> bool {
+        match filter {
+            RpcFilterType::Memcmp(Memcmp { offset, bytes: MemcmpEncodedBytes::Binary(bytes), .. }) => {
+                let bytes = bs58::decode(bytes).into_vec().unwrap();
+                data.get(*offset..*offset + bytes.len()) == Some(&bytes[..])
+            }
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn filters_match_the_account_layout() {
+        let mint = Pubkey::new_unique();
+        let mut data = vec![0; TokenLockAccount::space(1)];
+        TokenLockAccount::init(&mut data, &mint, &Pubkey::new_unique(), 0, 1000, 1, None).unwrap();
+
+        assert!(memcmp_matches(&discriminator_filter(), &data));
+        assert!(memcmp_matches(&mint_filter(&mint), &data));
+        assert!(!memcmp_matches(&mint_filter(&Pubkey::new_unique()), &data));
+    }
+}
@@ -1,12 +1,9 @@
 use crate::{processor::Processor};
 
-use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
-    account_info::{next_account_info, AccountInfo},
+    account_info::{AccountInfo},
     entrypoint,
     entrypoint::ProgramResult,
-    msg,
-    program_error::ProgramError,
     pubkey::Pubkey,
 };
 
@@ -14,28 +11,9 @@ use solana_program::{
 entrypoint!(process_instruction);
 // Program entrypoint's implementation
 pub fn process_instruction(
-    program_id: &Pubkey, // Public key of the account the hello world program was loaded into
-    accounts: &[AccountInfo], // The account to say hello to
-    instruction_data: &[u8], // Ignored, all helloworld instructions are hellos
+    program_id: &Pubkey, // Public key of the token lock program
+    accounts: &[AccountInfo], // The token lock account first, then the accounts of the instruction
+    instruction_data: &[u8], // A borsh serialized TokenLockInstruction
 ) -> ProgramResult {
-    msg!("Hello World Rust program entrypoint");
-
-    // Iterating accounts is safer then indexing
-    let accounts_iter = &mut accounts.iter();
-
-    // Get the account to say hello to
-    let account = next_account_info(accounts_iter)?;
-
-    // The account must be owned by the program in order to modify its data
-    if account.owner != program_id {
-        msg!("Greeted account does not have the correct program id");
-        return Err(ProgramError::IncorrectProgramId);
-    }
-
-    if let Err(error) = Processor::process_instruction(program_id, accounts, instruction_data) {
-        // catch the error so we can print it
-        //error.print::<NftError>();
-        return Err(error);
-    }
-    Ok(())    
-}
\ No newline at end of file
+    Processor::process_instruction(program_id, accounts, instruction_data)
+}
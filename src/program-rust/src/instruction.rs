@@ -1,20 +1,309 @@
 //! Instruction types
 
-use borsh::{BorshDeserialize, BorshSerialize};
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
 use solana_program::{
     instruction::{AccountMeta, Instruction},
     pubkey::Pubkey,
+    system_program,
     sysvar,
 };
+use crate::types::{CancelMode, CancelProposal, TokenLockAccount, TokenMetadata};
 
 
+/// Which balance of the signer's timelocks a burn is taken from
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, BorshSchema)]
+pub enum BurnSource {
+    /// Unlocked tokens still held in the vault, counted as transferred
+    Unlocked,
+    /// Locked tokens of timelocks nobody can cancel, shrinking the timelock's total amount
+    Locked,
+}
+
 /// Instruction definition
+///
+/// Timelocks are addressed by their index among all timelocks of the token lock account
+/// together with their recipient, see `TokenLockAccount`, not by their index among the recipient's
+/// timelocks like `timelockOf(who, idx)` of the Solidity contract.
 #[allow(clippy::large_enum_variant)]
 // can consider making it from primitive, read as input header and manually dispatch to borsh if needed (cause transfer as most often operation is empty input)
-#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq)]
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, BorshSchema)]
 pub enum TokenLockInstruction {
     Greeting,
+    /// Adds a release schedule timelocks can be funded with.
+    /// The fields are the release count, the delay until the first release in seconds,
+    /// the initial release portion in bips and the period between releases in seconds.
+    ///
+    /// Accounts expected:
+    ///   0. `[writable]` The token lock account
+    ///   1. `[signer]` The lockup's authority or its treasury authority
     CreateReleaseSchedule(u32, u32, u32, u32),
+    /// Moves a timelock, including its `tokens_transferred` progress, to a new recipient.
+    /// A cancel proposal still open for the timelock is closed, it is keyed to the current owner.
+    ///
+    /// Accounts expected:
+    ///   0. `[writable]` The token lock account
+    ///   1. `[signer]` The current owner of the timelock
+    ///   2. `[writable]` The cancel proposal PDA of the timelock, see `CancelProposal::find_address`, followed by its proposer if it is open
+    ///   3. ..`[signer]` Cancelers of the timelock, one is required if the timelock is cancelable
+    TransferTimelockOwnership {
+        timelock_index: u32,
+        new_owner: Pubkey,
+    },
+    /// Carves `amount` off a timelock into a new timelock for `to` with the same schedule.
+    ///
+    /// Accounts expected:
+    ///   0. `[writable]` The token lock account
+    ///   1. `[signer]` The owner of the timelock
+    ///   2. ..`[signer]` Cancelers of the timelock, one is required if the timelock is cancelable and `to` is not the owner
+    SplitTimelock {
+        timelock_index: u32,
+        amount: u32,
+        to: Pubkey,
+    },
+    /// Merges `other_timelock_index` into `timelock_index`, both held by the signer with identical schedules.
+    ///
+    /// Accounts expected:
+    ///   0. `[writable]` The token lock account
+    ///   1. `[signer]` The owner of both timelocks
+    MergeTimelocks {
+        timelock_index: u32,
+        other_timelock_index: u32,
+    },
+    /// Cancels a timelock, returning its locked tokens to `reclaim_token_to`.
+    ///
+    /// Accounts expected:
+    ///   0. `[writable]` The token lock account
+    ///   1. `[signer]` One of the timelock's cancelers
+    ///   2. `[writable]` The vault PDA, see `TokenLockAccount::find_vault`
+    ///   3. `[]` The mint
+    ///   4. `[]` The token program owning the mint, the system program for native SOL
+    ///   5. `[writable]` The token account of `reclaim_token_to`, or `reclaim_token_to` itself for native SOL
+    ///   6. `[writable]` The token account of `target`, or `target` itself for native SOL
+    CancelTimelock {
+        target: Pubkey,
+        timelock_index: u32,
+        reclaim_token_to: Pubkey,
+        mode: CancelMode,
+    },
+    /// Opens a cancellation proposal for a timelock with a cancel threshold above one.
+    /// The proposer counts as the first approval.
+    ///
+    /// Accounts expected:
+    ///   0. `[]` The token lock account
+    ///   1. `[signer, writable]` One of the timelock's cancelers, pays for the proposal account
+    ///   2. `[writable]` The proposal PDA, see `CancelProposal::find_address`
+    ///   3. `[]` The system program
+    ProposeCancel {
+        target: Pubkey,
+        timelock_index: u32,
+        reclaim_token_to: Pubkey,
+        mode: CancelMode,
+    },
+    /// Adds the signer's approval to a cancellation proposal.
+    ///
+    /// Accounts expected:
+    ///   0. `[]` The token lock account
+    ///   1. `[signer]` One of the timelock's cancelers
+    ///   2. `[writable]` The proposal PDA
+    ApproveCancel,
+    /// Cancels the timelock once the proposal has enough approvals and closes the proposal.
+    ///
+    /// Accounts expected:
+    ///   0. `[writable]` The token lock account
+    ///   1. `[signer]` The executor, anyone once the proposal has enough approvals
+    ///   2. `[writable]` The proposal PDA
+    ///   3. `[writable]` The proposer, receives the proposal account's rent
+    ///   4. `[writable]` The vault PDA
+    ///   5. `[]` The mint
+    ///   6. `[]` The token program owning the mint, the system program for native SOL
+    ///   7. `[writable]` The token account of the proposal's `reclaim_token_to`, or the address itself for native SOL
+    ///   8. `[writable]` The token account of the proposal's `target`, or the address itself for native SOL
+    ExecuteCancel,
+    /// Withdraws a cancellation proposal without canceling the timelock.
+    ///
+    /// Accounts expected:
+    ///   0. `[]` The token lock account
+    ///   1. `[writable]` The proposal PDA
+    ///   2. `[writable]` The proposer, receives the proposal account's rent and signs to close it itself
+    ///   3. ..`[signer]` Current cancelers of the timelock, as many as its cancel threshold, unless the proposer signs or the proposal expired
+    CloseCancelProposal,
+    /// Adds a canceler to a timelock.
+    ///
+    /// Accounts expected:
+    ///   0. `[writable]` The token lock account
+    ///   1. ..`[signer]` Current cancelers of the timelock, as many as its cancel threshold
+    AddCanceler {
+        target: Pubkey,
+        timelock_index: u32,
+        canceler: Pubkey,
+    },
+    /// Removes a canceler from a timelock.
+    ///
+    /// Accounts expected:
+    ///   0. `[writable]` The token lock account
+    ///   1. ..`[signer]` Current cancelers of the timelock, as many as its cancel threshold
+    RemoveCanceler {
+        target: Pubkey,
+        timelock_index: u32,
+        canceler: Pubkey,
+    },
+    /// Gives up the signer's own right to cancel a timelock.
+    ///
+    /// Accounts expected:
+    ///   0. `[writable]` The token lock account
+    ///   1. `[signer]` The canceler renouncing
+    RenounceCancel {
+        target: Pubkey,
+        timelock_index: u32,
+    },
+    /// Logs the cancelers and cancel threshold of a timelock, meant to be simulated.
+    ///
+    /// Accounts expected:
+    ///   0. `[]` The token lock account
+    LogCancelStatus {
+        target: Pubkey,
+        timelock_index: u32,
+    },
+    /// Burns the signer's tokens held in the vault, going through their timelocks in order.
+    ///
+    /// Accounts expected:
+    ///   0. `[writable]` The token lock account
+    ///   1. `[signer]` The recipient of the timelocks
+    ///   2. `[writable]` The vault PDA, see `TokenLockAccount::find_vault`
+    ///   3. `[writable]` The mint
+    ///   4. `[]` The SPL Token or Token-2022 program owning the mint
+    Burn {
+        amount: u32,
+        source: BurnSource,
+    },
+    /// Creates a mint, mints the whole supply to a new treasury token account and removes the
+    /// mint authority, so no tokens can ever be minted or frozen afterwards.
+    ///
+    /// Accounts expected:
+    ///   0. `[signer, writable]` The payer of the new accounts
+    ///   1. `[signer, writable]` The new mint account
+    ///   2. `[signer, writable]` The new treasury token account
+    ///   3. `[]` The owner of the treasury token account
+    ///   4. `[]` The mint authority PDA, see `TokenMetadata::find_mint_authority`
+    ///   5. `[writable]` The metadata PDA, see `TokenMetadata::find_address`
+    ///   6. `[]` The system program
+    ///   7. `[]` The SPL Token program
+    ///   8. `[]` The rent sysvar
+    InitializeToken {
+        decimals: u8,
+        total_supply: u64,
+        metadata: TokenMetadata,
+    },
+    /// Creates a token lock account for a mint and its vault.
+    /// Lockups of native SOL use the native mint and keep the lamports in a program owned vault.
+    /// Token-2022 mints with a transfer hook, a permanent delegate or frozen new accounts are not supported.
+    ///
+    /// Accounts expected:
+    ///   0. `[writable]` The token lock PDA, see `TokenLockAccount::find_address`
+    ///   1. `[signer, writable]` The payer of the new accounts, the authority the token lock address is derived from
+    ///   2. `[]` The mint, `spl_token::native_mint` for native SOL
+    ///   3. `[writable]` The vault PDA, see `TokenLockAccount::find_vault`
+    ///   4. `[]` The system program
+    ///   5. `[]` The SPL Token or Token-2022 program owning the mint, the system program for native SOL
+    ///   6. `[]` The rent sysvar
+    InitializeTokenLock {
+        nonce: u8,
+        max_release_delay: u32,
+        min_timelock_amount: u32,
+        /// Bytes allocated for the token lock account, see `TokenLockAccount::space`, with room for at most
+        /// `TokenLockAccount::MAX_TIMELOCKS` timelocks. The runtime can't resize the account, funding fails
+        /// once it holds as many timelocks as fit.
+        space: u32,
+        /// May sweep surplus tokens out of the vault, without one the original funder has to claim them
+        treasury_authority: Option<Pubkey>,
+    },
+    /// Transfers `amount` from the funder into the vault and funds a timelock for `to` with
+    /// what the vault received, which is less than `amount` if the mint charges a transfer fee.
+    /// Fails unless the new timelock gets `timelock_index`, so the same funding sent again in
+    /// another transaction doesn't fund a second timelock.
+    ///
+    /// Accounts expected:
+    ///   0. `[writable]` The token lock account
+    ///   1. `[signer, writable]` The funder
+    ///   2. `[writable]` The funder's token account, the funder itself for native SOL
+    ///   3. `[writable]` The vault PDA
+    ///   4. `[]` The mint
+    ///   5. `[]` The SPL Token or Token-2022 program owning the mint, the system program for native SOL
+    FundReleaseSchedule {
+        to: Pubkey,
+        amount: u32,
+        commencement_timestamp: u32,
+        schedule_id: u32,
+        cancelable_by: Vec<Pubkey>,
+        cancel_threshold: u8,
+        /// The number of timelocks funded so far
+        timelock_index: u32,
+    },
+    /// Transfers unlocked tokens of one of the signer's timelocks out of the vault to `to`.
+    ///
+    /// Accounts expected:
+    ///   0. `[writable]` The token lock account
+    ///   1. `[signer]` The recipient of the timelock
+    ///   2. `[writable]` The vault PDA
+    ///   3. `[]` The mint
+    ///   4. `[]` The token program owning the mint, the system program for native SOL
+    ///   5. `[writable]` The token account of `to`, or `to` itself for native SOL
+    TransferTimelock {
+        to: Pubkey,
+        value: u32,
+        timelock_index: u32,
+    },
+    /// Compares the vault balance with what the timelocks still owe and fails if the vault is short.
+    /// Anyone can call it.
+    ///
+    /// Accounts expected:
+    ///   0. `[]` The token lock account
+    ///   1. `[]` The vault PDA
+    ///   2. `[]` The token program owning the mint, the system program for native SOL
+    Reconcile,
+    /// Starts the claim of the vault's current surplus by the original funder of a lockup without a
+    /// treasury authority. The surplus can be swept after `TokenLockAccount::SURPLUS_CLAIM_DELAY`.
+    ///
+    /// Accounts expected:
+    ///   0. `[writable]` The token lock account
+    ///   1. `[signer]` The original funder
+    ///   2. `[]` The vault PDA
+    ///   3. `[]` The token program owning the mint, the system program for native SOL
+    ClaimSurplus,
+    /// Transfers tokens the vault holds beyond what the timelocks still owe to the treasury authority,
+    /// or without one to the original funder as far as they claimed it.
+    ///
+    /// Accounts expected:
+    ///   0. `[writable]` The token lock account
+    ///   1. `[signer]` The treasury authority or the original funder
+    ///   2. `[writable]` The vault PDA
+    ///   3. `[]` The mint
+    ///   4. `[]` The token program owning the mint, the system program for native SOL
+    ///   5. `[writable]` The token account of the signer, or the signer itself for native SOL
+    SweepSurplus {
+        amount: u64,
+    },
+    /// Moves a token lock account stored in an older layout into a token lock account the same authority
+    /// initialized, which needs room for its timelocks, see `v0::TokenLockAccount::migrated_space`, and has
+    /// no release schedules or timelocks yet. It takes the old account's settings. The vault receives what
+    /// the timelocks still hold from the authority and the old account is closed.
+    /// Timelocks of version 0 accounts were indexed per recipient and get new indices,
+    /// see `v0::TokenLockAccount::timelocks_in_migration_order`.
+    ///
+    /// Accounts expected:
+    ///   0. `[writable]` The token lock account
+    ///   1. `[signer, writable]` The authority of the token lock account, receives the old account's rent
+    ///   2. `[writable]` The old account, at `Pubkey::create_with_seed(authority, seed, program_id)`
+    ///   3. `[writable]` The authority's token account, or the authority itself for native SOL
+    ///   4. `[writable]` The vault PDA
+    ///   5. `[]` The mint
+    ///   6. `[]` The token program owning the mint, the system program for native SOL
+    Migrate {
+        /// The layout version the old account is stored in
+        from_version: u8,
+        /// The seed the old account's address was created with from the authority
+        seed: String,
+    },
 }
 
 impl TokenLockInstruction {
@@ -24,10 +313,351 @@ impl TokenLockInstruction {
         Instruction::new_with_borsh(crate::id(), &data, accounts)
     }
 
-    pub fn createReleaseSchedule(token :&Pubkey, release_count: u32, delay_until_first_release_in_seconds:u32, initial_release_portion_in_bips:u32, period_between_releases_in_seconds:u32)-> Instruction{
+    pub fn create_release_schedule(token :&Pubkey, authority: &Pubkey, release_count: u32, delay_until_first_release_in_seconds:u32, initial_release_portion_in_bips:u32, period_between_releases_in_seconds:u32)-> Instruction{
         let data = TokenLockInstruction::CreateReleaseSchedule(release_count, delay_until_first_release_in_seconds, initial_release_portion_in_bips, period_between_releases_in_seconds);
         let accounts = vec![
             AccountMeta::new(*token, false),
+            AccountMeta::new_readonly(*authority, true),
+        ];
+        Instruction::new_with_borsh(crate::id(), &data, accounts)
+    }
+
+    /// `proposer` is the proposer of the timelock's open cancel proposal, if there is one.
+    pub fn transfer_timelock_ownership(token: &Pubkey, owner: &Pubkey, proposer: Option<&Pubkey>, cancelers: &[Pubkey], timelock_index: u32, new_owner: &Pubkey) -> Instruction{
+        let (proposal, _) = CancelProposal::find_address(token, owner, timelock_index, &crate::id());
+        let data = TokenLockInstruction::TransferTimelockOwnership {
+            timelock_index,
+            new_owner: *new_owner,
+        };
+        let mut accounts = vec![
+            AccountMeta::new(*token, false),
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new(proposal, false),
+        ];
+        if let Some(proposer) = proposer {
+            accounts.push(AccountMeta::new(*proposer, false));
+        }
+        for canceler in cancelers {
+            accounts.push(AccountMeta::new_readonly(*canceler, true));
+        }
+        Instruction::new_with_borsh(crate::id(), &data, accounts)
+    }
+
+    pub fn split_timelock(token: &Pubkey, owner: &Pubkey, cancelers: &[Pubkey], timelock_index: u32, amount: u32, to: &Pubkey) -> Instruction{
+        let data = TokenLockInstruction::SplitTimelock {
+            timelock_index,
+            amount,
+            to: *to,
+        };
+        let mut accounts = vec![
+            AccountMeta::new(*token, false),
+            AccountMeta::new_readonly(*owner, true),
+        ];
+        for canceler in cancelers {
+            accounts.push(AccountMeta::new_readonly(*canceler, true));
+        }
+        Instruction::new_with_borsh(crate::id(), &data, accounts)
+    }
+
+    pub fn merge_timelocks(token: &Pubkey, owner: &Pubkey, timelock_index: u32, other_timelock_index: u32) -> Instruction{
+        let data = TokenLockInstruction::MergeTimelocks {
+            timelock_index,
+            other_timelock_index,
+        };
+        let accounts = vec![
+            AccountMeta::new(*token, false),
+            AccountMeta::new_readonly(*owner, true),
+        ];
+        Instruction::new_with_borsh(crate::id(), &data, accounts)
+    }
+
+    pub fn cancel_timelock(token: &Pubkey, canceler: &Pubkey, mint: &Pubkey, token_program: &Pubkey, target: &Pubkey, target_destination: &Pubkey, timelock_index: u32, reclaim_token_to: &Pubkey, reclaim_destination: &Pubkey, mode: CancelMode) -> Instruction{
+        let (vault, _) = TokenLockAccount::find_vault(token, &crate::id());
+        let data = TokenLockInstruction::CancelTimelock {
+            target: *target,
+            timelock_index,
+            reclaim_token_to: *reclaim_token_to,
+            mode,
+        };
+        let accounts = vec![
+            AccountMeta::new(*token, false),
+            AccountMeta::new_readonly(*canceler, true),
+            AccountMeta::new(vault, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(*token_program, false),
+            AccountMeta::new(*reclaim_destination, false),
+            AccountMeta::new(*target_destination, false),
+        ];
+        Instruction::new_with_borsh(crate::id(), &data, accounts)
+    }
+
+    pub fn propose_cancel(token: &Pubkey, canceler: &Pubkey, target: &Pubkey, timelock_index: u32, reclaim_token_to: &Pubkey, mode: CancelMode) -> Instruction{
+        let (proposal, _) = CancelProposal::find_address(token, target, timelock_index, &crate::id());
+        let data = TokenLockInstruction::ProposeCancel {
+            target: *target,
+            timelock_index,
+            reclaim_token_to: *reclaim_token_to,
+            mode,
+        };
+        let accounts = vec![
+            AccountMeta::new_readonly(*token, false),
+            AccountMeta::new(*canceler, true),
+            AccountMeta::new(proposal, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ];
+        Instruction::new_with_borsh(crate::id(), &data, accounts)
+    }
+
+    pub fn approve_cancel(token: &Pubkey, canceler: &Pubkey, target: &Pubkey, timelock_index: u32) -> Instruction{
+        let (proposal, _) = CancelProposal::find_address(token, target, timelock_index, &crate::id());
+        let data = TokenLockInstruction::ApproveCancel;
+        let accounts = vec![
+            AccountMeta::new_readonly(*token, false),
+            AccountMeta::new_readonly(*canceler, true),
+            AccountMeta::new(proposal, false),
+        ];
+        Instruction::new_with_borsh(crate::id(), &data, accounts)
+    }
+
+    pub fn execute_cancel(token: &Pubkey, executor: &Pubkey, proposer: &Pubkey, mint: &Pubkey, token_program: &Pubkey, target: &Pubkey, target_destination: &Pubkey, timelock_index: u32, reclaim_destination: &Pubkey) -> Instruction{
+        let (proposal, _) = CancelProposal::find_address(token, target, timelock_index, &crate::id());
+        let (vault, _) = TokenLockAccount::find_vault(token, &crate::id());
+        let data = TokenLockInstruction::ExecuteCancel;
+        let accounts = vec![
+            AccountMeta::new(*token, false),
+            AccountMeta::new_readonly(*executor, true),
+            AccountMeta::new(proposal, false),
+            AccountMeta::new(*proposer, false),
+            AccountMeta::new(vault, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(*token_program, false),
+            AccountMeta::new(*reclaim_destination, false),
+            AccountMeta::new(*target_destination, false),
+        ];
+        Instruction::new_with_borsh(crate::id(), &data, accounts)
+    }
+
+    pub fn close_cancel_proposal(token: &Pubkey, proposer: &Pubkey, signers: &[Pubkey], target: &Pubkey, timelock_index: u32) -> Instruction{
+        let (proposal, _) = CancelProposal::find_address(token, target, timelock_index, &crate::id());
+        let data = TokenLockInstruction::CloseCancelProposal;
+        let mut accounts = vec![
+            AccountMeta::new_readonly(*token, false),
+            AccountMeta::new(proposal, false),
+            AccountMeta::new(*proposer, signers.contains(proposer)),
+        ];
+        for signer in signers.iter().filter(|signer| *signer != proposer) {
+            accounts.push(AccountMeta::new_readonly(*signer, true));
+        }
+        Instruction::new_with_borsh(crate::id(), &data, accounts)
+    }
+
+    pub fn add_canceler(token: &Pubkey, cancelers: &[Pubkey], target: &Pubkey, timelock_index: u32, canceler: &Pubkey) -> Instruction{
+        let data = TokenLockInstruction::AddCanceler {
+            target: *target,
+            timelock_index,
+            canceler: *canceler,
+        };
+        let mut accounts = vec![
+            AccountMeta::new(*token, false),
+        ];
+        for signer in cancelers {
+            accounts.push(AccountMeta::new_readonly(*signer, true));
+        }
+        Instruction::new_with_borsh(crate::id(), &data, accounts)
+    }
+
+    pub fn remove_canceler(token: &Pubkey, cancelers: &[Pubkey], target: &Pubkey, timelock_index: u32, canceler: &Pubkey) -> Instruction{
+        let data = TokenLockInstruction::RemoveCanceler {
+            target: *target,
+            timelock_index,
+            canceler: *canceler,
+        };
+        let mut accounts = vec![
+            AccountMeta::new(*token, false),
+        ];
+        for signer in cancelers {
+            accounts.push(AccountMeta::new_readonly(*signer, true));
+        }
+        Instruction::new_with_borsh(crate::id(), &data, accounts)
+    }
+
+    pub fn renounce_cancel(token: &Pubkey, canceler: &Pubkey, target: &Pubkey, timelock_index: u32) -> Instruction{
+        let data = TokenLockInstruction::RenounceCancel {
+            target: *target,
+            timelock_index,
+        };
+        let accounts = vec![
+            AccountMeta::new(*token, false),
+            AccountMeta::new_readonly(*canceler, true),
+        ];
+        Instruction::new_with_borsh(crate::id(), &data, accounts)
+    }
+
+    pub fn log_cancel_status(token: &Pubkey, target: &Pubkey, timelock_index: u32) -> Instruction{
+        let data = TokenLockInstruction::LogCancelStatus {
+            target: *target,
+            timelock_index,
+        };
+        let accounts = vec![
+            AccountMeta::new_readonly(*token, false),
+        ];
+        Instruction::new_with_borsh(crate::id(), &data, accounts)
+    }
+
+    pub fn burn(token: &Pubkey, recipient: &Pubkey, mint: &Pubkey, token_program: &Pubkey, amount: u32, source: BurnSource) -> Instruction{
+        let (vault, _) = TokenLockAccount::find_vault(token, &crate::id());
+        let data = TokenLockInstruction::Burn {
+            amount,
+            source,
+        };
+        let accounts = vec![
+            AccountMeta::new(*token, false),
+            AccountMeta::new_readonly(*recipient, true),
+            AccountMeta::new(vault, false),
+            AccountMeta::new(*mint, false),
+            AccountMeta::new_readonly(*token_program, false),
+        ];
+        Instruction::new_with_borsh(crate::id(), &data, accounts)
+    }
+
+    pub fn initialize_token(payer: &Pubkey, mint: &Pubkey, treasury: &Pubkey, treasury_owner: &Pubkey, decimals: u8, total_supply: u64, metadata: TokenMetadata) -> Instruction{
+        let (mint_authority, _) = TokenMetadata::find_mint_authority(mint, &crate::id());
+        let (metadata_key, _) = TokenMetadata::find_address(mint, &crate::id());
+        let data = TokenLockInstruction::InitializeToken {
+            decimals,
+            total_supply,
+            metadata,
+        };
+        let accounts = vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(*mint, true),
+            AccountMeta::new(*treasury, true),
+            AccountMeta::new_readonly(*treasury_owner, false),
+            AccountMeta::new_readonly(mint_authority, false),
+            AccountMeta::new(metadata_key, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ];
+        Instruction::new_with_borsh(crate::id(), &data, accounts)
+    }
+
+    pub fn initialize_token_lock(payer: &Pubkey, mint: &Pubkey, token_program: &Pubkey, nonce: u8, max_release_delay: u32, min_timelock_amount: u32, space: u32, treasury_authority: Option<Pubkey>) -> Instruction{
+        let (token, _) = TokenLockAccount::find_address(mint, payer, nonce, &crate::id());
+        let (vault, _) = TokenLockAccount::find_vault(&token, &crate::id());
+        let data = TokenLockInstruction::InitializeTokenLock {
+            nonce,
+            max_release_delay,
+            min_timelock_amount,
+            space,
+            treasury_authority,
+        };
+        let accounts = vec![
+            AccountMeta::new(token, false),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new(vault, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(*token_program, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ];
+        Instruction::new_with_borsh(crate::id(), &data, accounts)
+    }
+
+    pub fn fund_release_schedule(token: &Pubkey, funder: &Pubkey, source: &Pubkey, mint: &Pubkey, token_program: &Pubkey, to: &Pubkey, amount: u32, commencement_timestamp: u32, schedule_id: u32, cancelable_by: &[Pubkey], cancel_threshold: u8, timelock_index: u32) -> Instruction{
+        let (vault, _) = TokenLockAccount::find_vault(token, &crate::id());
+        let data = TokenLockInstruction::FundReleaseSchedule {
+            to: *to,
+            amount,
+            commencement_timestamp,
+            schedule_id,
+            cancelable_by: cancelable_by.to_vec(),
+            cancel_threshold,
+            timelock_index,
+        };
+        let accounts = vec![
+            AccountMeta::new(*token, false),
+            AccountMeta::new(*funder, true),
+            AccountMeta::new(*source, false),
+            AccountMeta::new(vault, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(*token_program, false),
+        ];
+        Instruction::new_with_borsh(crate::id(), &data, accounts)
+    }
+
+    pub fn transfer_timelock(token: &Pubkey, owner: &Pubkey, mint: &Pubkey, token_program: &Pubkey, to: &Pubkey, destination: &Pubkey, value: u32, timelock_index: u32) -> Instruction{
+        let (vault, _) = TokenLockAccount::find_vault(token, &crate::id());
+        let data = TokenLockInstruction::TransferTimelock {
+            to: *to,
+            value,
+            timelock_index,
+        };
+        let accounts = vec![
+            AccountMeta::new(*token, false),
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new(vault, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(*token_program, false),
+            AccountMeta::new(*destination, false),
+        ];
+        Instruction::new_with_borsh(crate::id(), &data, accounts)
+    }
+
+    pub fn reconcile(token: &Pubkey, token_program: &Pubkey) -> Instruction{
+        let (vault, _) = TokenLockAccount::find_vault(token, &crate::id());
+        let data = TokenLockInstruction::Reconcile;
+        let accounts = vec![
+            AccountMeta::new_readonly(*token, false),
+            AccountMeta::new_readonly(vault, false),
+            AccountMeta::new_readonly(*token_program, false),
+        ];
+        Instruction::new_with_borsh(crate::id(), &data, accounts)
+    }
+
+    pub fn claim_surplus(token: &Pubkey, funder: &Pubkey, token_program: &Pubkey) -> Instruction{
+        let (vault, _) = TokenLockAccount::find_vault(token, &crate::id());
+        let data = TokenLockInstruction::ClaimSurplus;
+        let accounts = vec![
+            AccountMeta::new(*token, false),
+            AccountMeta::new_readonly(*funder, true),
+            AccountMeta::new_readonly(vault, false),
+            AccountMeta::new_readonly(*token_program, false),
+        ];
+        Instruction::new_with_borsh(crate::id(), &data, accounts)
+    }
+
+    pub fn sweep_surplus(token: &Pubkey, authority: &Pubkey, mint: &Pubkey, token_program: &Pubkey, destination: &Pubkey, amount: u64) -> Instruction{
+        let (vault, _) = TokenLockAccount::find_vault(token, &crate::id());
+        let data = TokenLockInstruction::SweepSurplus {
+            amount,
+        };
+        let accounts = vec![
+            AccountMeta::new(*token, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(vault, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(*token_program, false),
+            AccountMeta::new(*destination, false),
+        ];
+        Instruction::new_with_borsh(crate::id(), &data, accounts)
+    }
+
+    pub fn migrate(token: &Pubkey, authority: &Pubkey, seed: &str, source: &Pubkey, mint: &Pubkey, token_program: &Pubkey, from_version: u8) -> Instruction{
+        let (vault, _) = TokenLockAccount::find_vault(token, &crate::id());
+        let old_account = Pubkey::create_with_seed(authority, seed, &crate::id()).expect("seed longer than 32 bytes");
+        let data = TokenLockInstruction::Migrate {
+            from_version,
+            seed: seed.to_string(),
+        };
+        let accounts = vec![
+            AccountMeta::new(*token, false),
+            AccountMeta::new(*authority, true),
+            AccountMeta::new(old_account, false),
+            AccountMeta::new(*source, false),
+            AccountMeta::new(vault, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(*token_program, false),
         ];
         Instruction::new_with_borsh(crate::id(), &data, accounts)
     }
@@ -38,10 +668,8 @@ impl TokenLockInstruction {
 mod tests {
     use super::{TokenLockInstruction};
     use borsh::{BorshDeserialize, BorshSerialize};
-    use crate::{
-        types::{TokenLockAccount, ReleaseSchedule}
-    };
-    use std::collections::HashMap;
+    use crate::types::TokenMetadata;
+    use solana_program::pubkey::Pubkey;
 
     #[test]
     fn pack_unpack() {
@@ -49,35 +677,32 @@ mod tests {
         let packed = mint.try_to_vec().unwrap();
         assert_eq!(hex::encode(packed), "00");
 
-        // let mint = TokenLockInstruction::CreateReleaseSchedule(2, 3, 4, 5);
-        // let packed = mint.try_to_vec().unwrap();
-        // assert_eq!(hex::encode(packed), "00");
-
-        // let account = TokenLockAccount{
-        //     release_schedules: vec![],
-        //     timelocks: HashMap::new(),
-        //     total_tokens_unlocked: HashMap::new(),
-        //     allowances: HashMap::new(),
-        //     counter: 0 
-        // };
-        // let packed = account.try_to_vec().unwrap();
-        // assert_eq!(hex::encode(packed), "00");
-
+        let transfer = TokenLockInstruction::TransferTimelockOwnership {
+            timelock_index: 3,
+            new_owner: Pubkey::new(&[7; 32]),
+        };
+        let packed = transfer.try_to_vec().unwrap();
+        assert_eq!(TokenLockInstruction::try_from_slice(&packed).unwrap(), transfer);
 
-        //println!("{}", hex::encode(packed));
+        let initialize = TokenLockInstruction::InitializeToken {
+            decimals: 8,
+            total_supply: 1_000_000_000,
+            metadata: TokenMetadata {
+                name: "Token".to_string(),
+                symbol: "TKN".to_string(),
+                uri: "".to_string(),
+            },
+        };
+        let packed = initialize.try_to_vec().unwrap();
+        assert_eq!(TokenLockInstruction::try_from_slice(&packed).unwrap(), initialize);
 
-        // for b in packed{
-        //     println!("{}", b);    
-        // }
-        // let unpack = NftInstruction::deserialize(&mut &packed[..]).unwrap();
+        let create = TokenLockInstruction::CreateReleaseSchedule(2, 3, 4, 5);
+        let packed = create.try_to_vec().unwrap();
+        assert_eq!(hex::encode(&packed), "0102000000030000000400000005000000");
+        assert_eq!(TokenLockInstruction::try_from_slice(&packed).unwrap(), create);
 
-        // assert_eq!(mint, unpack);
-
-        // let mint = TokenLockInstruction::TokenLockInstruction;
-        // let packed = mint.try_to_vec().unwrap();
-        // let unpack = NftInstruction::deserialize(&mut &packed[..]).unwrap();
-
-        // assert_eq!(mint, unpack);
-        // assert!(matches!(unpack, NftInstruction::InitializeMint(data) if !data.name.is_empty()));
+        // trailing bytes and unknown variants are rejected
+        assert!(TokenLockInstruction::try_from_slice(&[0, 0]).is_err());
+        assert!(TokenLockInstruction::try_from_slice(&[200]).is_err());
     }
-}
\ No newline at end of file
+}
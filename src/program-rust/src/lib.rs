@@ -1,7 +1,19 @@
+// early returns and instructions taking every account they touch as a parameter are kept as they are
+// in the Solidity contract the program is ported from
+#![allow(clippy::needless_return, clippy::too_many_arguments)]
 pub mod types;
+#[cfg(not(feature = "no-entrypoint"))]
 pub mod entrypoint;
+// the variant structs BorshSchema derives for TokenLockInstruction are never read
+#[allow(dead_code)]
 pub mod instruction;
 pub mod processor;
+pub mod token;
+pub mod migration;
+// returns the ClientResult of the RpcClient it wraps
+#[cfg(feature = "client")]
+#[allow(clippy::result_large_err)]
+pub mod client;
 
 solana_program::declare_id!("FZiJXJ5ZhMvYDE5JjRs9P6vQP5TxbKVP63y3WgDVQUQb");
 // Export current sdk types for downstream users building with a different sdk version
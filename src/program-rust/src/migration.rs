@@ -0,0 +1,235 @@
+//! Older layouts of the token lock account and their conversion to the current layout
+use std::collections::HashMap;
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+use bytemuck::try_from_bytes;
+use solana_program::{msg, program_error::ProgramError, pubkey::Pubkey};
+use crate::types::{ReleaseSchedule, Timelock, TokenLockAccount, TokenLockHeader};
+
+/// The Borsh layout of the first release, where timelocks were grouped by recipient and indexed per
+/// recipient. These accounts were created by the client at `Pubkey::create_with_seed(payer, seed, program_id)`,
+/// so they have no mint, vault or authority, and the payer proves ownership by signing for the base.
+pub mod v0 {
+    use super::*;
+
+    #[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, BorshSchema)]
+    pub struct Timelock {
+        pub schedule_id: u32,
+        pub commencement_timestamp: u32,
+        pub tokens_transferred: u32,
+        pub total_amount: u32,
+        pub cancelable_by: Vec<Pubkey>,
+    }
+
+    #[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, BorshSchema)]
+    pub struct TokenLockAccount {
+        pub counter: u32,
+        pub max_release_delay: u32,
+        pub min_timelock_amount: u32,
+        pub release_schedules: Vec<ReleaseSchedule>,
+        pub timelocks: HashMap<Pubkey, Vec<Timelock>>,
+        /// Never written, must be empty to migrate
+        pub total_tokens_unlocked: HashMap<Pubkey, u32>,
+        /// Never written, must be empty to migrate
+        pub allowances: HashMap<Pubkey, HashMap<Pubkey, u32>>,
+    }
+
+    impl TokenLockAccount {
+        /// Reads the account data, which may be longer than the serialized state.
+        pub fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+            Ok(Self::deserialize(&mut &data[..])?)
+        }
+    }
+}
+
+/// The layout version of token lock account data, 0 for the Borsh layout without a discriminator
+pub fn stored_version(data: &[u8]) -> u8 {
+    if data.len() < TokenLockHeader::LEN || data[..8] != TokenLockAccount::DISCRIMINATOR {
+        return 0;
+    }
+    try_from_bytes::<TokenLockHeader>(&data[..TokenLockHeader::LEN]).map_or(0, |header| header.version)
+}
+
+impl v0::TokenLockAccount {
+    /// The timelocks in the order they get their new indices: by recipient address, then by their old
+    /// per recipient index. That is the order version 0 stored them in, Borsh writes maps sorted by key,
+    /// so there is no other order across recipients to keep. Returns the recipient and the old index with
+    /// each timelock.
+    pub fn timelocks_in_migration_order(&self) -> Vec<(Pubkey, usize, &v0::Timelock)> {
+        let mut recipients: Vec<&Pubkey> = self.timelocks.keys().collect();
+        recipients.sort();
+        recipients.into_iter()
+            .flat_map(|recipient| self.timelocks[recipient].iter().enumerate().map(move |(idx, timelock)| (*recipient, idx, timelock)))
+            .collect()
+    }
+
+    /// Bytes the token lock account needs to take the timelocks
+    pub fn migrated_space(&self) -> usize {
+        let timelock_count: usize = self.timelocks.values().map(Vec::len).sum();
+        TokenLockAccount::space(timelock_count.max(1))
+    }
+
+    /// Tokens the timelocks still hold, which the vault has to receive when migrating
+    pub fn outstanding(&self) -> u64 {
+        self.timelocks.values().flatten()
+            .map(|timelock| timelock.total_amount.saturating_sub(timelock.tokens_transferred) as u64)
+            .sum()
+    }
+
+    /// Copies the release schedules, timelocks and settings into `account`, an initialized token lock account
+    /// without any release schedules or timelocks yet. Fails rather than drop anything the current layout
+    /// has no room for.
+    pub fn migrate(&self, account: &mut TokenLockAccount) -> Result<(), ProgramError> {
+        if !account.release_schedules().is_empty() || !account.timelocks().is_empty() {
+            msg!("the token lock account is in use");
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        if self.release_schedules.len() > TokenLockAccount::MAX_RELEASE_SCHEDULES {
+            msg!("more than {} release schedules", TokenLockAccount::MAX_RELEASE_SCHEDULES);
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if !self.total_tokens_unlocked.is_empty() || !self.allowances.is_empty() {
+            msg!("unlocked totals and allowances can't be migrated");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if self.migrated_space() > TokenLockAccount::space(account.timelock_capacity()) {
+            msg!("migration needs {} bytes", self.migrated_space());
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+
+        account.counter = self.counter;
+        account.max_release_delay = self.max_release_delay;
+        account.min_timelock_amount = self.min_timelock_amount;
+        for release_schedule in &self.release_schedules {
+            account.add_release_schedule(*release_schedule)?;
+        }
+        for (recipient, _, old) in self.timelocks_in_migration_order() {
+            if old.cancelable_by.len() > Timelock::MAX_CANCELABLE_BY {
+                msg!("timelock of {} has more than {} cancelers", recipient, Timelock::MAX_CANCELABLE_BY);
+                return Err(ProgramError::InvalidAccountData);
+            }
+            if old.schedule_id as usize >= self.release_schedules.len() || old.tokens_transferred > old.total_amount {
+                msg!("invalid timelock of {}", recipient);
+                return Err(ProgramError::InvalidAccountData);
+            }
+            let mut timelock = Timelock::new(old.schedule_id, old.commencement_timestamp, old.total_amount);
+            timelock.tokens_transferred = old.tokens_transferred;
+            timelock.set_cancelable_by(&old.cancelable_by);
+            account.add_timelock(&recipient, timelock)?;
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v0_timelock(total_amount: u32, tokens_transferred: u32, cancelable_by: Vec<Pubkey>) -> v0::Timelock {
+        v0::Timelock {
+            schedule_id: 0,
+            commencement_timestamp: 100,
+            tokens_transferred,
+            total_amount,
+            cancelable_by,
+        }
+    }
+
+    fn v0_account(timelocks: HashMap<Pubkey, Vec<v0::Timelock>>) -> v0::TokenLockAccount {
+        v0::TokenLockAccount {
+            counter: 7,
+            max_release_delay: 1000,
+            min_timelock_amount: 10,
+            release_schedules: vec![ReleaseSchedule {
+                release_count: 4,
+                delay_until_first_release_in_seconds: 0,
+                initial_release_portion_in_bips: 1000,
+                period_between_releases_in_seconds: 100,
+            }],
+            timelocks,
+            total_tokens_unlocked: HashMap::new(),
+            allowances: HashMap::new(),
+        }
+    }
+
+    /// Reads a migrated account back into the v0 layout
+    fn to_v0(account: &TokenLockAccount) -> v0::TokenLockAccount {
+        let mut timelocks: HashMap<Pubkey, Vec<v0::Timelock>> = HashMap::new();
+        for timelock in account.timelocks() {
+            timelocks.entry(timelock.recipient).or_default().push(v0::Timelock {
+                schedule_id: timelock.schedule_id,
+                commencement_timestamp: timelock.commencement_timestamp,
+                tokens_transferred: timelock.tokens_transferred,
+                total_amount: timelock.total_amount,
+                cancelable_by: timelock.cancelable_by().to_vec(),
+            });
+        }
+        v0::TokenLockAccount {
+            counter: account.counter,
+            max_release_delay: account.max_release_delay,
+            min_timelock_amount: account.min_timelock_amount,
+            release_schedules: account.release_schedules().to_vec(),
+            timelocks,
+            total_tokens_unlocked: HashMap::new(),
+            allowances: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn migrate_v0_keeps_progress() {
+        let owner = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let canceler = Pubkey::new_unique();
+        let mut timelocks = HashMap::new();
+        timelocks.insert(owner, vec![v0_timelock(1000, 250, vec![canceler]), v0_timelock(500, 500, vec![])]);
+        timelocks.insert(other, vec![v0_timelock(300, 0, vec![canceler, owner])]);
+        let old = v0_account(timelocks);
+
+        // the client allocated the account with room to spare
+        let mut data = old.try_to_vec().unwrap();
+        data.resize(data.len() + 64, 0);
+        assert_eq!(stored_version(&data), 0);
+        let unpacked = v0::TokenLockAccount::unpack(&data).unwrap();
+        assert_eq!(unpacked, old);
+        assert_eq!(unpacked.outstanding(), 750 + 300);
+
+        let mut data = vec![0; unpacked.migrated_space()];
+        let mut account = TokenLockAccount::init(&mut data, &Pubkey::new_unique(), &Pubkey::new_unique(), 0, 0, 0, None).unwrap();
+        unpacked.migrate(&mut account).unwrap();
+        assert_eq!(account.timelocks().len(), 3);
+        assert_eq!(to_v0(&account), old);
+
+        // recipients in address order, each keeping the order of its timelocks
+        let order = old.timelocks_in_migration_order();
+        for (idx, (recipient, old_idx, timelock)) in order.iter().enumerate() {
+            let migrated = account.get_timelock(recipient, idx).unwrap();
+            assert_eq!(old.timelocks[recipient][*old_idx], **timelock);
+            assert_eq!(migrated.tokens_transferred, timelock.tokens_transferred);
+        }
+
+        // only into a lockup that has nothing yet
+        assert_eq!(unpacked.migrate(&mut account), Err(ProgramError::AccountAlreadyInitialized));
+        assert_eq!(stored_version(&data), TokenLockAccount::VERSION);
+    }
+
+    #[test]
+    fn migrate_v0_refuses_to_drop_state() {
+        let migrate = |old: &v0::TokenLockAccount, space: usize| {
+            let mut data = vec![0; space];
+            let mut account = TokenLockAccount::init(&mut data, &Pubkey::new_unique(), &Pubkey::new_unique(), 0, 0, 0, None).unwrap();
+            old.migrate(&mut account)
+        };
+        let mut old = v0_account(HashMap::new());
+        old.timelocks.insert(Pubkey::new_unique(), vec![v0_timelock(100, 0, (0..11).map(|_| Pubkey::new_unique()).collect())]);
+        assert_eq!(migrate(&old, old.migrated_space()), Err(ProgramError::InvalidAccountData));
+
+        old.timelocks.insert(Pubkey::new_unique(), vec![v0_timelock(100, 0, vec![]), v0_timelock(100, 0, vec![])]);
+        old.timelocks.retain(|_, timelocks| timelocks.len() == 2);
+        assert_eq!(migrate(&old, TokenLockAccount::space(1)), Err(ProgramError::AccountDataTooSmall));
+        assert_eq!(migrate(&old, old.migrated_space()), Ok(()));
+
+        old.timelocks.clear();
+        old.total_tokens_unlocked.insert(Pubkey::new_unique(), 5);
+        assert_eq!(migrate(&old, old.migrated_space()), Err(ProgramError::InvalidAccountData));
+    }
+}
@@ -1,29 +1,36 @@
 use crate::{
-    instruction::{TokenLockInstruction},
-    types::{TokenLockAccount, ReleaseSchedule, Timelock},
+    instruction::{TokenLockInstruction, BurnSource},
+    migration,
+    token,
+    types::{TokenLockAccount, ReleaseSchedule, Timelock, CancelMode, CancelProposal, TokenMetadata, ReconcileReport, SurplusClaim},
 };
 
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{    
     account_info::{next_account_info, AccountInfo}, 
-    entrypoint::ProgramResult, program_error::ProgramError,
+    clock::Clock,
+    entrypoint::{ProgramResult, MAX_PERMITTED_DATA_INCREASE}, program_error::ProgramError,
     msg,
+    program::{invoke, invoke_signed},
+    program_pack::Pack,
     pubkey::Pubkey, rent::Rent, sysvar::Sysvar,
+    system_instruction, system_program,
 };
 
 
 /// Program state handler.
 pub struct Processor<'a>{
     pub account_key: &'a Pubkey,
-    pub account_info: &'a mut TokenLockAccount,
-    pub modified: bool
+    pub account_info: TokenLockAccount<'a>,
+    pub modified: bool,
+    /// Unix timestamp of the current block, used for the unlock calculations
+    pub timestamp: u32,
 }
 
 impl<'a> Processor<'a> {
     const BIPS_PRECISION: u32 = 10000;
     pub fn process_greeting(
         &mut self,
-        program_id: &Pubkey
     ) -> ProgramResult    {       
      
         self.account_info.counter += 1;
@@ -34,85 +41,112 @@ impl<'a> Processor<'a> {
 
 
 
+    /**
+        @notice Create a vesting schedule that timelocks can be funded with. Only the lockup's authority or its
+            treasury authority can, so nobody else can use up the release schedule slots.
+        @param authority the authority or the treasury authority, must sign
+        @param releaseCount the total number of releases including the initial release
+        @param delayUntilFirstReleaseInSeconds "cliff" or 0 for immediate release
+        @param initialReleasePortionInBips portion to release in 100ths of 1% (10000 BIPS per 100%)
+        @param periodBetweenReleasesInSeconds after the delay and initial release
+    */
     pub fn process_create_release_schedule(
         &mut self,
-        program_id: &Pubkey, 
-        account: &AccountInfo,
+        authority: &AccountInfo,
         release_count: u32,
         delay_until_first_release_in_seconds: u32,
         initial_release_portion_in_bips: u32,
         period_between_releases_in_seconds: u32
     ) -> ProgramResult {
+        if !authority.is_signer {
+            msg!("authority must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if authority.key != &self.account_info.authority() && self.account_info.treasury_authority() != Some(*authority.key) {
+            msg!("not allowed to create release schedules");
+            return Err(ProgramError::InvalidArgument);
+        }
+        let schedule = ReleaseSchedule{
+            release_count,
+            delay_until_first_release_in_seconds,
+            initial_release_portion_in_bips,
+            period_between_releases_in_seconds
+        };
+        self.create_release_schedule(schedule)?;
+        Ok(())
+    }
 
+    /**
+        @notice Checks a release schedule and adds it to the token lock account
+        @param schedule the release schedule to add
+        @return unlockScheduleId the id of the new release schedule
+    */
+    pub fn create_release_schedule(&mut self, schedule: ReleaseSchedule) -> Result<u32, ProgramError> {
         //checking params
-        if delay_until_first_release_in_seconds > self.account_info.max_release_delay {
+        if schedule.delay_until_first_release_in_seconds > self.account_info.max_release_delay {
             msg!("first release > max delay");
             return Err(ProgramError::InvalidArgument);
         }
-        if release_count < 1 {
+        if schedule.release_count < 1 {
             msg!("< 1 release");
             return Err(ProgramError::InvalidArgument);
         }
-        if initial_release_portion_in_bips > Self::BIPS_PRECISION {
+        if schedule.initial_release_portion_in_bips > Self::BIPS_PRECISION {
             msg!("release > 100%");
             return Err(ProgramError::InvalidArgument);
         }
-        if release_count > 1 && period_between_releases_in_seconds ==0 {
+        if schedule.release_count > 1 && schedule.period_between_releases_in_seconds ==0 {
             msg!("period = 0");
             return Err(ProgramError::InvalidArgument);
         }
-        if release_count == 1 && initial_release_portion_in_bips < Self::BIPS_PRECISION {
+        if schedule.release_count == 1 && schedule.initial_release_portion_in_bips < Self::BIPS_PRECISION {
             msg!("released < 100%");
             return Err(ProgramError::InvalidArgument);
         }
 
-        let schedule = ReleaseSchedule{
-            release_count: release_count,
-            delay_until_first_release_in_seconds: delay_until_first_release_in_seconds,
-            initial_release_portion_in_bips: initial_release_portion_in_bips,
-            period_between_releases_in_seconds: period_between_releases_in_seconds
-        };
-        self.account_info.add_release_schedule(schedule);
+        let schedule_id = self.account_info.add_release_schedule(schedule).map_err(|err| {
+            msg!("max {} release schedules", TokenLockAccount::MAX_RELEASE_SCHEDULES);
+            err
+        })?;
         self.modified = true;
-        msg!("process_create_release_schedule is called {}!", self.account_info.release_schedules.len());
-        Ok(())
+        msg!("ReleaseScheduleCreated {}", schedule_id);
+        Ok(schedule_id)
     }    
 
-    fn fund(&mut self, to :&Pubkey, amount: u32, commencement_timestamp: u32, schedule_id: u32) -> i32 {
+    fn fund(&mut self, to :&Pubkey, amount: u32, commencement_timestamp: u32, schedule_id: u32) -> Result<u32, ProgramError> {
 
         //check params        
         if amount < self.account_info.min_timelock_amount {
             msg!("amount < min funding");
-            return -1;
+            return Err(ProgramError::InvalidArgument);
         }
         //require(to != address(0), "to 0 address");
-        if schedule_id < self.account_info.release_schedules.len() as u32 {
+        if schedule_id >= self.account_info.release_schedules().len() as u32 {
             msg!("bad scheduleId");
-            return -1;
+            return Err(ProgramError::InvalidArgument);
         }
-        if amount < self.account_info.release_schedules[schedule_id as usize].release_count {
+        let release_schedule = &self.account_info.release_schedules()[schedule_id as usize];
+        if amount < release_schedule.release_count {
             msg!("< 1 token per release");
-            return -1;
-        }
-
-        // It will revert via ERC20 implementation if there's no allowance
-        //token.safeTransferFrom(msg.sender, address(this), amount);
-        // require(
-        //     commencementTimestamp <= block.timestamp + maxReleaseDelay
-        // , "commencement time out of range");
-
-        // require(
-        //     commencementTimestamp + releaseSchedules[scheduleId].delayUntilFirstReleaseInSeconds <=
-        //     block.timestamp + maxReleaseDelay
-        // , "initial release out of range");
-        let timelock = Timelock {
-            schedule_id: schedule_id,
-            commencement_timestamp: commencement_timestamp,
-            tokens_transferred: 0,
-            total_amount: amount,
-            cancelable_by: vec![],
-        };
-        return self.account_info.add_timelock(to, timelock);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let max_timestamp = self.timestamp as u64 + self.account_info.max_release_delay as u64;
+        if commencement_timestamp as u64 > max_timestamp {
+            msg!("commencement time out of range");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if commencement_timestamp as u64 + release_schedule.delay_until_first_release_in_seconds as u64 > max_timestamp {
+            msg!("initial release out of range");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let timelock = Timelock::new(schedule_id, commencement_timestamp, amount);
+        let timelock_id = self.account_info.add_timelock(to, timelock).map_err(|err| {
+            msg!("no room for another timelock");
+            err
+        })?;
+        return Ok(timelock_id as u32);
     }    
 
     /**
@@ -125,65 +159,1363 @@ impl<'a> Processor<'a> {
         @param commencementTimestamp the time the release schedule will start
         @param scheduleId the id of the release schedule that will be used to release the tokens
         @param cancelableBy array of canceler addresses
-        @return success Always returns true on completion so that a function calling it can revert if the required call did not succeed
+        @param cancelThreshold number of cancelableBy approvals needed to cancel, 0 or 1 for any single canceler
+        @return timelockId the index of the new timelock of the recipient
     */
-    pub fn fund_release_schedule(&mut self, to: &Pubkey, amount: u32, commencement_timestamp: u32, schedule_id: u32, cancelable_by: &[Pubkey]) -> bool {
-        if cancelable_by.len() > 10 {
-            msg!("max 10 cancelableBy addressees");
-            return false;
+    pub fn fund_release_schedule(&mut self, to: &Pubkey, amount: u32, commencement_timestamp: u32, schedule_id: u32, cancelable_by: &[Pubkey], cancel_threshold: u8) -> Result<u32, ProgramError> {
+        if cancelable_by.len() > Timelock::MAX_CANCELABLE_BY {
+            msg!("max {} cancelableBy addressees", Timelock::MAX_CANCELABLE_BY);
+            return Err(ProgramError::InvalidArgument);
+        }
+        if cancel_threshold as usize > cancelable_by.len() {
+            msg!("threshold > cancelableBy");
+            return Err(ProgramError::InvalidArgument);
         }
 
-        let timelock_id = self.fund(to, amount, commencement_timestamp, schedule_id);
-        if cancelable_by.len() > 0 {
-            self.account_info.get_timelock_mut(to, timelock_id as usize).unwrap().set_cancelable_by(cancelable_by);
+        let timelock_id = self.fund(to, amount, commencement_timestamp, schedule_id)?;
+        if !cancelable_by.is_empty() {
+            let timelock = self.account_info.get_timelock_mut(to, timelock_id as usize).unwrap();
+            timelock.set_cancelable_by(cancelable_by);
+            timelock.set_cancel_threshold(cancel_threshold);
         }
+        self.modified = true;
         //emit ScheduleFunded(msg.sender, to, scheduleId, amount, commencementTimestamp, timelockId, cancelableBy);
-        return true;
+        msg!("ScheduleFunded {} {} {}", to, schedule_id, amount);
+        msg!("timelock {} commencing {}", timelock_id, commencement_timestamp);
+        return Ok(timelock_id);
+    }
+
+
+    /**
+        @notice Transfer tokens from the funder into the vault and fund a timelock with them.
+            The timelock is funded with what the vault actually received, which is less than amount
+            for Token-2022 mints with a transfer fee. Native SOL lockups are funded with lamports of the funder.
+        @param funder the owner of the source token account, must sign
+        @param source the funder's token account, unused for native SOL
+        @param vault the PDA holding the locked tokens
+        @param mint the mint of the token lock account
+        @param tokenProgram the SPL Token or Token-2022 program owning the mint, the system program for native SOL
+        @param to recipient address that will have tokens unlocked on a release schedule
+        @param amount of tokens to transfer in base units
+        @param commencementTimestamp the time the release schedule will start
+        @param scheduleId the id of the release schedule that will be used to release the tokens
+        @param cancelableBy array of canceler addresses
+        @param cancelThreshold number of cancelableBy approvals needed to cancel
+        @param timelockIndex the index the new timelock has to get, so a funding sent again fails
+    */
+    pub fn process_fund_release_schedule<'b>(
+        &mut self,
+        program_id: &Pubkey,
+        funder: &AccountInfo<'b>,
+        source: &AccountInfo<'b>,
+        vault: &AccountInfo<'b>,
+        mint: &AccountInfo<'b>,
+        token_program: &AccountInfo<'b>,
+        to: &Pubkey,
+        amount: u32,
+        commencement_timestamp: u32,
+        schedule_id: u32,
+        cancelable_by: &[Pubkey],
+        cancel_threshold: u8,
+        timelock_index: u32,
+    ) -> ProgramResult {
+        if !funder.is_signer {
+            msg!("funder must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if self.account_info.timelocks().len() != timelock_index as usize {
+            msg!("the new timelock would get index {}", self.account_info.timelocks().len());
+            return Err(ProgramError::InvalidArgument);
+        }
+        let received = self.transfer_to_vault(program_id, funder, source, vault, mint, token_program, amount as u64)?;
+
+        self.fund_release_schedule(to, received as u32, commencement_timestamp, schedule_id, cancelable_by, cancel_threshold)?;
+        if self.account_info.original_funder().is_none() {
+            self.account_info.set_original_funder(funder.key);
+        }
+        Ok(())
     }
 
 
     /**
         @notice Cancel a cancelable timelock created by the fundReleaseSchedule function.
             WARNING: this function cannot cancel a release schedule created by fundReleaseSchedule
-            If canceled the tokens that are locked at the time of the cancellation will be returned to the funder
-            and unlocked tokens will be transferred to the recipient.
+            If canceled the tokens that are locked at the time of the cancellation will be returned to the funder.
+            With CancelMode::PayOut the unlocked tokens will be transferred to the recipient.
+            With CancelMode::StopVesting vesting stops at the cancellation time and the unlocked tokens
+            stay in the timelock for the recipient to withdraw later.
+        @param canceler one of the timelock's cancelableBy addresses, must sign
+        @param vault the PDA holding the locked tokens
+        @param mint the mint of the token lock account
+        @param tokenProgram the token program owning the mint, the system program for native SOL
+        @param reclaimDestination the token account of reclaimTokenTo, or reclaimTokenTo itself for native SOL
+        @param targetDestination the token account of the target, or the target itself for native SOL
         @param target The address that would receive the tokens when released from the timelock.
-        @return success Always returns true on completion so that a function calling it can revert if the required call did not succeed
+        @param timelockIndex the index of the timelock belonging to the target
+        @param reclaimTokenTo the address the locked tokens are returned to
+        @param mode whether the unlocked tokens are paid out or left claimable
     */
-    pub fn cancel_timelock(&mut self, target: &Pubkey, timelock_index: u32, reclaim_token_to: &Pubkey) -> bool {
+    pub fn process_cancel_timelock<'b>(
+        &mut self,
+        program_id: &Pubkey,
+        canceler: &AccountInfo<'b>,
+        vault: &AccountInfo<'b>,
+        mint: &AccountInfo<'b>,
+        token_program: &AccountInfo<'b>,
+        reclaim_destination: &AccountInfo<'b>,
+        target_destination: &AccountInfo<'b>,
+        target: &Pubkey,
+        timelock_index: u32,
+        reclaim_token_to: &Pubkey,
+        mode: CancelMode,
+    ) -> ProgramResult {
+        if !canceler.is_signer {
+            msg!("canceler must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
 
-        let timelock :&mut Timelock;
-        let account_info = &self.account_info;
-        if let Some(tmlock) = TokenLockAccount::get_timelock_mut(&account_info, target, timelock_index as usize){
-            timelock = tmlock;
-        }else
-        {
+        let can_cancelable;
+        let cancel_threshold;
+        if let Some(timelock) = self.account_info.get_timelock(target, timelock_index as usize){
+            can_cancelable = timelock.has_cancelable_by(canceler.key);
+            cancel_threshold = timelock.cancel_threshold;
+        } else {
             msg!("invalid timelock");
-            return false;
+            return Err(ProgramError::InvalidArgument);
         }
 
         //require(reclaimTokenTo != address(0), "Invalid reclaimTokenTo");
-        let can_cancelable = timelock.has_cancelable_by(self.account_key);
-        if can_cancelable == false{
+        if !can_cancelable {
             msg!("You are not allowed to cancel this timelock");
-            return false;
+            return Err(ProgramError::InvalidArgument);
+        }
+        if cancel_threshold > 1 {
+            msg!("cancel needs {} approvals, use ProposeCancel", cancel_threshold);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        self.check_destination(reclaim_destination, reclaim_token_to)?;
+        self.check_destination(target_destination, target)?;
+        let (canceled_amount, paid_amount) = self.cancel(canceler.key, target, timelock_index, reclaim_token_to, mode)?;
+        self.transfer_from_vault(program_id, vault, mint, token_program, reclaim_destination, reclaim_token_to, canceled_amount as u64)?;
+        self.transfer_from_vault(program_id, vault, mint, token_program, target_destination, target, paid_amount as u64)
+    }
+
+    /// Cancels the timelock and returns the amounts to pay out of the vault,
+    /// the canceled amount to the reclaim address and the paid amount to the target.
+    fn cancel(&mut self, canceler: &Pubkey, target: &Pubkey, timelock_index: u32, reclaim_token_to: &Pubkey, mode: CancelMode) -> Result<(u32, u32), ProgramError> {
+        let canceled_amount = self.locked_balance_of_timelock(target, timelock_index);
+        if canceled_amount == 0{
+            msg!("Timelock has no value left");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let unlocked_amount = self.unlocked_balance_of_timelock(target, timelock_index);
+        let timestamp = self.timestamp;
+        let timelock = self.account_info.get_timelock_mut(target, timelock_index as usize).unwrap();
+        let paid_amount;
+        match mode {
+            CancelMode::PayOut => {
+                paid_amount = unlocked_amount;
+                timelock.tokens_transferred = timelock.total_amount;
+            }
+            CancelMode::StopVesting => {
+                paid_amount = 0;
+                timelock.total_amount -= canceled_amount;
+                timelock.vesting_stopped_at = timestamp;
+            }
+        }
+        self.modified = true;
+        // emit TimelockCanceled(msg.sender, target, timelockIndex, reclaimTokenTo, canceledAmount, paidAmount);
+        msg!("TimelockCanceled {} {} {}", canceler, target, timelock_index);
+        msg!("reclaimed {} to {}, paid {}", canceled_amount, reclaim_token_to, paid_amount);
+        Ok((canceled_amount, paid_amount))
+    }
+
+
+    /**
+        @notice Open a proposal to cancel a timelock that needs several cancelers to approve.
+            The proposal is stored in a PDA paid for by the proposer, who counts as the first approval.
+            It expires after `CancelProposal::LIFETIME`, a new proposal for the timelock can be opened once it is closed.
+        @param canceler one of the timelock's cancelableBy addresses, must sign and pays for the proposal
+        @param proposal the proposal PDA derived from the token lock account, the target and the timelock index
+        @param systemProgram the system program, used to create the proposal account
+        @param target The address that would receive the tokens when released from the timelock.
+        @param timelockIndex the index of the timelock belonging to the target
+        @param reclaimTokenTo the address the locked tokens are returned to
+        @param mode whether the unlocked tokens are paid out or left claimable
+    */
+    pub fn process_propose_cancel<'b>(&mut self, program_id: &Pubkey, canceler: &AccountInfo<'b>, proposal: &AccountInfo<'b>, system_program: &AccountInfo<'b>, target: &Pubkey, timelock_index: u32, reclaim_token_to: &Pubkey, mode: CancelMode) -> ProgramResult {
+        if !canceler.is_signer {
+            msg!("canceler must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if let Some(timelock) = self.account_info.get_timelock(target, timelock_index as usize){
+            if !timelock.has_cancelable_by(canceler.key) {
+                msg!("You are not allowed to cancel this timelock");
+                return Err(ProgramError::InvalidArgument);
+            }
+        } else {
+            msg!("invalid timelock");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let (proposal_key, bump) = CancelProposal::find_address(self.account_key, target, timelock_index, program_id);
+        if proposal.key != &proposal_key {
+            msg!("invalid proposal address");
+            return Err(ProgramError::InvalidSeeds);
+        }
+        if proposal.owner == program_id {
+            msg!("proposal already exists");
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+
+        Self::create_pda(
+            canceler,
+            proposal,
+            CancelProposal::LEN,
+            program_id,
+            system_program,
+            &[CancelProposal::SEED, self.account_key.as_ref(), target.as_ref(), &timelock_index.to_le_bytes(), &[bump]],
+        )?;
+
+        let cancel_proposal = CancelProposal {
+            target: *target,
+            timelock_index,
+            reclaim_token_to: *reclaim_token_to,
+            mode,
+            proposer: *canceler.key,
+            expires_at: self.timestamp.saturating_add(CancelProposal::LIFETIME),
+            approvals: vec![*canceler.key],
+        };
+        cancel_proposal.serialize(&mut &mut proposal.data.borrow_mut()[..])?;
+        msg!("CancelProposed {} {} {}", canceler.key, target, timelock_index);
+        Ok(())
+    }
+
+
+    /**
+        @notice Approve a pending cancellation proposal
+        @param canceler one of the timelock's cancelableBy addresses, must sign
+        @param proposal the proposal PDA
+    */
+    pub fn process_approve_cancel(&mut self, program_id: &Pubkey, canceler: &AccountInfo, proposal: &AccountInfo) -> ProgramResult {
+        if !canceler.is_signer {
+            msg!("canceler must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let mut cancel_proposal = self.load_cancel_proposal(program_id, proposal)?;
+        if self.timestamp >= cancel_proposal.expires_at {
+            msg!("proposal expired");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if let Some(timelock) = self.account_info.get_timelock(&cancel_proposal.target, cancel_proposal.timelock_index as usize){
+            if !timelock.has_cancelable_by(canceler.key) {
+                msg!("You are not allowed to cancel this timelock");
+                return Err(ProgramError::InvalidArgument);
+            }
+        } else {
+            msg!("invalid timelock");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if cancel_proposal.has_approval(canceler.key) {
+            msg!("already approved");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if cancel_proposal.approvals.len() >= CancelProposal::MAX_APPROVALS {
+            msg!("max {} approvals", CancelProposal::MAX_APPROVALS);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        cancel_proposal.approvals.push(*canceler.key);
+        cancel_proposal.serialize(&mut &mut proposal.data.borrow_mut()[..])?;
+        msg!("CancelApproved {} {} {}", canceler.key, cancel_proposal.target, cancel_proposal.timelock_index);
+        Ok(())
+    }
+
+
+    /**
+        @notice Cancel the timelock of a proposal once enough of the timelock's current cancelers approved it.
+            The proposal account is closed and its rent returned to the proposer.
+        @param executor executes the cancellation, anyone once there are enough approvals, must sign
+        @param proposal the proposal PDA
+        @param proposer the account that opened the proposal
+        @param vault the PDA holding the locked tokens
+        @param mint the mint of the token lock account
+        @param tokenProgram the token program owning the mint, the system program for native SOL
+        @param reclaimDestination the token account of the proposal's reclaimTokenTo, or the address itself for native SOL
+        @param targetDestination the token account of the proposal's target, or the target itself for native SOL
+    */
+    pub fn process_execute_cancel<'b>(
+        &mut self,
+        program_id: &Pubkey,
+        executor: &AccountInfo<'b>,
+        proposal: &AccountInfo<'b>,
+        proposer: &AccountInfo<'b>,
+        vault: &AccountInfo<'b>,
+        mint: &AccountInfo<'b>,
+        token_program: &AccountInfo<'b>,
+        reclaim_destination: &AccountInfo<'b>,
+        target_destination: &AccountInfo<'b>,
+    ) -> ProgramResult {
+        if !executor.is_signer {
+            msg!("executor must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let cancel_proposal = self.load_cancel_proposal(program_id, proposal)?;
+        if proposer.key != &cancel_proposal.proposer {
+            msg!("invalid proposer");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if self.timestamp >= cancel_proposal.expires_at {
+            msg!("proposal expired");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let approvals;
+        let cancel_threshold;
+        if let Some(timelock) = self.account_info.get_timelock(&cancel_proposal.target, cancel_proposal.timelock_index as usize){
+            // only count approvals of addresses that can still cancel
+            approvals = cancel_proposal.approvals.iter().filter(|approval| timelock.has_cancelable_by(approval)).count();
+            cancel_threshold = timelock.cancel_threshold.max(1);
+        } else {
+            msg!("invalid timelock");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if approvals < cancel_threshold as usize {
+            msg!("{} of {} approvals", approvals, cancel_threshold);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        self.check_destination(reclaim_destination, &cancel_proposal.reclaim_token_to)?;
+        self.check_destination(target_destination, &cancel_proposal.target)?;
+        let (canceled_amount, paid_amount) = self.cancel(executor.key, &cancel_proposal.target, cancel_proposal.timelock_index, &cancel_proposal.reclaim_token_to, cancel_proposal.mode)?;
+        self.transfer_from_vault(program_id, vault, mint, token_program, reclaim_destination, &cancel_proposal.reclaim_token_to, canceled_amount as u64)?;
+        self.transfer_from_vault(program_id, vault, mint, token_program, target_destination, &cancel_proposal.target, paid_amount as u64)?;
+        Self::close_cancel_proposal(proposal, proposer);
+        Ok(())
+    }
+
+    /**
+        @notice Withdraw a cancellation proposal without canceling the timelock. The proposer or a quorum of the
+            timelock's current cancelers can close it any time, anyone can close it once it expired.
+            The proposal account is closed and its rent returned to the proposer.
+        @param proposal the proposal PDA
+        @param proposer the account that opened the proposal, signs to close it itself
+        @param signers current cancelers of the timelock, at least as many as its cancel threshold, unless the
+            proposer signs or the proposal expired
+    */
+    pub fn process_close_cancel_proposal(&mut self, program_id: &Pubkey, proposal: &AccountInfo, proposer: &AccountInfo, signers: &[AccountInfo]) -> ProgramResult {
+        let cancel_proposal = self.load_cancel_proposal(program_id, proposal)?;
+        if proposer.key != &cancel_proposal.proposer {
+            msg!("invalid proposer");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if self.timestamp < cancel_proposal.expires_at && !proposer.is_signer {
+            if let Some(timelock) = self.account_info.get_timelock(&cancel_proposal.target, cancel_proposal.timelock_index as usize){
+                Self::check_canceler_quorum(timelock, signers)?;
+            } else {
+                msg!("invalid timelock");
+                return Err(ProgramError::InvalidArgument);
+            }
+        }
+
+        Self::close_cancel_proposal(proposal, proposer);
+        msg!("CancelProposalClosed {} {} {}", proposer.key, cancel_proposal.target, cancel_proposal.timelock_index);
+        Ok(())
+    }
+
+
+    /// Returns the rent of a proposal account to its proposer and clears its data.
+    fn close_cancel_proposal(proposal: &AccountInfo, proposer: &AccountInfo) {
+        let lamports = proposal.lamports();
+        **proposal.lamports.borrow_mut() = 0;
+        **proposer.lamports.borrow_mut() += lamports;
+        proposal.data.borrow_mut().fill(0);
+    }
+
+
+    /// Reads a cancel proposal, checking it belongs to this token lock account.
+    fn load_cancel_proposal(&self, program_id: &Pubkey, proposal: &AccountInfo) -> Result<CancelProposal, ProgramError> {
+        if proposal.owner != program_id {
+            msg!("invalid proposal owner");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let cancel_proposal = CancelProposal::deserialize(&mut &proposal.data.borrow()[..])?;
+        let (proposal_key, _) = CancelProposal::find_address(self.account_key, &cancel_proposal.target, cancel_proposal.timelock_index, program_id);
+        if proposal.key != &proposal_key {
+            msg!("invalid proposal address");
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(cancel_proposal)
+    }
+
+
+    /**
+        @notice Add a canceler to a timelock after it was funded
+        @param signers current cancelers of the timelock, at least as many as its cancel threshold must sign
+        @param target The address that would receive the tokens when released from the timelock.
+        @param timelockIndex the index of the timelock belonging to the target
+        @param canceler the address that can cancel the timelock from now on
+    */
+    pub fn process_add_canceler(&mut self, signers: &[AccountInfo], target: &Pubkey, timelock_index: u32, canceler: &Pubkey) -> ProgramResult {
+        let timelock;
+        if let Some(tmlock) = self.account_info.get_timelock_mut(target, timelock_index as usize){
+            timelock = tmlock;
+        } else {
+            msg!("invalid timelock");
+            return Err(ProgramError::InvalidArgument);
+        }
+        Self::check_canceler_quorum(timelock, signers)?;
+        if timelock.has_cancelable_by(canceler) {
+            msg!("already a canceler");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if timelock.cancelable_by().len() >= Timelock::MAX_CANCELABLE_BY {
+            msg!("max {} cancelableBy addressees", Timelock::MAX_CANCELABLE_BY);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        timelock.add_cancelable_by(canceler);
+        self.modified = true;
+        msg!("CancelerAdded {} {} {}", target, timelock_index, canceler);
+        Ok(())
+    }
+
+
+    /**
+        @notice Remove a canceler from a timelock. The cancel threshold is lowered if fewer cancelers are left.
+        @param signers current cancelers of the timelock, at least as many as its cancel threshold must sign
+        @param target The address that would receive the tokens when released from the timelock.
+        @param timelockIndex the index of the timelock belonging to the target
+        @param canceler the address that can no longer cancel the timelock
+    */
+    pub fn process_remove_canceler(&mut self, signers: &[AccountInfo], target: &Pubkey, timelock_index: u32, canceler: &Pubkey) -> ProgramResult {
+        let timelock;
+        if let Some(tmlock) = self.account_info.get_timelock_mut(target, timelock_index as usize){
+            timelock = tmlock;
+        } else {
+            msg!("invalid timelock");
+            return Err(ProgramError::InvalidArgument);
+        }
+        Self::check_canceler_quorum(timelock, signers)?;
+        if !timelock.remove_cancelable_by(canceler) {
+            msg!("not a canceler");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        self.modified = true;
+        msg!("CancelerRemoved {} {} {}", target, timelock_index, canceler);
+        Ok(())
+    }
+
+
+    /**
+        @notice Give up the signer's own right to cancel a timelock. The cancel threshold is lowered if fewer cancelers are left.
+        @param canceler the canceler renouncing, must sign
+        @param target The address that would receive the tokens when released from the timelock.
+        @param timelockIndex the index of the timelock belonging to the target
+    */
+    pub fn process_renounce_cancel(&mut self, canceler: &AccountInfo, target: &Pubkey, timelock_index: u32) -> ProgramResult {
+        if !canceler.is_signer {
+            msg!("canceler must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let timelock;
+        if let Some(tmlock) = self.account_info.get_timelock_mut(target, timelock_index as usize){
+            timelock = tmlock;
+        } else {
+            msg!("invalid timelock");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if !timelock.remove_cancelable_by(canceler.key) {
+            msg!("not a canceler");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        self.modified = true;
+        msg!("CancelRenounced {} {} {}", target, timelock_index, canceler.key);
+        Ok(())
+    }
+
+
+    /**
+        @notice Log who can cancel a timelock and how many of them have to approve
+        @param target The address that would receive the tokens when released from the timelock.
+        @param timelockIndex the index of the timelock belonging to the target
+    */
+    pub fn process_log_cancel_status(&self, target: &Pubkey, timelock_index: u32) -> ProgramResult {
+        if let Some(timelock) = self.account_info.get_timelock(target, timelock_index as usize){
+            msg!("CancelStatus {} {} threshold {}", target, timelock_index, timelock.cancel_threshold.max(1));
+            for canceler in timelock.cancelable_by() {
+                msg!("cancelable by {}", canceler);
+            }
+            Ok(())
+        } else {
+            msg!("invalid timelock");
+            Err(ProgramError::InvalidArgument)
+        }
+    }
+
+
+    /**
+        @notice Check the vault holds at least what the timelocks still owe and log the comparison.
+        @param vault the PDA holding the locked tokens
+        @param tokenProgram the token program owning the mint, the system program for native SOL
+    */
+    pub fn process_reconcile(&self, program_id: &Pubkey, vault: &AccountInfo, token_program: &AccountInfo) -> ProgramResult {
+        self.check_vault(program_id, vault, token_program)?;
+        let report = ReconcileReport::new(&self.account_info, self.vault_balance(vault)?);
+        msg!("Reconcile {} vault {} obligations {}", self.account_key, report.vault_balance, report.obligations);
+        msg!("recipients {} timelocks {} surplus {}", report.recipients, report.timelocks, report.surplus());
+        if report.shortfall() > 0 {
+            msg!("vault short by {}", report.shortfall());
+            return Err(ProgramError::InsufficientFunds);
+        }
+        Ok(())
+    }
+
+
+    /**
+        @notice Start claiming the vault's surplus as the original funder of a lockup without a treasury authority.
+            The surplus at this time can be swept once the claim delay has passed, a new claim replaces the pending one.
+        @param funder the original funder, must sign
+        @param vault the PDA holding the locked tokens
+        @param tokenProgram the token program owning the mint, the system program for native SOL
+    */
+    pub fn process_claim_surplus(&mut self, program_id: &Pubkey, funder: &AccountInfo, vault: &AccountInfo, token_program: &AccountInfo) -> ProgramResult {
+        if !funder.is_signer {
+            msg!("funder must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if self.account_info.treasury_authority().is_some() {
+            msg!("surplus is swept by the treasury authority");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if self.account_info.original_funder() != Some(*funder.key) {
+            msg!("not the original funder");
+            return Err(ProgramError::InvalidArgument);
+        }
+        self.check_vault(program_id, vault, token_program)?;
+        let surplus = ReconcileReport::new(&self.account_info, self.vault_balance(vault)?).surplus();
+        if surplus == 0 {
+            msg!("no surplus");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let claim = SurplusClaim {
+            amount: surplus,
+            claimable_at: self.timestamp.checked_add(TokenLockAccount::SURPLUS_CLAIM_DELAY).ok_or_else(|| {
+                msg!("claim delay out of range");
+                ProgramError::InvalidArgument
+            })?,
+        };
+        self.account_info.set_surplus_claim(Some(claim));
+        self.modified = true;
+        msg!("SurplusClaimed {} {} claimable at {}", funder.key, claim.amount, claim.claimable_at);
+        Ok(())
+    }
+
+    /**
+        @notice Transfer tokens the vault holds beyond what the timelocks still owe. The treasury authority can
+            sweep any surplus, without one the original funder can sweep what they claimed once the delay passed.
+        @param authority the treasury authority or the original funder, must sign
+        @param vault the PDA holding the locked tokens
+        @param mint the mint of the token lock account
+        @param tokenProgram the token program owning the mint, the system program for native SOL
+        @param destination the token account of the authority, or the authority itself for native SOL
+        @param amount of surplus tokens to sweep in base units
+    */
+    pub fn process_sweep_surplus<'b>(
+        &mut self,
+        program_id: &Pubkey,
+        authority: &AccountInfo<'b>,
+        vault: &AccountInfo<'b>,
+        mint: &AccountInfo<'b>,
+        token_program: &AccountInfo<'b>,
+        destination: &AccountInfo<'b>,
+        amount: u64,
+    ) -> ProgramResult {
+        if !authority.is_signer {
+            msg!("authority must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        self.check_vault(program_id, vault, token_program)?;
+        let surplus = ReconcileReport::new(&self.account_info, self.vault_balance(vault)?).surplus();
+        if amount > surplus {
+            msg!("amount > surplus {}", surplus);
+            return Err(ProgramError::InsufficientFunds);
+        }
+
+        if let Some(treasury_authority) = self.account_info.treasury_authority() {
+            if authority.key != &treasury_authority {
+                msg!("not the treasury authority");
+                return Err(ProgramError::InvalidArgument);
+            }
+        } else {
+            if self.account_info.original_funder() != Some(*authority.key) {
+                msg!("not the original funder");
+                return Err(ProgramError::InvalidArgument);
+            }
+            let claim = match self.account_info.surplus_claim() {
+                Some(claim) => claim,
+                None => {
+                    msg!("surplus not claimed");
+                    return Err(ProgramError::InvalidArgument);
+                }
+            };
+            if self.timestamp < claim.claimable_at {
+                msg!("claimable at {}", claim.claimable_at);
+                return Err(ProgramError::InvalidArgument);
+            }
+            if amount > claim.amount {
+                msg!("amount > claimed {}", claim.amount);
+                return Err(ProgramError::InvalidArgument);
+            }
+            self.account_info.set_surplus_claim(if amount < claim.amount {
+                Some(SurplusClaim { amount: claim.amount - amount, ..claim })
+            } else {
+                None
+            });
+            self.modified = true;
+        }
+
+        self.transfer_from_vault(program_id, vault, mint, token_program, destination, authority.key, amount)?;
+        msg!("SurplusSwept {} {}", authority.key, amount);
+        Ok(())
+    }
+
+
+    /**
+        @notice Burn tokens the signer holds in the vault through their timelocks.
+            Unlocked tokens are burned as if they were transferred out, so the timelock's schedule is unchanged.
+            Locked tokens are burned by shrinking the timelock's total amount, which scales down what
+            unlocks over the rest of the schedule. The unlocked balance has to be transferred or burned
+            first as far as it would otherwise shrink with the total. Only locked tokens of timelocks nobody
+            can cancel are burned, the locked tokens of a cancelable timelock may still be reclaimed.
+        @param recipient the recipient of the timelocks, must sign
+        @param vault the PDA token account holding the locked tokens
+        @param mint the mint of the token lock account
+        @param tokenProgram the SPL Token or Token-2022 program owning the mint
+        @param amount of tokens to burn in base units
+        @param source whether unlocked or locked tokens are burned
+    */
+    pub fn process_burn<'b>(&mut self, program_id: &Pubkey, recipient: &AccountInfo<'b>, vault: &AccountInfo<'b>, mint: &AccountInfo<'b>, token_program: &AccountInfo<'b>, amount: u32, source: BurnSource) -> ProgramResult {
+        if !recipient.is_signer {
+            msg!("recipient must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if self.account_info.is_native() {
+            msg!("native SOL can't be burned");
+            return Err(ProgramError::InvalidArgument);
+        }
+        let bump = self.check_vault(program_id, vault, token_program)?;
+        self.check_mint(mint)?;
+
+        self.burn_from_timelocks(recipient.key, amount, source)?;
+
+        invoke_signed(
+            &token::burn(token_program.key, vault.key, mint.key, vault.key, amount as u64),
+            &[vault.clone(), mint.clone(), token_program.clone()],
+            &[&[TokenLockAccount::VAULT_SEED, self.account_key.as_ref(), &[bump]]],
+        )?;
+        msg!("Burned {} {}", recipient.key, amount);
+        Ok(())
+    }
+
+    /// Checks the vault is this token lock account's vault PDA owned by the token program, or by this
+    /// program for native SOL, and returns its bump seed.
+    fn check_vault(&self, program_id: &Pubkey, vault: &AccountInfo, token_program: &AccountInfo) -> Result<u8, ProgramError> {
+        let (vault_key, bump) = TokenLockAccount::find_vault(self.account_key, program_id);
+        if vault.key != &vault_key {
+            msg!("invalid vault");
+            return Err(ProgramError::InvalidSeeds);
+        }
+        let vault_owner = if self.account_info.is_native() {
+            if token_program.key != &system_program::id() {
+                msg!("invalid system program");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            program_id
+        } else {
+            token::check_token_program(token_program)?;
+            token_program.key
+        };
+        if vault.owner != vault_owner {
+            msg!("invalid vault owner");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Ok(bump)
+    }
+
+    fn check_mint(&self, mint: &AccountInfo) -> ProgramResult {
+        if mint.key != &self.account_info.mint {
+            msg!("invalid mint");
+            return Err(ProgramError::InvalidArgument);
+        }
+        Ok(())
+    }
+
+    /// The vault balance backing the timelocks. The rent-exempt minimum of a native SOL vault is not part of it.
+    fn vault_balance(&self, vault: &AccountInfo) -> Result<u64, ProgramError> {
+        if self.account_info.is_native() {
+            let rent_exempt = Rent::get()?.minimum_balance(vault.data_len());
+            return Ok(vault.lamports().saturating_sub(rent_exempt));
         }
+        Ok(token::unpack_account(&vault.data.borrow())?.amount)
+    }
+
+    /// Checks `destination` is a token account of `owner`, or the owner itself for native SOL.
+    fn check_destination(&self, destination: &AccountInfo, owner: &Pubkey) -> ProgramResult {
+        let destination_owner = if self.account_info.is_native() {
+            *destination.key
+        } else {
+            token::unpack_account(&destination.data.borrow())?.owner
+        };
+        if &destination_owner != owner {
+            msg!("invalid destination");
+            return Err(ProgramError::InvalidArgument);
+        }
+        Ok(())
+    }
+
+    /// Transfers `amount` from `source`, a token account of `funder`, into the vault, or lamports of the funder
+    /// for native SOL, and returns what the vault received.
+    fn transfer_to_vault<'b>(
+        &self,
+        program_id: &Pubkey,
+        funder: &AccountInfo<'b>,
+        source: &AccountInfo<'b>,
+        vault: &AccountInfo<'b>,
+        mint: &AccountInfo<'b>,
+        token_program: &AccountInfo<'b>,
+        amount: u64,
+    ) -> Result<u64, ProgramError> {
+        self.check_vault(program_id, vault, token_program)?;
+        self.check_mint(mint)?;
+
+        let balance_before = self.vault_balance(vault)?;
+        if self.account_info.is_native() {
+            invoke(
+                &system_instruction::transfer(funder.key, vault.key, amount),
+                &[funder.clone(), vault.clone(), token_program.clone()],
+            )?;
+        } else {
+            let decimals = token::unpack_mint(&mint.data.borrow())?.decimals;
+            invoke(
+                &token::transfer_checked(token_program.key, source.key, mint.key, vault.key, funder.key, amount, decimals),
+                &[source.clone(), mint.clone(), vault.clone(), funder.clone(), token_program.clone()],
+            )?;
+        }
+        Ok(self.vault_balance(vault)? - balance_before)
+    }
+
+    /// Pays `amount` out of the vault to `owner`, into `destination` which is a token account of the owner,
+    /// or the owner itself for native SOL.
+    fn transfer_from_vault<'b>(
+        &self,
+        program_id: &Pubkey,
+        vault: &AccountInfo<'b>,
+        mint: &AccountInfo<'b>,
+        token_program: &AccountInfo<'b>,
+        destination: &AccountInfo<'b>,
+        owner: &Pubkey,
+        amount: u64,
+    ) -> ProgramResult {
+        if amount == 0 {
+            return Ok(());
+        }
+        let bump = self.check_vault(program_id, vault, token_program)?;
+        self.check_mint(mint)?;
+
+        self.check_destination(destination, owner)?;
+        if self.account_info.is_native() {
+            if self.vault_balance(vault)? < amount {
+                msg!("vault balance < {}", amount);
+                return Err(ProgramError::InsufficientFunds);
+            }
+            **vault.try_borrow_mut_lamports()? -= amount;
+            **destination.try_borrow_mut_lamports()? += amount;
+            return Ok(());
+        }
+
+        let decimals = token::unpack_mint(&mint.data.borrow())?.decimals;
+        invoke_signed(
+            &token::transfer_checked(token_program.key, vault.key, mint.key, destination.key, vault.key, amount, decimals),
+            &[vault.clone(), mint.clone(), destination.clone(), token_program.clone()],
+            &[&[TokenLockAccount::VAULT_SEED, self.account_key.as_ref(), &[bump]]],
+        )
+    }
+
+    /**
+        @notice Transfers unlocked tokens of one of the sender's timelocks to an address.
+        @param owner the recipient of the timelock, must sign
+        @param vault the PDA holding the locked tokens
+        @param mint the mint of the token lock account
+        @param tokenProgram the token program owning the mint, the system program for native SOL
+        @param destination the token account of to, or to itself for native SOL
+        @param to the address the tokens are transferred to
+        @param value the amount of unlocked tokens to transfer
+        @param timelockIndex the index of the owner's timelock
+    */
+    pub fn process_transfer_timelock<'b>(
+        &mut self,
+        program_id: &Pubkey,
+        owner: &AccountInfo<'b>,
+        vault: &AccountInfo<'b>,
+        mint: &AccountInfo<'b>,
+        token_program: &AccountInfo<'b>,
+        destination: &AccountInfo<'b>,
+        to: &Pubkey,
+        value: u32,
+        timelock_index: u32,
+    ) -> ProgramResult {
+        if !owner.is_signer {
+            msg!("owner must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if self.account_info.get_timelock(owner.key, timelock_index as usize).is_none() {
+            msg!("invalid timelock");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if self.unlocked_balance_of_timelock(owner.key, timelock_index) < value {
+            msg!("amount > unlocked");
+            return Err(ProgramError::InsufficientFunds);
+        }
+
+        self.transfer_from_vault(program_id, vault, mint, token_program, destination, to, value as u64)?;
+        self.account_info.get_timelock_mut(owner.key, timelock_index as usize).unwrap().tokens_transferred += value;
+        self.modified = true;
+        // emit Transfer(msg.sender, to, value);
+        msg!("Transfer {} {} {}", owner.key, to, value);
+        Ok(())
+    }
+
+    /// Shrinks a timelock's total amount so its locked balance drops by `locked_amount` and its unlocked
+    /// balance stays the same, rebasing the tokens transferred so far onto the smaller total.
+    fn shrink_timelock(&self, timelock: &Timelock, locked_amount: u32) -> Result<Timelock, ProgramError> {
+        let unlocked = self.total_unlocked_to_date(timelock);
+        let locked = timelock.total_amount - unlocked;
+        // lowering the total by one lowers the locked balance by zero or one, so stepping by the
+        // difference from the proportional estimate converges on the exact reduction
+        let mut reduction = (locked_amount as u64 * timelock.total_amount as u64 / locked as u64) as u32;
+        let mut shrunk = *timelock;
+        for _ in 0..32 {
+            shrunk.total_amount = match timelock.total_amount.checked_sub(reduction) {
+                Some(total_amount) => total_amount,
+                None => {
+                    msg!("amount > locked");
+                    return Err(ProgramError::InsufficientFunds);
+                }
+            };
+            let unlocked_reduction = unlocked - self.total_unlocked_to_date(&shrunk);
+            let locked_reduction = reduction - unlocked_reduction;
+            if locked_reduction == locked_amount {
+                if unlocked_reduction > timelock.tokens_transferred {
+                    msg!("transfer or burn unlocked tokens before burning locked ones");
+                    return Err(ProgramError::InvalidArgument);
+                }
+                shrunk.tokens_transferred = timelock.tokens_transferred - unlocked_reduction;
+                return Ok(shrunk);
+            }
+            if locked_reduction < locked_amount {
+                reduction += locked_amount - locked_reduction;
+            } else {
+                reduction -= locked_reduction - locked_amount;
+            }
+        }
+        msg!("could not shrink timelock");
+        Err(ProgramError::InvalidArgument)
+    }
+
+    /// Takes a burn of `amount` out of `who`'s timelocks in order, failing if they don't hold enough.
+    /// Locked tokens are only taken out of timelocks without cancelers.
+    fn burn_from_timelocks(&mut self, who: &Pubkey, amount: u32, source: BurnSource) -> ProgramResult {
+        let timelock_indices: Vec<u32> = self.account_info.timelocks_of(who)
+            .filter(|(_, timelock)| source == BurnSource::Unlocked || timelock.cancelable_by().is_empty())
+            .map(|(timelock_index, _)| timelock_index as u32)
+            .collect();
+        let balance: u64 = timelock_indices.iter().map(|&timelock_index| match source {
+            BurnSource::Unlocked => self.unlocked_balance_of_timelock(who, timelock_index) as u64,
+            BurnSource::Locked => self.locked_balance_of_timelock(who, timelock_index) as u64,
+        }).sum();
+        if balance < amount as u64 {
+            msg!("burn amount exceeds balance");
+            return Err(ProgramError::InsufficientFunds);
+        }
+
+        let mut remaining = amount;
+        for timelock_index in timelock_indices {
+            if remaining == 0 {
+                break;
+            }
+            match source {
+                BurnSource::Unlocked => {
+                    let burned = remaining.min(self.unlocked_balance_of_timelock(who, timelock_index));
+                    let timelock = self.account_info.get_timelock_mut(who, timelock_index as usize).unwrap();
+                    timelock.tokens_transferred += burned;
+                    remaining -= burned;
+                }
+                BurnSource::Locked => {
+                    let burned = remaining.min(self.locked_balance_of_timelock(who, timelock_index));
+                    if burned == 0 {
+                        continue;
+                    }
+                    let timelock = self.account_info.get_timelock(who, timelock_index as usize).unwrap();
+                    let shrunk = self.shrink_timelock(timelock, burned)?;
+                    *self.account_info.get_timelock_mut(who, timelock_index as usize).unwrap() = shrunk;
+                    remaining -= burned;
+                }
+            }
+        }
+        self.modified = true;
+        Ok(())
+    }
+
+
+    /**
+        @notice Create a token lock account for a mint. Each mint can have several token lock accounts told apart
+            by their authority and a nonce, each with its own vault, release schedules and limits.
+            Token-2022 mints with a transfer hook, a permanent delegate or frozen new accounts are rejected,
+            see `token::unsupported_extension`.
+        @param tokenLock the token lock PDA derived from the mint, the payer and the nonce
+        @param payer pays for the new accounts and becomes the lockup's authority, must sign
+        @param mint the mint of the locked tokens, the native mint for native SOL
+        @param vault the vault PDA, created as a token account of the mint owned by itself,
+            or as a data-less account of this program for native SOL
+        @param tokenProgram the SPL Token or Token-2022 program owning the mint, the system program for native SOL
+        @param nonce tells apart token lock accounts of the same mint
+        @param maxReleaseDelay the longest a release may be delayed from funding
+        @param minTimelockAmount the smallest amount a timelock can be funded with
+        @param space bytes allocated for the token lock account, at most room for `TokenLockAccount::MAX_TIMELOCKS`.
+            The account can't be resized, so this fixes how many timelocks it can hold
+        @param treasuryAuthority may sweep surplus tokens out of the vault, without one the original funder has to claim them
+    */
+    pub fn process_initialize_token_lock<'b>(
+        program_id: &Pubkey,
+        token_lock: &AccountInfo<'b>,
+        payer: &AccountInfo<'b>,
+        mint: &AccountInfo<'b>,
+        vault: &AccountInfo<'b>,
+        system_program: &AccountInfo<'b>,
+        token_program: &AccountInfo<'b>,
+        rent_sysvar: &AccountInfo<'b>,
+        nonce: u8,
+        max_release_delay: u32,
+        min_timelock_amount: u32,
+        space: u32,
+        treasury_authority: Option<Pubkey>,
+    ) -> ProgramResult {
+        if !payer.is_signer {
+            msg!("payer must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let native = mint.key == &spl_token::native_mint::id();
+        if native {
+            if token_program.key != &system_program::id() {
+                msg!("invalid system program");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+        } else {
+            token::check_token_program(token_program)?;
+            if mint.owner != token_program.key {
+                msg!("mint not owned by token program");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+            if let Some(reason) = token::unsupported_extension(&mint.data.borrow()) {
+                msg!(reason);
+                return Err(ProgramError::InvalidArgument);
+            }
+        }
+        let (token_lock_key, token_lock_bump) = TokenLockAccount::find_address(mint.key, payer.key, nonce, program_id);
+        if token_lock.key != &token_lock_key {
+            msg!("invalid token lock address");
+            return Err(ProgramError::InvalidSeeds);
+        }
+        let (vault_key, vault_bump) = TokenLockAccount::find_vault(token_lock.key, program_id);
+        if vault.key != &vault_key {
+            msg!("invalid vault");
+            return Err(ProgramError::InvalidSeeds);
+        }
+        if token_lock.owner == program_id {
+            msg!("token lock already exists");
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+
+        if (space as usize) < TokenLockAccount::space(1) {
+            msg!("space < {}", TokenLockAccount::space(1));
+            return Err(ProgramError::InvalidArgument);
+        }
+        // the runtime caps the data of accounts created by a program and can't resize them
+        if space as usize > MAX_PERMITTED_DATA_INCREASE {
+            msg!("space > {}, room for {} timelocks", MAX_PERMITTED_DATA_INCREASE, TokenLockAccount::MAX_TIMELOCKS);
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        Self::create_pda(
+            payer,
+            token_lock,
+            space as usize,
+            program_id,
+            system_program,
+            &[TokenLockAccount::SEED, mint.key.as_ref(), payer.key.as_ref(), &[nonce], &[token_lock_bump]],
+        )?;
+        TokenLockAccount::init(&mut token_lock.data.borrow_mut(), mint.key, payer.key, nonce, max_release_delay, min_timelock_amount, treasury_authority)?;
+
+        if native {
+            Self::create_pda(payer, vault, 0, program_id, system_program, &[TokenLockAccount::VAULT_SEED, token_lock.key.as_ref(), &[vault_bump]])?;
+            msg!("TokenLockInitialized {} {} {}", token_lock.key, mint.key, nonce);
+            return Ok(());
+        }
+
+        let vault_len = token::account_len(&mint.data.borrow());
+        Self::create_pda(payer, vault, vault_len, token_program.key, system_program, &[TokenLockAccount::VAULT_SEED, token_lock.key.as_ref(), &[vault_bump]])?;
+        invoke(
+            &token::initialize_account(token_program.key, vault.key, mint.key, vault.key),
+            &[vault.clone(), mint.clone(), vault.clone(), rent_sysvar.clone(), token_program.clone()],
+        )?;
+
+        msg!("TokenLockInitialized {} {} {}", token_lock.key, mint.key, nonce);
+        Ok(())
+    }
+
+
+    /**
+        @notice Create a fixed supply token. The whole supply is minted to the treasury and the mint
+            authority is removed in the same instruction, the mint is created without a freeze authority.
+        @param payer pays for the new accounts, must sign
+        @param mint the new mint account, must sign
+        @param treasury the new token account receiving the supply, must sign
+        @param treasuryOwner the owner of the treasury token account
+        @param mintAuthority the PDA that is the mint authority until the supply is minted
+        @param metadata the PDA storing the token metadata
+        @param decimals of the token
+        @param totalSupply the number of tokens ever minted in base units
+        @param tokenMetadata name, symbol and uri of the token
+    */
+    pub fn process_initialize_token<'b>(
+        program_id: &Pubkey,
+        payer: &AccountInfo<'b>,
+        mint: &AccountInfo<'b>,
+        treasury: &AccountInfo<'b>,
+        treasury_owner: &AccountInfo<'b>,
+        mint_authority: &AccountInfo<'b>,
+        metadata: &AccountInfo<'b>,
+        system_program: &AccountInfo<'b>,
+        token_program: &AccountInfo<'b>,
+        rent_sysvar: &AccountInfo<'b>,
+        decimals: u8,
+        total_supply: u64,
+        token_metadata: TokenMetadata,
+    ) -> ProgramResult {
+        if !payer.is_signer || !mint.is_signer || !treasury.is_signer {
+            msg!("payer, mint and treasury must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if token_program.key != &spl_token::id() {
+            msg!("invalid token program");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if total_supply == 0 {
+            msg!("total supply = 0");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if token_metadata.name.len() > TokenMetadata::MAX_NAME_LENGTH
+            || token_metadata.symbol.len() > TokenMetadata::MAX_SYMBOL_LENGTH
+            || token_metadata.uri.len() > TokenMetadata::MAX_URI_LENGTH {
+            msg!("metadata too long");
+            return Err(ProgramError::InvalidArgument);
+        }
+        let (mint_authority_key, mint_authority_bump) = TokenMetadata::find_mint_authority(mint.key, program_id);
+        if mint_authority.key != &mint_authority_key {
+            msg!("invalid mint authority");
+            return Err(ProgramError::InvalidSeeds);
+        }
+        let (metadata_key, metadata_bump) = TokenMetadata::find_address(mint.key, program_id);
+        if metadata.key != &metadata_key {
+            msg!("invalid metadata address");
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        let rent = Rent::get()?;
+        invoke(
+            &system_instruction::create_account(
+                payer.key,
+                mint.key,
+                rent.minimum_balance(spl_token::state::Mint::LEN),
+                spl_token::state::Mint::LEN as u64,
+                token_program.key,
+            ),
+            &[payer.clone(), mint.clone(), system_program.clone()],
+        )?;
+        invoke(
+            &spl_token::instruction::initialize_mint(token_program.key, mint.key, mint_authority.key, None, decimals)?,
+            &[mint.clone(), rent_sysvar.clone(), token_program.clone()],
+        )?;
+
+        invoke(
+            &system_instruction::create_account(
+                payer.key,
+                treasury.key,
+                rent.minimum_balance(spl_token::state::Account::LEN),
+                spl_token::state::Account::LEN as u64,
+                token_program.key,
+            ),
+            &[payer.clone(), treasury.clone(), system_program.clone()],
+        )?;
+        invoke(
+            &spl_token::instruction::initialize_account(token_program.key, treasury.key, mint.key, treasury_owner.key)?,
+            &[treasury.clone(), mint.clone(), treasury_owner.clone(), rent_sysvar.clone(), token_program.clone()],
+        )?;
+
+        let mint_authority_seeds: &[&[u8]] = &[TokenMetadata::MINT_AUTHORITY_SEED, mint.key.as_ref(), &[mint_authority_bump]];
+        invoke_signed(
+            &spl_token::instruction::mint_to(token_program.key, mint.key, treasury.key, mint_authority.key, &[], total_supply)?,
+            &[mint.clone(), treasury.clone(), mint_authority.clone(), token_program.clone()],
+            &[mint_authority_seeds],
+        )?;
+        invoke_signed(
+            &spl_token::instruction::set_authority(
+                token_program.key,
+                mint.key,
+                None,
+                spl_token::instruction::AuthorityType::MintTokens,
+                mint_authority.key,
+                &[],
+            )?,
+            &[mint.clone(), mint_authority.clone(), token_program.clone()],
+            &[mint_authority_seeds],
+        )?;
+
+        let data = token_metadata.try_to_vec()?;
+        Self::create_pda(payer, metadata, data.len(), program_id, system_program, &[TokenMetadata::SEED, mint.key.as_ref(), &[metadata_bump]])?;
+        metadata.data.borrow_mut().copy_from_slice(&data);
+
+        msg!("TokenInitialized {} {} {}", mint.key, treasury.key, total_supply);
+        Ok(())
+    }
+
+
+    /**
+        @notice Move a timelock to a new recipient wallet, e.g. when the current wallet is compromised or rotated.
+            The tokens transferred so far move with the timelock, which keeps its index.
+            If the timelock is cancelable one of its cancelers must co-sign.
+            A cancel proposal still open is keyed to the current owner, so it is closed and its rent returned to the proposer.
+        @param owner the current recipient of the timelock, must sign
+        @param proposal the cancel proposal PDA of the timelock
+        @param accounts the proposer if the proposal is open, then signer accounts checked against the cancelableBy list
+        @param timelockIndex the index of the timelock belonging to the owner
+        @param newOwner the recipient the timelock is moved to
+    */
+    pub fn process_transfer_timelock_ownership(&mut self, program_id: &Pubkey, owner: &AccountInfo, proposal: &AccountInfo, accounts: &[AccountInfo], timelock_index: u32, new_owner: &Pubkey) -> ProgramResult {
+        if !owner.is_signer {
+            msg!("owner must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if owner.key == new_owner {
+            msg!("new owner is the current owner");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let timelock;
+        if let Some(tmlock) = self.account_info.get_timelock(owner.key, timelock_index as usize){
+            timelock = tmlock;
+        } else {
+            msg!("invalid timelock");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if timelock.total_amount <= timelock.tokens_transferred {
+            msg!("Timelock has no value left");
+            return Err(ProgramError::InvalidArgument);
+        }
+        let (proposal_key, _) = CancelProposal::find_address(self.account_key, owner.key, timelock_index, program_id);
+        if proposal.key != &proposal_key {
+            msg!("invalid proposal address");
+            return Err(ProgramError::InvalidSeeds);
+        }
+        let (open_proposal, cosigners) = if proposal.owner == program_id && proposal.lamports() > 0 {
+            let cancel_proposal = self.load_cancel_proposal(program_id, proposal)?;
+            match accounts.split_first() {
+                Some((proposer, cosigners)) if proposer.key == &cancel_proposal.proposer => (Some((cancel_proposal, proposer)), cosigners),
+                _ => {
+                    msg!("invalid proposer");
+                    return Err(ProgramError::InvalidArgument);
+                }
+            }
+        } else {
+            (None, accounts)
+        };
+        Self::check_canceler_cosigned(timelock, cosigners)?;
+
+        if let Some((cancel_proposal, proposer)) = open_proposal {
+            Self::close_cancel_proposal(proposal, proposer);
+            msg!("CancelProposalClosed {} {} {}", proposer.key, cancel_proposal.target, cancel_proposal.timelock_index);
+        }
+        self.account_info.get_timelock_mut(owner.key, timelock_index as usize).unwrap().recipient = *new_owner;
+        self.modified = true;
+        msg!("TimelockOwnershipTransferred {} {} {}", owner.key, timelock_index, new_owner);
+        Ok(())
+    }
+
+
+    /**
+        @notice Carve part of a timelock off into a new timelock for another (or the same) recipient.
+            The new timelock keeps the schedule, commencement and cancelers of the original and takes
+            a proportional share of the tokens already transferred.
+            Fails if rounding would decrease the amount unlocked across both timelocks.
+            If the timelock is cancelable and the recipient changes one of its cancelers must co-sign.
+        @param owner the current recipient of the timelock, must sign
+        @param cosigners signer accounts checked against the cancelableBy list
+        @param timelockIndex the index of the timelock belonging to the owner
+        @param amount the total amount of the new timelock
+        @param to the recipient of the new timelock
+    */
+    pub fn process_split_timelock(&mut self, owner: &AccountInfo, cosigners: &[AccountInfo], timelock_index: u32, amount: u32, to: &Pubkey) -> ProgramResult {
+        if !owner.is_signer {
+            msg!("owner must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        let timelock;
+        if let Some(tmlock) = self.account_info.get_timelock(owner.key, timelock_index as usize){
+            timelock = *tmlock;
+        } else {
+            msg!("invalid timelock");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if amount == 0 || amount >= timelock.total_amount {
+            msg!("split amount out of range");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if to != owner.key {
+            Self::check_canceler_cosigned(&timelock, cosigners)?;
+        }
+
+        let split_transferred = (timelock.tokens_transferred as u64 * amount as u64 / timelock.total_amount as u64) as u32;
+        let mut split = timelock;
+        split.total_amount = amount;
+        split.tokens_transferred = split_transferred;
+        let mut rest = timelock;
+        rest.total_amount -= amount;
+        rest.tokens_transferred -= split_transferred;
+
+        let release_count = self.account_info.release_schedules()[timelock.schedule_id as usize].release_count;
+        for part in [&split, &rest].iter() {
+            if part.total_amount < self.account_info.min_timelock_amount {
+                msg!("amount < min funding");
+                return Err(ProgramError::InvalidArgument);
+            }
+            if part.total_amount < release_count {
+                msg!("< 1 token per release");
+                return Err(ProgramError::InvalidArgument);
+            }
+        }
+
+        let unlocked_before = self.total_unlocked_to_date(&timelock) - timelock.tokens_transferred;
+        let split_unlocked = self.total_unlocked_to_date(&split);
+        let rest_unlocked = self.total_unlocked_to_date(&rest);
+        if split_unlocked < split.tokens_transferred || rest_unlocked < rest.tokens_transferred
+            || (split_unlocked - split.tokens_transferred) + (rest_unlocked - rest.tokens_transferred) < unlocked_before {
+            msg!("split would decrease unlocked balance");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let split_timelock_index = self.account_info.add_timelock(to, split).map_err(|err| {
+            msg!("no room for another timelock");
+            err
+        })?;
+        *self.account_info.get_timelock_mut(owner.key, timelock_index as usize).unwrap() = rest;
+        self.modified = true;
+        msg!("TimelockSplit {} {} {}", owner.key, timelock_index, amount);
+        msg!("new timelock {} {}", to, split_timelock_index);
+        Ok(())
+    }
+
+
+    /**
+        @notice Merge one of the owner's timelocks into another one with the same release schedule,
+            commencement and cancelers. The merged slot is left empty so indices don't change.
+        @param owner the recipient of both timelocks, must sign
+        @param timelockIndex the index of the timelock that is kept
+        @param otherTimelockIndex the index of the timelock merged into it
+    */
+    pub fn process_merge_timelocks(&mut self, owner: &AccountInfo, timelock_index: u32, other_timelock_index: u32) -> ProgramResult {
+        if !owner.is_signer {
+            msg!("owner must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if timelock_index == other_timelock_index {
+            msg!("cannot merge a timelock with itself");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let (timelock, other);
+        match (self.account_info.get_timelock(owner.key, timelock_index as usize), self.account_info.get_timelock(owner.key, other_timelock_index as usize)) {
+            (Some(a), Some(b)) => {
+                timelock = *a;
+                other = *b;
+            }
+            _ => {
+                msg!("invalid timelock");
+                return Err(ProgramError::InvalidArgument);
+            }
+        }
+        if timelock.schedule_id != other.schedule_id
+            || timelock.commencement_timestamp != other.commencement_timestamp
+            || timelock.vesting_stopped_at != other.vesting_stopped_at
+            || timelock.cancel_threshold != other.cancel_threshold
+            || timelock.cancelable_by().len() != other.cancelable_by().len()
+            || !other.cancelable_by().iter().all(|canceler| timelock.has_cancelable_by(canceler)) {
+            msg!("timelocks are not identical");
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        let mut merged = timelock;
+        merged.total_amount = timelock.total_amount.checked_add(other.total_amount).ok_or(ProgramError::InvalidArgument)?;
+        merged.tokens_transferred = timelock.tokens_transferred + other.tokens_transferred;
 
-        let canceled_amount = Self::locked_balance_of_timelock(self, target, timelock_index);
-        if canceled_amount == 0{
-            msg!("Timelock has no value left");
-            return false;
+        let unlocked_before = (self.total_unlocked_to_date(&timelock) - timelock.tokens_transferred)
+            + (self.total_unlocked_to_date(&other) - other.tokens_transferred);
+        if self.total_unlocked_to_date(&merged) - merged.tokens_transferred < unlocked_before {
+            msg!("merge would decrease unlocked balance");
+            return Err(ProgramError::InvalidArgument);
         }
 
-        let paid_amount = self.unlocked_balance_of_timelock(target, timelock_index);
+        self.account_info.take_timelock(owner.key, other_timelock_index as usize);
+        *self.account_info.get_timelock_mut(owner.key, timelock_index as usize).unwrap() = merged;
+        self.modified = true;
+        msg!("TimelocksMerged {} {} {}", owner.key, timelock_index, other_timelock_index);
+        Ok(())
+    }
 
-        // token.safeTransfer(reclaim_token_to, canceled_amount);
-        // token.safeTransfer(target, paid_amount);
-        // emit TimelockCanceled(msg.sender, target, timelockIndex, reclaimTokenTo, canceledAmount, paidAmount);
 
-        timelock.tokens_transferred = timelock.total_amount;
-        return true;
-    }    
+    /// Checks that at least the cancel threshold of distinct cancelers signed, at least one if there is no threshold.
+    fn check_canceler_quorum(timelock: &Timelock, signers: &[AccountInfo]) -> ProgramResult {
+        let mut approvals: Vec<&Pubkey> = signers.iter()
+            .filter(|signer| signer.is_signer && timelock.has_cancelable_by(signer.key))
+            .map(|signer| signer.key)
+            .collect();
+        approvals.sort();
+        approvals.dedup();
+        if approvals.len() < timelock.cancel_threshold.max(1) as usize {
+            msg!("{} of {} canceler signatures", approvals.len(), timelock.cancel_threshold.max(1));
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        Ok(())
+    }
+
+
+    /// Checks that a canceler of a cancelable timelock is among the signing cosigners.
+    fn check_canceler_cosigned(timelock: &Timelock, cosigners: &[AccountInfo]) -> ProgramResult {
+        if !timelock.cancelable_by().is_empty() {
+            let approved = cosigners.iter().any(|cosigner| cosigner.is_signer && timelock.has_cancelable_by(cosigner.key));
+            if !approved {
+                msg!("a canceler must co-sign");
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+        }
+        Ok(())
+    }
 
 
     /**
@@ -192,7 +1524,7 @@ impl<'a> Processor<'a> {
         @param timelockIndex for a specific timelock belonging to the who address
         @return unlocked balance of the timelock
     */
-    fn unlocked_balance_of_timelock(&self, who: &Pubkey, timelock_index: u32) ->u32 {
+    pub fn unlocked_balance_of_timelock(&self, who: &Pubkey, timelock_index: u32) ->u32 {
         if let Some(timelock) = self.account_info.get_timelock(who, timelock_index as usize){
             if timelock.total_amount <= timelock.tokens_transferred {
                 return 0;
@@ -210,8 +1542,8 @@ impl<'a> Processor<'a> {
         @param timelockIndex Specific timelock belonging to the who address
         @return locked Balance of the timelock
     */
-    pub fn locked_balance_of_timelock(&mut self, who: &Pubkey, timelock_index: u32) ->u32 {
-        if let Some(timelock) = self.account_info.get_timelock_mut(who, timelock_index as usize) {
+    pub fn locked_balance_of_timelock(&self, who: &Pubkey, timelock_index: u32) ->u32 {
+        if let Some(timelock) = self.account_info.get_timelock(who, timelock_index as usize) {
             if timelock.total_amount <= timelock.tokens_transferred {
                 return 0;
             } else {
@@ -221,6 +1553,37 @@ impl<'a> Processor<'a> {
         return 0;
     }
     
+    /**
+        @notice Get the total unlocked balance of an address across all of its timelocks
+        @param who Address to calculate
+        @return amount of unlocked timelock tokens
+    */
+    pub fn unlocked_balance_of(&self, who: &Pubkey) -> u64 {
+        self.account_info.timelocks_of(who)
+            .map(|(idx, _)| self.unlocked_balance_of_timelock(who, idx as u32) as u64)
+            .sum()
+    }
+
+    /**
+        @notice Get the total locked balance of an address across all of its timelocks
+        @param who Address to calculate
+        @return amount of locked timelock tokens
+    */
+    pub fn locked_balance_of(&self, who: &Pubkey) -> u64 {
+        self.account_info.timelocks_of(who)
+            .map(|(idx, _)| self.locked_balance_of_timelock(who, idx as u32) as u64)
+            .sum()
+    }
+
+    /**
+        @notice Get the total locked and unlocked balance of an address across all of its timelocks
+        @param who Address to calculate
+        @return total locked and unlocked balance
+    */
+    pub fn balance_of(&self, who: &Pubkey) -> u64 {
+        self.unlocked_balance_of(who) + self.locked_balance_of(who)
+    }
+
     /**
         @notice Gets the total locked and unlocked balance of a specific address's timelocks
         @param who The address to check
@@ -228,13 +1591,30 @@ impl<'a> Processor<'a> {
         @return total Locked and unlocked amount for the specified timelock
     */
     fn total_unlocked_to_date_of_timelock(&self, who: &Pubkey, timelock_index: u32) ->u32 {
-        //let timelock = Self::timelock_of(account, who, timelock_index);
-        // return calculate_unlocked(
-        //     timelock.commencement_timestamp,
-        //     block.timestamp,
-        //     timelock.total_amount,
-        //     timelock.schedule_id
-        // );
+        if let Some(timelock) = self.account_info.get_timelock(who, timelock_index as usize) {
+            return self.total_unlocked_to_date(timelock);
+        }
+        return 0;
+    }
+
+    /**
+        @notice Same as above for a timelock that is not necessarily stored in the account yet
+        @param timelock The timelock to calculate for at the current block timestamp
+        @return total Locked and unlocked amount for the timelock
+    */
+    fn total_unlocked_to_date(&self, timelock: &Timelock) ->u32 {
+        // the total of a timelock whose vesting was stopped was cut down to what had unlocked
+        if timelock.vesting_stopped_at != 0 {
+            return timelock.total_amount;
+        }
+        if let Some(release_schedule) = self.account_info.release_schedules().get(timelock.schedule_id as usize) {
+            return Self::calculate_unlocked(
+                timelock.commencement_timestamp,
+                self.timestamp,
+                timelock.total_amount,
+                release_schedule
+            );
+        }
         return 0;
     }
 
@@ -249,8 +1629,8 @@ impl<'a> Processor<'a> {
         @param releaseSchedule a ReleaseSchedule struct used to calculate the unlocked amount
         @return unlocked the total amount unlocked for the schedule given the other parameters
     */
-    fn calculate_unlocked(&mut self, commenced_timestamp: u32, current_timestamp: u32, amount: u32, release_schedule: &ReleaseSchedule) -> u32{
-        return self.calculate_unlocked_0(
+    pub fn calculate_unlocked(commenced_timestamp: u32, current_timestamp: u32, amount: u32, release_schedule: &ReleaseSchedule) -> u32{
+        return Self::calculate_unlocked_0(
             commenced_timestamp,
             current_timestamp,
             amount,
@@ -272,8 +1652,7 @@ impl<'a> Processor<'a> {
         @param periodBetweenReleasesInSeconds After the delay and initial release
         @return unlocked the total amount unlocked for the schedule given the other parameters
     */
-    fn calculate_unlocked_0(
-        &mut self, 
+    pub fn calculate_unlocked_0(
         commenced_timestamp: u32, 
         current_timestamp: u32, 
         amount: u32,
@@ -285,20 +1664,25 @@ impl<'a> Processor<'a> {
         if commenced_timestamp > current_timestamp {
             return 0;
         }
-        let seconds_elapsed = current_timestamp - commenced_timestamp;
+        // widened like the uint256 math of the Solidity contract, none of the products below overflow a u64
+        let seconds_elapsed = (current_timestamp - commenced_timestamp) as u64;
+        let amount = amount as u64;
+        let release_count = release_count as u64;
+        let delay_until_first_release_in_seconds = delay_until_first_release_in_seconds as u64;
+        let period_between_releases_in_seconds = period_between_releases_in_seconds as u64;
 
         // return the full amount if the total lockup period has expired
         // unlocked amounts in each period are truncated and round down remainders smaller than the smallest unit
         // unlocking the full amount unlocks any remainder amounts in the final unlock period
         // this is done first to reduce computation
-        if seconds_elapsed >= delay_until_first_release_in_seconds + (period_between_releases_in_seconds * (release_count - 1)){
-            return amount;
+        if seconds_elapsed >= delay_until_first_release_in_seconds + (period_between_releases_in_seconds * release_count.saturating_sub(1)){
+            return amount as u32;
         }
 
         let mut unlocked = 0;
         // unlock the initial release if the delay has elapsed
         if seconds_elapsed >= delay_until_first_release_in_seconds {
-            unlocked = (amount * initial_release_portion_in_bips) / Self::BIPS_PRECISION;
+            unlocked = (amount * initial_release_portion_in_bips as u64) / Self::BIPS_PRECISION as u64;
 
             // if at least one period after the delay has passed
             if seconds_elapsed - delay_until_first_release_in_seconds >= period_between_releases_in_seconds {
@@ -314,10 +1698,135 @@ impl<'a> Processor<'a> {
                 unlocked += ((amount - unlocked) * additional_unlocked_periods) / (release_count - 1);
             }
         }
-        return unlocked;
+        return unlocked as u32;
+    }
+
+
+
+    /**
+        @notice Moves a token lock account stored in an older layout into this one, which the same authority
+            initialized and has no release schedules or timelocks yet, and closes the old account. The vault
+            receives the tokens the timelocks still hold from the authority, since older layouts had no vault.
+            Timelocks of version 0 accounts were indexed per recipient and get new indices, see
+            `v0::TokenLockAccount::timelocks_in_migration_order`.
+        @param authority the authority of this token lock account and base of the old account's address, must sign
+        @param oldAccount the account stored in the older layout, its rent goes to the authority
+        @param source the authority's token account, unused for native SOL
+        @param vault the PDA holding the locked tokens
+        @param mint the mint of the token lock account
+        @param tokenProgram the token program owning the mint, the system program for native SOL
+        @param fromVersion the layout version the old account is stored in
+        @param seed the seed the old account's address was created with from the authority
+    */
+    pub fn process_migrate<'b>(
+        &mut self,
+        program_id: &Pubkey,
+        authority: &AccountInfo<'b>,
+        old_account: &AccountInfo<'b>,
+        source: &AccountInfo<'b>,
+        vault: &AccountInfo<'b>,
+        mint: &AccountInfo<'b>,
+        token_program: &AccountInfo<'b>,
+        from_version: u8,
+        seed: &str,
+    ) -> ProgramResult {
+        if !authority.is_signer {
+            msg!("authority must sign");
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        if authority.key != &self.account_info.authority() {
+            msg!("not the lockup's authority");
+            return Err(ProgramError::InvalidArgument);
+        }
+        if old_account.owner != program_id {
+            msg!("Old account does not have the correct program id");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if Pubkey::create_with_seed(authority.key, seed, program_id) != Ok(*old_account.key) {
+            msg!("invalid old account address");
+            return Err(ProgramError::InvalidSeeds);
+        }
+        let stored_version = migration::stored_version(&old_account.data.borrow());
+        if stored_version != from_version {
+            msg!("account is stored in version {}", stored_version);
+            return Err(ProgramError::InvalidArgument);
+        }
+        let old = match from_version {
+            0 => migration::v0::TokenLockAccount::unpack(&old_account.data.borrow())?,
+            _ => {
+                msg!("no migration from version {}", from_version);
+                return Err(ProgramError::InvalidArgument);
+            }
+        };
+
+        old.migrate(&mut self.account_info)?;
+        self.modified = true;
+        let outstanding = old.outstanding();
+        if outstanding > 0 {
+            let received = self.transfer_to_vault(program_id, authority, source, vault, mint, token_program, outstanding)?;
+            if received < outstanding {
+                msg!("the vault received {} of the {} the timelocks hold", received, outstanding);
+                return Err(ProgramError::InsufficientFunds);
+            }
+            self.account_info.set_original_funder(authority.key);
+        }
+
+        let lamports = old_account.lamports();
+        **old_account.lamports.borrow_mut() = 0;
+        **authority.lamports.borrow_mut() += lamports;
+        old_account.data.borrow_mut().fill(0);
+
+        for (idx, (recipient, old_idx, _)) in old.timelocks_in_migration_order().iter().enumerate() {
+            msg!("TimelockMigrated {} {} {}", recipient, old_idx, idx);
+        }
+        msg!("TokenLockMigrated {} {} {}", self.account_key, from_version, TokenLockAccount::VERSION);
+        Ok(())
     }
 
+    /// Reads the token lock account, checking it is a token lock PDA owned by the program.
+    fn load_token_lock<'d>(program_id: &Pubkey, account: &AccountInfo, data: &'d mut [u8]) -> Result<TokenLockAccount<'d>, ProgramError> {
+        if account.owner != program_id {
+            msg!("Token lock account does not have the correct program id");
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let account_info = TokenLockAccount::load_mut(data)?;
+        let (token_lock_key, _) = TokenLockAccount::find_address(&account_info.mint, &account_info.authority(), account_info.nonce, program_id);
+        if account.key != &token_lock_key {
+            msg!("invalid token lock address");
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(account_info)
+    }
 
+    /// Creates a PDA owned by `owner`, signing with its seeds. Anyone can send lamports to the address
+    /// beforehand, which would make `create_account` fail, so such an account is topped up to rent
+    /// exemption and allocated and assigned instead.
+    fn create_pda<'b>(payer: &AccountInfo<'b>, account: &AccountInfo<'b>, space: usize, owner: &Pubkey, system_program: &AccountInfo<'b>, seeds: &[&[u8]]) -> ProgramResult {
+        let lamports = Rent::get()?.minimum_balance(space);
+        if account.lamports() == 0 {
+            return invoke_signed(
+                &system_instruction::create_account(payer.key, account.key, lamports, space as u64, owner),
+                &[payer.clone(), account.clone(), system_program.clone()],
+                &[seeds],
+            );
+        }
+        if account.lamports() < lamports {
+            invoke(
+                &system_instruction::transfer(payer.key, account.key, lamports - account.lamports()),
+                &[payer.clone(), account.clone(), system_program.clone()],
+            )?;
+        }
+        invoke_signed(
+            &system_instruction::allocate(account.key, space as u64),
+            &[account.clone(), system_program.clone()],
+            &[seeds],
+        )?;
+        invoke_signed(
+            &system_instruction::assign(account.key, owner),
+            &[account.clone(), system_program.clone()],
+            &[seeds],
+        )
+    }
 
     pub fn process_instruction(
         program_id: &Pubkey,
@@ -328,39 +1837,1091 @@ impl<'a> Processor<'a> {
         let account = next_account_info(accounts_iter)?;    
 
         let instruction =  TokenLockInstruction::try_from_slice(input).or(Err(ProgramError::InvalidInstructionData))?;
+
         match instruction {
             TokenLockInstruction::Greeting => {
-                let mut account_info = TokenLockAccount::try_from_slice(&account.data.borrow()).unwrap();
-                let processor = Processor{
+                let mut data = account.data.borrow_mut();
+                let mut processor = Processor{
                     //account: &account,
                     account_key: account.key,
-                    account_info: &mut account_info,
-                    modified: false
-                };                    
-                let res = processor.process_greeting(program_id);
-                if processor.modified == true {
-                    account_info.serialize(&mut &mut account.data.borrow_mut()[..])?
-                }
-                return res;
+                    account_info: Self::load_token_lock(program_id, account, &mut data)?,
+                    modified: false,
+                    timestamp: Clock::get()?.unix_timestamp as u32,
+                };
+                return processor.process_greeting();
             }
             TokenLockInstruction::CreateReleaseSchedule(release_count, delay_until_first_release_in_seconds, initial_release_portion_in_bips, period_between_releases_in_seconds) => {
-                // if let [account, ..] = accounts {
-                //     let mut account_info = TokenLockAccount::try_from_slice(&account.data.borrow()).unwrap();
-                //     let mut processor = Processor{
-                //         account: &account,
-                //         account_info: &account_info,
-                //         modified: false
-                //     };                    
-                //     processor.process_create_release_schedule(program_id, account, release_count, delay_until_first_release_in_seconds, initial_release_portion_in_bips, period_between_releases_in_seconds);
-                //     if processor.modified==true {
-                //         account_info.serialize(&mut &mut account.data.borrow_mut()[..])?;
-                //     }
-                // } else {
-                //     Err(ProgramError::NotEnoughAccountKeys)
-                // }
-                Ok(())
-            }            
+                let authority = next_account_info(accounts_iter)?;
+                let mut data = account.data.borrow_mut();
+                let mut processor = Processor{
+                    account_key: account.key,
+                    account_info: Self::load_token_lock(program_id, account, &mut data)?,
+                    modified: false,
+                    timestamp: Clock::get()?.unix_timestamp as u32,
+                };
+                return processor.process_create_release_schedule(authority, release_count, delay_until_first_release_in_seconds, initial_release_portion_in_bips, period_between_releases_in_seconds);
+            }
+            TokenLockInstruction::TransferTimelockOwnership { timelock_index, new_owner } => {
+                let owner = next_account_info(accounts_iter)?;
+                let proposal = next_account_info(accounts_iter)?;
+                let accounts = accounts_iter.as_slice();
+                let mut data = account.data.borrow_mut();
+                let mut processor = Processor{
+                    account_key: account.key,
+                    account_info: Self::load_token_lock(program_id, account, &mut data)?,
+                    modified: false,
+                    timestamp: Clock::get()?.unix_timestamp as u32,
+                };
+                return processor.process_transfer_timelock_ownership(program_id, owner, proposal, accounts, timelock_index, &new_owner);
+            }
+            TokenLockInstruction::CancelTimelock { target, timelock_index, reclaim_token_to, mode } => {
+                let canceler = next_account_info(accounts_iter)?;
+                let vault = next_account_info(accounts_iter)?;
+                let mint = next_account_info(accounts_iter)?;
+                let token_program = next_account_info(accounts_iter)?;
+                let reclaim_destination = next_account_info(accounts_iter)?;
+                let target_destination = next_account_info(accounts_iter)?;
+                let mut data = account.data.borrow_mut();
+                let mut processor = Processor{
+                    account_key: account.key,
+                    account_info: Self::load_token_lock(program_id, account, &mut data)?,
+                    modified: false,
+                    timestamp: Clock::get()?.unix_timestamp as u32,
+                };
+                return processor.process_cancel_timelock(program_id, canceler, vault, mint, token_program, reclaim_destination, target_destination, &target, timelock_index, &reclaim_token_to, mode);
+            }
+            TokenLockInstruction::ProposeCancel { target, timelock_index, reclaim_token_to, mode } => {
+                let canceler = next_account_info(accounts_iter)?;
+                let proposal = next_account_info(accounts_iter)?;
+                let system_program = next_account_info(accounts_iter)?;
+                let mut data = account.data.borrow_mut();
+                let mut processor = Processor{
+                    account_key: account.key,
+                    account_info: Self::load_token_lock(program_id, account, &mut data)?,
+                    modified: false,
+                    timestamp: Clock::get()?.unix_timestamp as u32,
+                };
+                return processor.process_propose_cancel(program_id, canceler, proposal, system_program, &target, timelock_index, &reclaim_token_to, mode);
+            }
+            TokenLockInstruction::ApproveCancel => {
+                let canceler = next_account_info(accounts_iter)?;
+                let proposal = next_account_info(accounts_iter)?;
+                let mut data = account.data.borrow_mut();
+                let mut processor = Processor{
+                    account_key: account.key,
+                    account_info: Self::load_token_lock(program_id, account, &mut data)?,
+                    modified: false,
+                    timestamp: Clock::get()?.unix_timestamp as u32,
+                };
+                return processor.process_approve_cancel(program_id, canceler, proposal);
+            }
+            TokenLockInstruction::ExecuteCancel => {
+                let executor = next_account_info(accounts_iter)?;
+                let proposal = next_account_info(accounts_iter)?;
+                let proposer = next_account_info(accounts_iter)?;
+                let vault = next_account_info(accounts_iter)?;
+                let mint = next_account_info(accounts_iter)?;
+                let token_program = next_account_info(accounts_iter)?;
+                let reclaim_destination = next_account_info(accounts_iter)?;
+                let target_destination = next_account_info(accounts_iter)?;
+                let mut data = account.data.borrow_mut();
+                let mut processor = Processor{
+                    account_key: account.key,
+                    account_info: Self::load_token_lock(program_id, account, &mut data)?,
+                    modified: false,
+                    timestamp: Clock::get()?.unix_timestamp as u32,
+                };
+                return processor.process_execute_cancel(program_id, executor, proposal, proposer, vault, mint, token_program, reclaim_destination, target_destination);
+            }
+            TokenLockInstruction::CloseCancelProposal => {
+                let proposal = next_account_info(accounts_iter)?;
+                let proposer = next_account_info(accounts_iter)?;
+                let signers = accounts_iter.as_slice();
+                let mut data = account.data.borrow_mut();
+                let mut processor = Processor{
+                    account_key: account.key,
+                    account_info: Self::load_token_lock(program_id, account, &mut data)?,
+                    modified: false,
+                    timestamp: Clock::get()?.unix_timestamp as u32,
+                };
+                return processor.process_close_cancel_proposal(program_id, proposal, proposer, signers);
+            }
+            TokenLockInstruction::AddCanceler { target, timelock_index, canceler } => {
+                let signers = accounts_iter.as_slice();
+                let mut data = account.data.borrow_mut();
+                let mut processor = Processor{
+                    account_key: account.key,
+                    account_info: Self::load_token_lock(program_id, account, &mut data)?,
+                    modified: false,
+                    timestamp: Clock::get()?.unix_timestamp as u32,
+                };
+                return processor.process_add_canceler(signers, &target, timelock_index, &canceler);
+            }
+            TokenLockInstruction::RemoveCanceler { target, timelock_index, canceler } => {
+                let signers = accounts_iter.as_slice();
+                let mut data = account.data.borrow_mut();
+                let mut processor = Processor{
+                    account_key: account.key,
+                    account_info: Self::load_token_lock(program_id, account, &mut data)?,
+                    modified: false,
+                    timestamp: Clock::get()?.unix_timestamp as u32,
+                };
+                return processor.process_remove_canceler(signers, &target, timelock_index, &canceler);
+            }
+            TokenLockInstruction::RenounceCancel { target, timelock_index } => {
+                let canceler = next_account_info(accounts_iter)?;
+                let mut data = account.data.borrow_mut();
+                let mut processor = Processor{
+                    account_key: account.key,
+                    account_info: Self::load_token_lock(program_id, account, &mut data)?,
+                    modified: false,
+                    timestamp: Clock::get()?.unix_timestamp as u32,
+                };
+                return processor.process_renounce_cancel(canceler, &target, timelock_index);
+            }
+            TokenLockInstruction::LogCancelStatus { target, timelock_index } => {
+                let mut data = account.data.borrow_mut();
+                let processor = Processor{
+                    account_key: account.key,
+                    account_info: Self::load_token_lock(program_id, account, &mut data)?,
+                    modified: false,
+                    timestamp: Clock::get()?.unix_timestamp as u32,
+                };
+                return processor.process_log_cancel_status(&target, timelock_index);
+            }
+            TokenLockInstruction::Reconcile => {
+                let vault = next_account_info(accounts_iter)?;
+                let token_program = next_account_info(accounts_iter)?;
+                let mut data = account.data.borrow_mut();
+                let processor = Processor{
+                    account_key: account.key,
+                    account_info: Self::load_token_lock(program_id, account, &mut data)?,
+                    modified: false,
+                    timestamp: Clock::get()?.unix_timestamp as u32,
+                };
+                return processor.process_reconcile(program_id, vault, token_program);
+            }
+            TokenLockInstruction::ClaimSurplus => {
+                let funder = next_account_info(accounts_iter)?;
+                let vault = next_account_info(accounts_iter)?;
+                let token_program = next_account_info(accounts_iter)?;
+                let mut data = account.data.borrow_mut();
+                let mut processor = Processor{
+                    account_key: account.key,
+                    account_info: Self::load_token_lock(program_id, account, &mut data)?,
+                    modified: false,
+                    timestamp: Clock::get()?.unix_timestamp as u32,
+                };
+                return processor.process_claim_surplus(program_id, funder, vault, token_program);
+            }
+            TokenLockInstruction::SweepSurplus { amount } => {
+                let authority = next_account_info(accounts_iter)?;
+                let vault = next_account_info(accounts_iter)?;
+                let mint = next_account_info(accounts_iter)?;
+                let token_program = next_account_info(accounts_iter)?;
+                let destination = next_account_info(accounts_iter)?;
+                let mut data = account.data.borrow_mut();
+                let mut processor = Processor{
+                    account_key: account.key,
+                    account_info: Self::load_token_lock(program_id, account, &mut data)?,
+                    modified: false,
+                    timestamp: Clock::get()?.unix_timestamp as u32,
+                };
+                return processor.process_sweep_surplus(program_id, authority, vault, mint, token_program, destination, amount);
+            }
+            TokenLockInstruction::Burn { amount, source } => {
+                let recipient = next_account_info(accounts_iter)?;
+                let vault = next_account_info(accounts_iter)?;
+                let mint = next_account_info(accounts_iter)?;
+                let token_program = next_account_info(accounts_iter)?;
+                let mut data = account.data.borrow_mut();
+                let mut processor = Processor{
+                    account_key: account.key,
+                    account_info: Self::load_token_lock(program_id, account, &mut data)?,
+                    modified: false,
+                    timestamp: Clock::get()?.unix_timestamp as u32,
+                };
+                return processor.process_burn(program_id, recipient, vault, mint, token_program, amount, source);
+            }
+            TokenLockInstruction::InitializeToken { decimals, total_supply, metadata } => {
+                let payer = account;
+                let mint = next_account_info(accounts_iter)?;
+                let treasury = next_account_info(accounts_iter)?;
+                let treasury_owner = next_account_info(accounts_iter)?;
+                let mint_authority = next_account_info(accounts_iter)?;
+                let metadata_account = next_account_info(accounts_iter)?;
+                let system_program = next_account_info(accounts_iter)?;
+                let token_program = next_account_info(accounts_iter)?;
+                let rent_sysvar = next_account_info(accounts_iter)?;
+                return Self::process_initialize_token(program_id, payer, mint, treasury, treasury_owner, mint_authority, metadata_account, system_program, token_program, rent_sysvar, decimals, total_supply, metadata);
+            }
+            TokenLockInstruction::InitializeTokenLock { nonce, max_release_delay, min_timelock_amount, space, treasury_authority } => {
+                let token_lock = account;
+                let payer = next_account_info(accounts_iter)?;
+                let mint = next_account_info(accounts_iter)?;
+                let vault = next_account_info(accounts_iter)?;
+                let system_program = next_account_info(accounts_iter)?;
+                let token_program = next_account_info(accounts_iter)?;
+                let rent_sysvar = next_account_info(accounts_iter)?;
+                return Self::process_initialize_token_lock(program_id, token_lock, payer, mint, vault, system_program, token_program, rent_sysvar, nonce, max_release_delay, min_timelock_amount, space, treasury_authority);
+            }
+            TokenLockInstruction::FundReleaseSchedule { to, amount, commencement_timestamp, schedule_id, cancelable_by, cancel_threshold, timelock_index } => {
+                let funder = next_account_info(accounts_iter)?;
+                let source = next_account_info(accounts_iter)?;
+                let vault = next_account_info(accounts_iter)?;
+                let mint = next_account_info(accounts_iter)?;
+                let token_program = next_account_info(accounts_iter)?;
+                let mut data = account.data.borrow_mut();
+                let mut processor = Processor{
+                    account_key: account.key,
+                    account_info: Self::load_token_lock(program_id, account, &mut data)?,
+                    modified: false,
+                    timestamp: Clock::get()?.unix_timestamp as u32,
+                };
+                return processor.process_fund_release_schedule(program_id, funder, source, vault, mint, token_program, &to, amount, commencement_timestamp, schedule_id, &cancelable_by, cancel_threshold, timelock_index);
+            }
+            TokenLockInstruction::TransferTimelock { to, value, timelock_index } => {
+                let owner = next_account_info(accounts_iter)?;
+                let vault = next_account_info(accounts_iter)?;
+                let mint = next_account_info(accounts_iter)?;
+                let token_program = next_account_info(accounts_iter)?;
+                let destination = next_account_info(accounts_iter)?;
+                let mut data = account.data.borrow_mut();
+                let mut processor = Processor{
+                    account_key: account.key,
+                    account_info: Self::load_token_lock(program_id, account, &mut data)?,
+                    modified: false,
+                    timestamp: Clock::get()?.unix_timestamp as u32,
+                };
+                return processor.process_transfer_timelock(program_id, owner, vault, mint, token_program, destination, &to, value, timelock_index);
+            }
+            TokenLockInstruction::SplitTimelock { timelock_index, amount, to } => {
+                let owner = next_account_info(accounts_iter)?;
+                let cosigners = accounts_iter.as_slice();
+                let mut data = account.data.borrow_mut();
+                let mut processor = Processor{
+                    account_key: account.key,
+                    account_info: Self::load_token_lock(program_id, account, &mut data)?,
+                    modified: false,
+                    timestamp: Clock::get()?.unix_timestamp as u32,
+                };
+                return processor.process_split_timelock(owner, cosigners, timelock_index, amount, &to);
+            }
+            TokenLockInstruction::MergeTimelocks { timelock_index, other_timelock_index } => {
+                let owner = next_account_info(accounts_iter)?;
+                let mut data = account.data.borrow_mut();
+                let mut processor = Processor{
+                    account_key: account.key,
+                    account_info: Self::load_token_lock(program_id, account, &mut data)?,
+                    modified: false,
+                    timestamp: Clock::get()?.unix_timestamp as u32,
+                };
+                return processor.process_merge_timelocks(owner, timelock_index, other_timelock_index);
+            }
+            TokenLockInstruction::Migrate { from_version, seed } => {
+                let authority = next_account_info(accounts_iter)?;
+                let old_account = next_account_info(accounts_iter)?;
+                let source = next_account_info(accounts_iter)?;
+                let vault = next_account_info(accounts_iter)?;
+                let mint = next_account_info(accounts_iter)?;
+                let token_program = next_account_info(accounts_iter)?;
+                let mut data = account.data.borrow_mut();
+                let mut processor = Processor{
+                    account_key: account.key,
+                    account_info: Self::load_token_lock(program_id, account, &mut data)?,
+                    modified: false,
+                    timestamp: Clock::get()?.unix_timestamp as u32,
+                };
+                return processor.process_migrate(program_id, authority, old_account, source, vault, mint, token_program, from_version, &seed);
+            }
        }       
 
     }     
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Processor;
+    use crate::migration::v0;
+    use crate::instruction::BurnSource;
+    use crate::types::{TokenLockAccount, ReleaseSchedule, Timelock, CancelMode, CancelProposal, ReconcileReport};
+    use borsh::{BorshDeserialize, BorshSerialize};
+    use solana_program::{
+        account_info::AccountInfo, entrypoint::{ProgramResult, SUCCESS}, instruction::Instruction, program_error::ProgramError,
+        program_pack::Pack, program_stubs::{set_syscall_stubs, SyscallStubs}, pubkey::Pubkey, rent::Rent, system_program,
+    };
+    use std::convert::TryInto;
+    use spl_token::state::{Account, AccountState, Mint};
+    use proptest::prelude::*;
+
+    /// Syscall stubs for native SOL lockups, providing the default rent for vault balances and carrying out
+    /// system transfers
+    struct NativeStubs;
+
+    impl SyscallStubs for NativeStubs {
+        fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+            unsafe { *(var_addr as *mut Rent) = Rent::default() };
+            SUCCESS
+        }
+
+        fn sol_invoke_signed(&self, instruction: &Instruction, account_infos: &[AccountInfo], _signers_seeds: &[&[&[u8]]]) -> ProgramResult {
+            // SystemInstruction::Transfer, tag 2 followed by the lamports
+            if instruction.program_id == system_program::id() && instruction.data[..4] == 2u32.to_le_bytes() {
+                let lamports = u64::from_le_bytes(instruction.data[4..12].try_into().unwrap());
+                let find = |key: &Pubkey| account_infos.iter().find(|info| info.key == key).unwrap();
+                **find(&instruction.accounts[0].pubkey).lamports.borrow_mut() -= lamports;
+                **find(&instruction.accounts[1].pubkey).lamports.borrow_mut() += lamports;
+            }
+            Ok(())
+        }
+    }
+
+    fn token_lock_account() -> TokenLockAccount<'static> {
+        let data = Box::leak(vec![0; TokenLockAccount::space(10)].into_boxed_slice());
+        let mut account_info = TokenLockAccount::init(data, &Pubkey::new_unique(), &Pubkey::new_unique(), 0, 1000, 1, None).unwrap();
+        // 10% at the cliff, then 3 more releases every 100 seconds
+        account_info.add_release_schedule(ReleaseSchedule {
+            release_count: 4,
+            delay_until_first_release_in_seconds: 0,
+            initial_release_portion_in_bips: 1000,
+            period_between_releases_in_seconds: 100,
+        }).unwrap();
+        account_info
+    }
+
+    /// An account living for the rest of the test, so accounts of different lifetimes aren't mixed
+    fn account(key: Pubkey, is_signer: bool, lamports: u64, data: Vec<u8>, owner: Pubkey) -> AccountInfo<'static> {
+        AccountInfo::new(
+            Box::leak(Box::new(key)),
+            is_signer,
+            true,
+            Box::leak(Box::new(lamports)),
+            Box::leak(data.into_boxed_slice()),
+            Box::leak(Box::new(owner)),
+            false,
+            0,
+        )
+    }
+
+    /// The vault holding 1000 tokens, the mint and the token program of an SPL token lock account
+    fn token_vault(key: &Pubkey, mint: &Pubkey) -> [AccountInfo<'static>; 3] {
+        let (vault, _) = TokenLockAccount::find_vault(key, &crate::id());
+        let mut vault_data = vec![0; Account::LEN];
+        Account { mint: *mint, owner: vault, amount: 1000, state: AccountState::Initialized, ..Account::default() }.pack_into_slice(&mut vault_data);
+        let mut mint_data = vec![0; Mint::LEN];
+        Mint { decimals: 6, is_initialized: true, ..Mint::default() }.pack_into_slice(&mut mint_data);
+        [
+            account(vault, false, 0, vault_data, spl_token::id()),
+            account(*mint, false, 0, mint_data, spl_token::id()),
+            account(spl_token::id(), false, 0, vec![], Pubkey::default()),
+        ]
+    }
+
+    fn token_account(owner: &Pubkey, mint: &Pubkey) -> AccountInfo<'static> {
+        let mut data = vec![0; Account::LEN];
+        Account { mint: *mint, owner: *owner, state: AccountState::Initialized, ..Account::default() }.pack_into_slice(&mut data);
+        account(Pubkey::new_unique(), false, 0, data, spl_token::id())
+    }
+
+    fn timelock(total_amount: u32, tokens_transferred: u32) -> Timelock {
+        let mut timelock = Timelock::new(0, 0, total_amount);
+        timelock.tokens_transferred = tokens_transferred;
+        timelock
+    }
+
+    #[test]
+    fn only_authorities_create_release_schedules() {
+        let key = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let treasury_authority = Pubkey::new_unique();
+        let data = Box::leak(vec![0; TokenLockAccount::space(1)].into_boxed_slice());
+        let account_info = TokenLockAccount::init(data, &Pubkey::new_unique(), &authority, 0, 1000, 1, Some(treasury_authority)).unwrap();
+        let mut processor = Processor {
+            account_key: &key,
+            account_info,
+            modified: false,
+            timestamp: 0,
+        };
+
+        let stranger = account(Pubkey::new_unique(), true, 0, vec![], Pubkey::default());
+        assert_eq!(processor.process_create_release_schedule(&stranger, 1, 0, 10000, 0), Err(ProgramError::InvalidArgument));
+        let unsigned = account(authority, false, 0, vec![], Pubkey::default());
+        assert_eq!(processor.process_create_release_schedule(&unsigned, 1, 0, 10000, 0), Err(ProgramError::MissingRequiredSignature));
+        assert!(processor.account_info.release_schedules().is_empty());
+
+        for signer in [authority, treasury_authority].iter() {
+            let signer = account(*signer, true, 0, vec![], Pubkey::default());
+            processor.process_create_release_schedule(&signer, 1, 0, 10000, 0).unwrap();
+        }
+        assert_eq!(processor.account_info.release_schedules().len(), 2);
+    }
+
+    #[test]
+    fn fund_checks_schedule_and_release_delay() {
+        let key = Pubkey::new_unique();
+        let to = Pubkey::new_unique();
+        let account_info = token_lock_account();
+        let mut processor = Processor {
+            account_key: &key,
+            account_info,
+            modified: false,
+            timestamp: 500,
+        };
+        assert!(processor.fund_release_schedule(&to, 100, 500, 1, &[], 0).is_err());
+        assert!(processor.fund_release_schedule(&to, 3, 500, 0, &[], 0).is_err());
+        assert!(processor.fund_release_schedule(&to, 100, 1501, 0, &[], 0).is_err());
+        assert!(processor.fund_release_schedule(&to, 100, 500, 0, &[to], 2).is_err());
+        assert!(!processor.modified);
+
+        assert_eq!(processor.fund_release_schedule(&to, 100, 1500, 0, &[to], 1).unwrap(), 0);
+        assert_eq!(processor.fund_release_schedule(&to, 100, 500, 0, &[], 0).unwrap(), 1);
+        assert_eq!(processor.account_info.get_timelock(&to, 0).unwrap().cancel_threshold, 1);
+        assert!(processor.modified);
+    }
+
+    #[test]
+    fn split_and_merge_keep_unlocked_balance() {
+        let key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let to = Pubkey::new_unique();
+        let (mut lamports, mut data) = (0, vec![]);
+        let owner_info = AccountInfo::new(&owner, true, false, &mut lamports, &mut data, &key, false, 0);
+
+        let mut account_info = token_lock_account();
+        account_info.add_timelock(&owner, timelock(1000, 100)).unwrap();
+        let mut processor = Processor {
+            account_key: &key,
+            account_info,
+            modified: false,
+            timestamp: 150,
+        };
+        // 400 unlocked at t=150, 300 of it not yet transferred
+        assert_eq!(processor.unlocked_balance_of_timelock(&owner, 0), 300);
+
+        // timelock indices are shared by all recipients
+        processor.process_split_timelock(&owner_info, &[], 0, 250, &to).unwrap();
+        assert!(processor.account_info.get_timelock(&to, 0).is_none());
+        assert_eq!(processor.account_info.get_timelock(&to, 1).unwrap().tokens_transferred, 25);
+        assert_eq!(processor.account_info.get_timelock(&owner, 0).unwrap().tokens_transferred, 75);
+        assert_eq!(processor.unlocked_balance_of_timelock(&owner, 0) + processor.unlocked_balance_of_timelock(&to, 1), 300);
+
+        processor.process_split_timelock(&owner_info, &[], 0, 250, &owner).unwrap();
+        assert!(processor.process_merge_timelocks(&owner_info, 0, 1).is_err());
+        processor.process_merge_timelocks(&owner_info, 0, 2).unwrap();
+        assert_eq!(processor.account_info.get_timelock(&owner, 0).unwrap().total_amount, 750);
+        assert_eq!(processor.account_info.get_timelock(&owner, 2).unwrap().total_amount, 0);
+        assert_eq!(processor.unlocked_balance_of_timelock(&owner, 0), 225);
+    }
+
+    #[test]
+    fn split_rejects_rounding_loss() {
+        let key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let to = Pubkey::new_unique();
+        let (mut lamports, mut data) = (0, vec![]);
+        let owner_info = AccountInfo::new(&owner, true, false, &mut lamports, &mut data, &key, false, 0);
+
+        let mut account_info = token_lock_account();
+        account_info.add_timelock(&owner, timelock(20, 0)).unwrap();
+        let mut processor = Processor {
+            account_key: &key,
+            account_info,
+            modified: false,
+            timestamp: 0,
+        };
+        // 10% of 20 unlocks 2 tokens, but 10% of 5 and 15 unlocks only 0 and 1
+        assert!(processor.process_split_timelock(&owner_info, &[], 0, 5, &to).is_err());
+        assert!(processor.process_split_timelock(&owner_info, &[], 0, 10, &to).is_ok());
+    }
+
+    #[test]
+    fn cancel_with_stop_vesting_leaves_unlocked_claimable() {
+        let program_id = crate::id();
+        let key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let canceler = Pubkey::new_unique();
+        let canceler_info = account(canceler, true, 0, vec![], key);
+
+        let mut account_info = token_lock_account();
+        let [vault, mint, token_program] = token_vault(&key, &account_info.mint);
+        let reclaim_destination = token_account(&canceler, mint.key);
+        let target_destination = token_account(&owner, mint.key);
+        let mut cancelable = timelock(1000, 100);
+        cancelable.set_cancelable_by(&[canceler]);
+        account_info.add_timelock(&owner, cancelable).unwrap();
+        account_info.add_timelock(&owner, cancelable).unwrap();
+        let mut processor = Processor {
+            account_key: &key,
+            account_info,
+            modified: false,
+            timestamp: 150,
+        };
+
+        // payouts must go to token accounts of the reclaim address and the target
+        assert!(processor.process_cancel_timelock(&program_id, &canceler_info, &vault, &mint, &token_program, &target_destination, &target_destination, &owner, 1, &canceler, CancelMode::PayOut).is_err());
+        processor.process_cancel_timelock(&program_id, &canceler_info, &vault, &mint, &token_program, &reclaim_destination, &target_destination, &owner, 0, &canceler, CancelMode::StopVesting).unwrap();
+        assert_eq!(processor.locked_balance_of_timelock(&owner, 0), 0);
+        assert_eq!(processor.unlocked_balance_of_timelock(&owner, 0), 300);
+        processor.process_cancel_timelock(&program_id, &canceler_info, &vault, &mint, &token_program, &reclaim_destination, &target_destination, &owner, 1, &canceler, CancelMode::PayOut).unwrap();
+        assert_eq!(processor.unlocked_balance_of_timelock(&owner, 1), 0);
+
+        processor.timestamp = 1000;
+        assert_eq!(processor.unlocked_balance_of_timelock(&owner, 0), 300);
+        assert!(processor.process_cancel_timelock(&program_id, &canceler_info, &vault, &mint, &token_program, &reclaim_destination, &target_destination, &owner, 0, &canceler, CancelMode::PayOut).is_err());
+    }
+
+    #[test]
+    fn threshold_cancel_needs_approvals() {
+        let program_id = crate::id();
+        let key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let cancelers = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let canceler_0 = account(cancelers[0], true, 0, vec![], key);
+        let canceler_1 = account(cancelers[1], true, 0, vec![], key);
+
+        let (proposal_key, _) = CancelProposal::find_address(&key, &owner, 0, &program_id);
+        let mut proposal_data = vec![0; CancelProposal::LEN];
+        CancelProposal {
+            target: owner,
+            timelock_index: 0,
+            reclaim_token_to: cancelers[0],
+            mode: CancelMode::PayOut,
+            proposer: cancelers[0],
+            expires_at: 1000,
+            approvals: vec![cancelers[0]],
+        }.serialize(&mut &mut proposal_data[..]).unwrap();
+        let proposal = account(proposal_key, false, 100, proposal_data, program_id);
+
+        let mut account_info = token_lock_account();
+        let [vault, mint, token_program] = token_vault(&key, &account_info.mint);
+        let reclaim_destination = token_account(&cancelers[0], mint.key);
+        let target_destination = token_account(&owner, mint.key);
+        let mut cancelable = timelock(1000, 0);
+        cancelable.set_cancelable_by(&cancelers);
+        cancelable.set_cancel_threshold(2);
+        account_info.add_timelock(&owner, cancelable).unwrap();
+        let mut processor = Processor {
+            account_key: &key,
+            account_info,
+            modified: false,
+            timestamp: 150,
+        };
+
+        assert!(processor.process_cancel_timelock(&program_id, &canceler_0, &vault, &mint, &token_program, &reclaim_destination, &target_destination, &owner, 0, &cancelers[0], CancelMode::PayOut).is_err());
+        assert!(processor.process_execute_cancel(&program_id, &canceler_1, &proposal, &canceler_0, &vault, &mint, &token_program, &reclaim_destination, &target_destination).is_err());
+        assert!(processor.process_approve_cancel(&program_id, &canceler_0, &proposal).is_err());
+
+        processor.process_approve_cancel(&program_id, &canceler_1, &proposal).unwrap();
+        assert_eq!(CancelProposal::deserialize(&mut &proposal.data.borrow()[..]).unwrap().approvals, cancelers[..2].to_vec());
+        processor.process_execute_cancel(&program_id, &canceler_1, &proposal, &canceler_0, &vault, &mint, &token_program, &reclaim_destination, &target_destination).unwrap();
+        assert_eq!(processor.locked_balance_of_timelock(&owner, 0), 0);
+        assert_eq!(proposal.lamports(), 0);
+        assert_eq!(canceler_0.lamports(), 100);
+    }
+
+    #[test]
+    fn transfer_ownership_closes_open_cancel_proposal() {
+        let program_id = crate::id();
+        let key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let new_owner = Pubkey::new_unique();
+        let cancelers = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let (mut owner_lamports, mut owner_data) = (0, vec![]);
+        let owner_info = AccountInfo::new(&owner, true, false, &mut owner_lamports, &mut owner_data, &key, false, 0);
+        let (mut proposer_lamports, mut proposer_data) = (0, vec![]);
+        let proposer = AccountInfo::new(&cancelers[0], false, true, &mut proposer_lamports, &mut proposer_data, &key, false, 0);
+        let (mut lamports_1, mut data_1) = (0, vec![]);
+        let canceler_1 = AccountInfo::new(&cancelers[1], true, false, &mut lamports_1, &mut data_1, &key, false, 0);
+
+        let (proposal_key, _) = CancelProposal::find_address(&key, &owner, 0, &program_id);
+        let mut proposal_data = vec![0; CancelProposal::LEN];
+        CancelProposal {
+            target: owner,
+            timelock_index: 0,
+            reclaim_token_to: cancelers[0],
+            mode: CancelMode::PayOut,
+            proposer: cancelers[0],
+            expires_at: 1000,
+            approvals: vec![cancelers[0]],
+        }.serialize(&mut &mut proposal_data[..]).unwrap();
+        let mut proposal_lamports = 100;
+        let proposal = AccountInfo::new(&proposal_key, false, true, &mut proposal_lamports, &mut proposal_data, &program_id, false, 0);
+
+        let mut account_info = token_lock_account();
+        let mut cancelable = timelock(1000, 0);
+        cancelable.set_cancelable_by(&cancelers);
+        cancelable.set_cancel_threshold(2);
+        account_info.add_timelock(&owner, cancelable).unwrap();
+        let mut processor = Processor {
+            account_key: &key,
+            account_info,
+            modified: false,
+            timestamp: 150,
+        };
+
+        // the proposal of the timelock has to be passed, followed by its proposer while it is open
+        let (other_proposal_key, _) = CancelProposal::find_address(&key, &new_owner, 0, &program_id);
+        let system_program_id = system_program::id();
+        let (mut other_lamports, mut other_data) = (0, vec![]);
+        let other_proposal = AccountInfo::new(&other_proposal_key, false, true, &mut other_lamports, &mut other_data, &system_program_id, false, 0);
+        assert!(processor.process_transfer_timelock_ownership(&program_id, &owner_info, &other_proposal, std::slice::from_ref(&canceler_1), 0, &new_owner).is_err());
+        assert!(processor.process_transfer_timelock_ownership(&program_id, &owner_info, &proposal, std::slice::from_ref(&canceler_1), 0, &new_owner).is_err());
+        assert!(processor.process_transfer_timelock_ownership(&program_id, &owner_info, &proposal, std::slice::from_ref(&proposer), 0, &new_owner).is_err());
+
+        processor.process_transfer_timelock_ownership(&program_id, &owner_info, &proposal, &[proposer.clone(), canceler_1.clone()], 0, &new_owner).unwrap();
+        assert_eq!((proposal.lamports(), proposer.lamports()), (0, 100));
+        assert_eq!(processor.account_info.get_timelock(&new_owner, 0).unwrap().total_amount, 1000);
+
+        // without an open proposal only the cosigners follow
+        let (mut new_owner_lamports, mut new_owner_data) = (0, vec![]);
+        let new_owner_info = AccountInfo::new(&new_owner, true, false, &mut new_owner_lamports, &mut new_owner_data, &key, false, 0);
+        processor.process_transfer_timelock_ownership(&program_id, &new_owner_info, &other_proposal, &[canceler_1], 0, &owner).unwrap();
+        assert!(processor.account_info.get_timelock(&owner, 0).is_some());
+    }
+
+    #[test]
+    fn close_cancel_proposal() {
+        let program_id = crate::id();
+        let key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let cancelers = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let (proposal_key, _) = CancelProposal::find_address(&key, &owner, 0, &program_id);
+        let proposal_data = || {
+            let mut proposal_data = vec![0; CancelProposal::LEN];
+            CancelProposal {
+                target: owner,
+                timelock_index: 0,
+                reclaim_token_to: cancelers[0],
+                mode: CancelMode::PayOut,
+                proposer: cancelers[0],
+                expires_at: 1000,
+                approvals: vec![cancelers[0]],
+            }.serialize(&mut &mut proposal_data[..]).unwrap();
+            proposal_data
+        };
+        let (mut lamports_1, mut data_1) = (0, vec![]);
+        let canceler_1 = AccountInfo::new(&cancelers[1], true, false, &mut lamports_1, &mut data_1, &key, false, 0);
+        let (mut lamports_2, mut data_2) = (0, vec![]);
+        let canceler_2 = AccountInfo::new(&cancelers[2], true, false, &mut lamports_2, &mut data_2, &key, false, 0);
+
+        let mut account_info = token_lock_account();
+        let mut cancelable = timelock(1000, 0);
+        cancelable.set_cancelable_by(&cancelers);
+        cancelable.set_cancel_threshold(2);
+        account_info.add_timelock(&owner, cancelable).unwrap();
+        let mut processor = Processor {
+            account_key: &key,
+            account_info,
+            modified: false,
+            timestamp: 999,
+        };
+
+        // expired proposals can't be approved anymore
+        let (mut expired_lamports, mut expired_data) = (100, proposal_data());
+        let expired = AccountInfo::new(&proposal_key, false, true, &mut expired_lamports, &mut expired_data, &program_id, false, 0);
+        processor.timestamp = 1000;
+        assert!(processor.process_approve_cancel(&program_id, &canceler_1, &expired).is_err());
+        processor.timestamp = 999;
+
+        // neither the proposer's address without signing nor one other canceler
+        let (mut open_lamports, mut open_data) = (100, proposal_data());
+        let open = AccountInfo::new(&proposal_key, false, true, &mut open_lamports, &mut open_data, &program_id, false, 0);
+        let (mut proposer_lamports, mut proposer_data) = (0, vec![]);
+        let proposer = AccountInfo::new(&cancelers[0], false, true, &mut proposer_lamports, &mut proposer_data, &key, false, 0);
+        assert!(processor.process_close_cancel_proposal(&program_id, &open, &proposer, std::slice::from_ref(&canceler_1)).is_err());
+        processor.process_close_cancel_proposal(&program_id, &open, &proposer, &[canceler_1.clone(), canceler_2]).unwrap();
+        assert_eq!((open.lamports(), proposer.lamports()), (0, 100));
+
+        let (mut open_lamports, mut open_data) = (100, proposal_data());
+        let open = AccountInfo::new(&proposal_key, false, true, &mut open_lamports, &mut open_data, &program_id, false, 0);
+        let (mut signer_lamports, mut signer_data) = (0, vec![]);
+        let signing_proposer = AccountInfo::new(&cancelers[0], true, true, &mut signer_lamports, &mut signer_data, &key, false, 0);
+        processor.process_close_cancel_proposal(&program_id, &open, &signing_proposer, &[]).unwrap();
+        assert_eq!(open.lamports(), 0);
+
+        // anyone once it expired
+        processor.timestamp = 1000;
+        processor.process_close_cancel_proposal(&program_id, &expired, &proposer, &[]).unwrap();
+        assert_eq!(proposer.lamports(), 200);
+        assert_eq!(processor.account_info.get_timelock(&owner, 0).unwrap().tokens_transferred, 0);
+    }
+
+    #[test]
+    fn native_lockup_pays_out_lamports_above_rent() {
+        set_syscall_stubs(Box::new(NativeStubs));
+        let program_id = crate::id();
+        let key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let canceler = Pubkey::new_unique();
+        let rent_exempt = Rent::default().minimum_balance(0);
+
+        let mut account_info = token_lock_account();
+        account_info.mint = spl_token::native_mint::id();
+        let (vault_key, _) = TokenLockAccount::find_vault(&key, &program_id);
+        let vault = account(vault_key, false, rent_exempt + 1000, vec![], program_id);
+        let mint = account(account_info.mint, false, 0, vec![], spl_token::id());
+        let system = account(system_program::id(), false, 0, vec![], Pubkey::default());
+        let owner_info = account(owner, true, 0, vec![], system_program::id());
+        let canceler_info = account(canceler, true, 0, vec![], system_program::id());
+        let mut cancelable = timelock(1000, 0);
+        cancelable.set_cancelable_by(&[canceler]);
+        account_info.add_timelock(&owner, cancelable).unwrap();
+        let mut processor = Processor {
+            account_key: &key,
+            account_info,
+            modified: false,
+            timestamp: 150,
+        };
+
+        // 400 unlocked at t=150, paid straight to the owner's address
+        assert!(processor.process_transfer_timelock(&program_id, &owner_info, &vault, &mint, &system, &canceler_info, &owner, 100, 0).is_err());
+        processor.process_transfer_timelock(&program_id, &owner_info, &vault, &mint, &system, &owner_info, &owner, 100, 0).unwrap();
+        assert!(processor.process_transfer_timelock(&program_id, &owner_info, &vault, &mint, &system, &owner_info, &owner, 301, 0).is_err());
+        assert_eq!(owner_info.lamports(), 100);
+        assert_eq!(processor.vault_balance(&vault).unwrap(), 900);
+
+        processor.process_cancel_timelock(&program_id, &canceler_info, &vault, &mint, &system, &canceler_info, &owner_info, &owner, 0, &canceler, CancelMode::PayOut).unwrap();
+        assert_eq!(canceler_info.lamports(), 600);
+        assert_eq!(owner_info.lamports(), 400);
+        assert_eq!(vault.lamports(), rent_exempt);
+    }
+
+    #[test]
+    fn manage_cancelers_after_funding() {
+        let key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let cancelers = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let (mut lamports_0, mut data_0) = (0, vec![]);
+        let canceler_0 = AccountInfo::new(&cancelers[0], true, false, &mut lamports_0, &mut data_0, &key, false, 0);
+        let (mut lamports_1, mut data_1) = (0, vec![]);
+        let canceler_1 = AccountInfo::new(&cancelers[1], true, false, &mut lamports_1, &mut data_1, &key, false, 0);
+
+        let mut account_info = token_lock_account();
+        let mut cancelable = timelock(1000, 0);
+        cancelable.set_cancelable_by(&cancelers[..2]);
+        cancelable.set_cancel_threshold(2);
+        account_info.add_timelock(&owner, cancelable).unwrap();
+        let mut processor = Processor {
+            account_key: &key,
+            account_info,
+            modified: false,
+            timestamp: 0,
+        };
+
+        assert!(processor.process_add_canceler(std::slice::from_ref(&canceler_0), &owner, 0, &cancelers[2]).is_err());
+        assert!(processor.process_add_canceler(&[canceler_0.clone(), canceler_0.clone()], &owner, 0, &cancelers[2]).is_err());
+        processor.process_add_canceler(&[canceler_0.clone(), canceler_1.clone()], &owner, 0, &cancelers[2]).unwrap();
+        assert!(processor.account_info.get_timelock(&owner, 0).unwrap().has_cancelable_by(&cancelers[2]));
+
+        processor.process_remove_canceler(&[canceler_0.clone(), canceler_1.clone()], &owner, 0, &cancelers[2]).unwrap();
+        processor.process_renounce_cancel(&canceler_1, &owner, 0).unwrap();
+        let timelock = processor.account_info.get_timelock(&owner, 0).unwrap();
+        assert_eq!(timelock.cancelable_by(), &[cancelers[0]]);
+        assert_eq!(timelock.cancel_threshold, 1);
+        assert!(processor.process_renounce_cancel(&canceler_1, &owner, 0).is_err());
+    }
+
+    #[test]
+    fn burn_from_unlocked_and_locked_balances() {
+        let key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+
+        let mut account_info = token_lock_account();
+        account_info.add_timelock(&owner, timelock(1000, 100)).unwrap();
+        account_info.add_timelock(&owner, timelock(1000, 0)).unwrap();
+        let mut cancelable = timelock(1000, 0);
+        cancelable.set_cancelable_by(&[Pubkey::new_unique()]);
+        account_info.add_timelock(&owner, cancelable).unwrap();
+        let mut processor = Processor {
+            account_key: &key,
+            account_info,
+            modified: false,
+            timestamp: 0,
+        };
+
+        // 0 + 100 + 100 unlocked at the cliff
+        assert!(processor.burn_from_timelocks(&owner, 201, BurnSource::Unlocked).is_err());
+        processor.burn_from_timelocks(&owner, 60, BurnSource::Unlocked).unwrap();
+        assert_eq!(processor.account_info.get_timelock(&owner, 1).unwrap().tokens_transferred, 60);
+
+        // 900 + 900 locked in the timelocks nobody can cancel
+        assert_eq!(processor.burn_from_timelocks(&owner, 1801, BurnSource::Locked), Err(ProgramError::InsufficientFunds));
+        processor.burn_from_timelocks(&owner, 1000, BurnSource::Locked).unwrap();
+        let burned = processor.account_info.get_timelock(&owner, 0).unwrap();
+        assert_eq!((burned.total_amount, burned.tokens_transferred), (0, 0));
+        let shrunk = processor.account_info.get_timelock(&owner, 1).unwrap();
+        assert_eq!((shrunk.total_amount, shrunk.tokens_transferred), (888, 48));
+        assert_eq!(processor.locked_balance_of_timelock(&owner, 1), 800);
+        assert_eq!(processor.unlocked_balance_of_timelock(&owner, 1), 40);
+        assert_eq!(processor.account_info.get_timelock(&owner, 2).unwrap().total_amount, 1000);
+        // a reduction beyond the total fails instead of wrapping around
+        assert_eq!(processor.shrink_timelock(shrunk, 801).unwrap_err(), ProgramError::InsufficientFunds);
+    }
+
+    #[test]
+    fn migrate_moves_v0_account_into_lockup() {
+        set_syscall_stubs(Box::new(NativeStubs));
+        let program_id = crate::id();
+        let key = Pubkey::new_unique();
+        let authority = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let rent_exempt = Rent::default().minimum_balance(0);
+
+        let mut timelocks = std::collections::HashMap::new();
+        timelocks.insert(owner, vec![v0::Timelock {
+            schedule_id: 0,
+            commencement_timestamp: 100,
+            tokens_transferred: 400,
+            total_amount: 1000,
+            cancelable_by: vec![],
+        }]);
+        let old = v0::TokenLockAccount {
+            counter: 3,
+            max_release_delay: 500,
+            min_timelock_amount: 5,
+            release_schedules: token_lock_account().release_schedules().to_vec(),
+            timelocks,
+            total_tokens_unlocked: std::collections::HashMap::new(),
+            allowances: std::collections::HashMap::new(),
+        };
+        let old_key = Pubkey::create_with_seed(&authority, "hello", &program_id).unwrap();
+        let old_account = account(old_key, false, 50, old.try_to_vec().unwrap(), program_id);
+        let authority_info = account(authority, true, 1000, vec![], system_program::id());
+        let stranger = account(Pubkey::new_unique(), true, 1000, vec![], system_program::id());
+
+        // the vault has to receive what the timelocks hold, here the token program moves nothing
+        let data = Box::leak(vec![0; old.migrated_space()].into_boxed_slice());
+        let mint_key = Pubkey::new_unique();
+        let mut processor = Processor {
+            account_key: &key,
+            account_info: TokenLockAccount::init(data, &mint_key, &authority, 0, 1000, 1, None).unwrap(),
+            modified: false,
+            timestamp: 0,
+        };
+        let [vault, mint, token_program] = token_vault(&key, &mint_key);
+        let source = token_account(&authority, &mint_key);
+        assert_eq!(processor.process_migrate(&program_id, &authority_info, &old_account, &source, &vault, &mint, &token_program, 0, "hello"), Err(ProgramError::InsufficientFunds));
+
+        let data = Box::leak(vec![0; old.migrated_space()].into_boxed_slice());
+        let native_mint = spl_token::native_mint::id();
+        let mut processor = Processor {
+            account_key: &key,
+            account_info: TokenLockAccount::init(data, &native_mint, &authority, 0, 1000, 1, None).unwrap(),
+            modified: false,
+            timestamp: 0,
+        };
+        let (vault_key, _) = TokenLockAccount::find_vault(&key, &program_id);
+        let vault = account(vault_key, false, rent_exempt, vec![], program_id);
+        let mint = account(native_mint, false, 0, vec![], spl_token::id());
+        let system = account(system_program::id(), false, 0, vec![], Pubkey::default());
+
+        // only the authority, whose address the old account's was created from
+        assert_eq!(processor.process_migrate(&program_id, &stranger, &old_account, &stranger, &vault, &mint, &system, 0, "hello"), Err(ProgramError::InvalidArgument));
+        assert_eq!(processor.process_migrate(&program_id, &authority_info, &old_account, &authority_info, &vault, &mint, &system, 0, "other"), Err(ProgramError::InvalidSeeds));
+        assert_eq!(processor.process_migrate(&program_id, &authority_info, &old_account, &authority_info, &vault, &mint, &system, 1, "hello"), Err(ProgramError::InvalidArgument));
+
+        processor.process_migrate(&program_id, &authority_info, &old_account, &authority_info, &vault, &mint, &system, 0, "hello").unwrap();
+        assert_eq!(processor.vault_balance(&vault).unwrap(), 600);
+        assert_eq!(authority_info.lamports(), 1000 - 600 + 50);
+        assert_eq!((old_account.lamports(), old_account.data.borrow().iter().all(|byte| *byte == 0)), (0, true));
+        assert_eq!(processor.account_info.get_timelock(&owner, 0).unwrap().tokens_transferred, 400);
+        assert_eq!((processor.account_info.counter, processor.account_info.max_release_delay), (3, 500));
+        assert_eq!(processor.account_info.original_funder(), Some(authority));
+    }
+
+    #[test]
+    fn initialize_rejects_mints_that_could_empty_or_freeze_the_vault() {
+        let program_id = crate::id();
+        let payer = account(Pubkey::new_unique(), true, 1_000_000_000, vec![], system_program::id());
+        let system_program_info = account(system_program::id(), false, 0, vec![], Pubkey::default());
+        let token_program = account(crate::token::spl_token_2022::id(), false, 0, vec![], Pubkey::default());
+        let rent_sysvar = account(solana_program::sysvar::rent::id(), false, 0, vec![], Pubkey::default());
+
+        // a permanent delegate, then a default account state of frozen
+        for (extension_type, extension) in [(12u16, vec![7; 32]), (6u16, vec![AccountState::Frozen as u8])].iter() {
+            let mut mint_data = vec![0; Account::LEN];
+            Mint { decimals: 6, is_initialized: true, ..Mint::default() }.pack_into_slice(&mut mint_data[..Mint::LEN]);
+            // the mint account type, then the extension
+            mint_data.push(1);
+            mint_data.extend_from_slice(&extension_type.to_le_bytes());
+            mint_data.extend_from_slice(&(extension.len() as u16).to_le_bytes());
+            mint_data.extend_from_slice(extension);
+            let mint = account(Pubkey::new_unique(), false, 0, mint_data, *token_program.key);
+            let (token_lock_key, _) = TokenLockAccount::find_address(mint.key, payer.key, 0, &program_id);
+            let token_lock = account(token_lock_key, false, 0, vec![], system_program::id());
+            let (vault_key, _) = TokenLockAccount::find_vault(&token_lock_key, &program_id);
+            let vault = account(vault_key, false, 0, vec![], system_program::id());
+
+            assert_eq!(
+                Processor::process_initialize_token_lock(&program_id, &token_lock, &payer, &mint, &vault, &system_program_info, &token_program, &rent_sysvar, 0, 1000, 1, 1000, None),
+                Err(ProgramError::InvalidArgument)
+            );
+            assert_eq!(token_lock.data_len(), 0);
+        }
+    }
+
+    #[test]
+    fn initialize_rejects_more_timelocks_than_fit_an_account() {
+        let program_id = crate::id();
+        let payer = account(Pubkey::new_unique(), true, 1_000_000_000, vec![], system_program::id());
+        let system_program_info = account(system_program::id(), false, 0, vec![], Pubkey::default());
+        let rent_sysvar = account(solana_program::sysvar::rent::id(), false, 0, vec![], Pubkey::default());
+        let mint_key = Pubkey::new_unique();
+        let (token_lock_key, _) = TokenLockAccount::find_address(&mint_key, payer.key, 0, &program_id);
+        let [vault, mint, token_program] = token_vault(&token_lock_key, &mint_key);
+        let token_lock = account(token_lock_key, false, 0, vec![], system_program::id());
+
+        let space = TokenLockAccount::space(TokenLockAccount::MAX_TIMELOCKS + 1) as u32;
+        assert_eq!(
+            Processor::process_initialize_token_lock(&program_id, &token_lock, &payer, &mint, &vault, &system_program_info, &token_program, &rent_sysvar, 0, 1000, 1, space, None),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(token_lock.data_len(), 0);
+    }
+
+    #[test]
+    fn reconcile_fails_when_vault_is_short() {
+        let program_id = crate::id();
+        let key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+
+        let mut account_info = token_lock_account();
+        let [vault, _, token_program] = token_vault(&key, &account_info.mint);
+        account_info.add_timelock(&owner, timelock(600, 100)).unwrap();
+        account_info.add_timelock(&owner, timelock(300, 0)).unwrap();
+        account_info.add_timelock(&Pubkey::new_unique(), timelock(200, 0)).unwrap();
+        let mut processor = Processor {
+            account_key: &key,
+            account_info,
+            modified: false,
+            timestamp: 0,
+        };
+
+        let report = ReconcileReport::new(&processor.account_info, 1000);
+        assert_eq!((report.obligations, report.recipients, report.timelocks), (1000, 2, 3));
+        assert_eq!((report.surplus(), report.shortfall()), (0, 0));
+        processor.process_reconcile(&program_id, &vault, &token_program).unwrap();
+
+        processor.account_info.get_timelock_mut(&owner, 0).unwrap().tokens_transferred = 0;
+        assert_eq!(ReconcileReport::new(&processor.account_info, 1000).shortfall(), 100);
+        assert!(processor.process_reconcile(&program_id, &vault, &token_program).is_err());
+        assert!(processor.process_reconcile(&program_id, &token_program, &token_program).is_err());
+    }
+
+    #[test]
+    fn sweep_only_the_surplus() {
+        let program_id = crate::id();
+        let key = Pubkey::new_unique();
+        let funder = Pubkey::new_unique();
+        let treasury = Pubkey::new_unique();
+        let funder_info = account(funder, true, 0, vec![], key);
+        let treasury_info = account(treasury, true, 0, vec![], key);
+
+        let mut account_info = token_lock_account();
+        let [vault, mint, token_program] = token_vault(&key, &account_info.mint);
+        let funder_destination = token_account(&funder, mint.key);
+        let treasury_destination = token_account(&treasury, mint.key);
+        account_info.add_timelock(&Pubkey::new_unique(), timelock(900, 0)).unwrap();
+        account_info.set_original_funder(&funder);
+        let mut processor = Processor {
+            account_key: &key,
+            account_info,
+            modified: false,
+            timestamp: 0,
+        };
+
+        // without a treasury authority the original funder claims the surplus of 100 first, a claim that
+        // could only be swept past the end of time fails
+        processor.timestamp = u32::MAX;
+        assert!(processor.process_claim_surplus(&program_id, &funder_info, &vault, &token_program).is_err());
+        processor.timestamp = 0;
+        assert!(processor.process_sweep_surplus(&program_id, &funder_info, &vault, &mint, &token_program, &funder_destination, 100).is_err());
+        assert!(processor.process_claim_surplus(&program_id, &treasury_info, &vault, &token_program).is_err());
+        processor.process_claim_surplus(&program_id, &funder_info, &vault, &token_program).unwrap();
+        assert!(processor.process_sweep_surplus(&program_id, &funder_info, &vault, &mint, &token_program, &funder_destination, 100).is_err());
+        processor.timestamp = TokenLockAccount::SURPLUS_CLAIM_DELAY;
+        assert!(processor.process_sweep_surplus(&program_id, &funder_info, &vault, &mint, &token_program, &funder_destination, 101).is_err());
+        processor.process_sweep_surplus(&program_id, &funder_info, &vault, &mint, &token_program, &funder_destination, 60).unwrap();
+        assert_eq!(processor.account_info.surplus_claim().unwrap().amount, 40);
+
+        processor.account_info.set_treasury_authority(Some(treasury));
+        assert!(processor.process_claim_surplus(&program_id, &funder_info, &vault, &token_program).is_err());
+        assert!(processor.process_sweep_surplus(&program_id, &funder_info, &vault, &mint, &token_program, &funder_destination, 10).is_err());
+        assert!(processor.process_sweep_surplus(&program_id, &treasury_info, &vault, &mint, &token_program, &funder_destination, 10).is_err());
+        assert!(processor.process_sweep_surplus(&program_id, &treasury_info, &vault, &mint, &token_program, &treasury_destination, 101).is_err());
+        processor.process_sweep_surplus(&program_id, &treasury_info, &vault, &mint, &token_program, &treasury_destination, 100).unwrap();
+    }
+
+    #[test]
+    fn timelock_indices_are_never_reused() {
+        let key = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let mut account_info = token_lock_account();
+        while account_info.timelocks().len() < account_info.timelock_capacity() - 1 {
+            account_info.add_timelock(&owner, timelock(100, 0)).unwrap();
+        }
+        let mut processor = Processor {
+            account_key: &key,
+            account_info,
+            modified: false,
+            timestamp: 500,
+        };
+        // a drained timelock keeps its slot, the next timelock gets the last free index
+        processor.account_info.take_timelock(&owner, 0).unwrap();
+        assert_eq!(processor.fund_release_schedule(&owner, 100, 500, 0, &[], 0).unwrap(), 9);
+
+        // the account never grows, a full account rejects timelocks
+        assert!(processor.fund_release_schedule(&owner, 100, 500, 0, &[], 0).is_err());
+        assert_eq!(processor.account_info.timelocks().len(), 10);
+        assert_eq!(processor.account_info.get_timelock(&owner, 0).unwrap().total_amount, 0);
+    }
+
+    const DAY: u32 = 24 * 60 * 60;
+
+    /// Schedules `create_release_schedule` accepts, short enough to end before `u32::MAX` when commenced
+    /// by `i32::MAX`
+    fn release_schedule() -> impl Strategy<Value = ReleaseSchedule> {
+        (1..=120u32, 0..=10 * 365 * DAY, 0..=Processor::BIPS_PRECISION, 1..=30 * DAY).prop_map(|(release_count, delay, bips, period)| {
+            ReleaseSchedule {
+                release_count,
+                delay_until_first_release_in_seconds: delay,
+                initial_release_portion_in_bips: if release_count == 1 { Processor::BIPS_PRECISION } else { bips },
+                period_between_releases_in_seconds: period,
+            }
+        })
+    }
+
+    fn end_of(commenced: u32, schedule: &ReleaseSchedule) -> u32 {
+        commenced + schedule.delay_until_first_release_in_seconds + schedule.period_between_releases_in_seconds * (schedule.release_count - 1)
+    }
+
+    proptest! {
+        #[test]
+        fn unlocked_is_monotone_in_time(schedule in release_schedule(), amount in any::<u32>(), commenced in 0..=i32::MAX as u32, t1 in any::<u32>(), t2 in any::<u32>()) {
+            let (earlier, later) = (t1.min(t2), t1.max(t2));
+            prop_assert!(Processor::calculate_unlocked(commenced, earlier, amount, &schedule) <= Processor::calculate_unlocked(commenced, later, amount, &schedule));
+        }
+
+        #[test]
+        fn unlocked_is_bounded_by_amount(schedule in release_schedule(), amount in any::<u32>(), commenced in 0..=i32::MAX as u32, now in any::<u32>()) {
+            prop_assert!(Processor::calculate_unlocked(commenced, now, amount, &schedule) <= amount);
+        }
+
+        #[test]
+        fn everything_unlocks_at_the_end(schedule in release_schedule(), amount in any::<u32>(), commenced in 0..=i32::MAX as u32, after in 0..=u32::MAX / 8) {
+            let end = end_of(commenced, &schedule);
+            prop_assert_eq!(Processor::calculate_unlocked(commenced, end + after, amount, &schedule), amount);
+        }
+
+        #[test]
+        fn initial_portion_unlocks_at_the_cliff(schedule in release_schedule(), amount in any::<u32>(), commenced in 1..=i32::MAX as u32) {
+            let cliff = commenced + schedule.delay_until_first_release_in_seconds;
+            let initial = (amount as u64 * schedule.initial_release_portion_in_bips as u64 / Processor::BIPS_PRECISION as u64) as u32;
+            let expected = if schedule.release_count == 1 { amount } else { initial };
+            prop_assert_eq!(Processor::calculate_unlocked(commenced, cliff, amount, &schedule), expected);
+            // nothing before the cliff, or before commencement
+            prop_assert_eq!(Processor::calculate_unlocked(commenced, cliff - 1, amount, &schedule), 0);
+            prop_assert_eq!(Processor::calculate_unlocked(commenced, commenced - 1, amount, &schedule), 0);
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! Token program helpers that work with both the SPL Token and the Token-2022 program.
+//! The instructions used here have the same layout in both programs, Token-2022 only
+//! appends extensions after the base mint and account data.
+use solana_program::{
+    account_info::AccountInfo,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar,
+};
+use spl_token::state::AccountState;
+
+/// The Token-2022 program
+pub mod spl_token_2022 {
+    solana_program::declare_id!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+}
+
+const INITIALIZE_ACCOUNT: u8 = 1;
+const BURN: u8 = 8;
+const TRANSFER_CHECKED: u8 = 12;
+
+/// Offset of the account type byte that precedes the extensions of Token-2022 mints and accounts
+const ACCOUNT_TYPE_OFFSET: usize = spl_token::state::Account::LEN;
+const TRANSFER_FEE_CONFIG: u16 = 1;
+const DEFAULT_ACCOUNT_STATE: u16 = 6;
+const NON_TRANSFERABLE: u16 = 9;
+const PERMANENT_DELEGATE: u16 = 12;
+const TRANSFER_HOOK: u16 = 14;
+/// Size of an extension's type and length header
+const EXTENSION_HEADER_LEN: usize = 4;
+
+pub fn is_token_program(key: &Pubkey) -> bool {
+    key == &spl_token::id() || key == &spl_token_2022::id()
+}
+
+pub fn check_token_program(token_program: &AccountInfo) -> Result<(), ProgramError> {
+    if !is_token_program(token_program.key) {
+        msg!("invalid token program");
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
+
+pub fn transfer_checked(token_program: &Pubkey, source: &Pubkey, mint: &Pubkey, destination: &Pubkey, authority: &Pubkey, amount: u64, decimals: u8) -> Instruction {
+    let mut data = vec![TRANSFER_CHECKED];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.push(decimals);
+    Instruction {
+        program_id: *token_program,
+        accounts: vec![
+            AccountMeta::new(*source, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data,
+    }
+}
+
+pub fn burn(token_program: &Pubkey, account: &Pubkey, mint: &Pubkey, authority: &Pubkey, amount: u64) -> Instruction {
+    let mut data = vec![BURN];
+    data.extend_from_slice(&amount.to_le_bytes());
+    Instruction {
+        program_id: *token_program,
+        accounts: vec![
+            AccountMeta::new(*account, false),
+            AccountMeta::new(*mint, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+        data,
+    }
+}
+
+pub fn initialize_account(token_program: &Pubkey, account: &Pubkey, mint: &Pubkey, owner: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: *token_program,
+        accounts: vec![
+            AccountMeta::new(*account, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(*owner, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+        data: vec![INITIALIZE_ACCOUNT],
+    }
+}
+
+/// Reads the base part of a token account, ignoring any Token-2022 extensions.
+pub fn unpack_account(data: &[u8]) -> Result<spl_token::state::Account, ProgramError> {
+    if data.len() < spl_token::state::Account::LEN {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    spl_token::state::Account::unpack_from_slice(&data[..spl_token::state::Account::LEN])
+}
+
+/// Reads the base part of a mint, ignoring any Token-2022 extensions.
+pub fn unpack_mint(data: &[u8]) -> Result<spl_token::state::Mint, ProgramError> {
+    if data.len() < spl_token::state::Mint::LEN {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    spl_token::state::Mint::unpack_from_slice(&data[..spl_token::state::Mint::LEN])
+}
+
+/// The extensions of a Token-2022 mint with their data in the order they are stored, none for SPL Token mints.
+fn extensions(mint_data: &[u8]) -> Vec<(u16, &[u8])> {
+    let mut extensions = vec![];
+    let mut offset = ACCOUNT_TYPE_OFFSET + 1;
+    while offset + EXTENSION_HEADER_LEN <= mint_data.len() {
+        let extension_type = u16::from_le_bytes([mint_data[offset], mint_data[offset + 1]]);
+        let length = u16::from_le_bytes([mint_data[offset + 2], mint_data[offset + 3]]) as usize;
+        offset += EXTENSION_HEADER_LEN;
+        let end = mint_data.len().min(offset + length);
+        extensions.push((extension_type, &mint_data[offset..end]));
+        offset += length;
+    }
+    extensions
+}
+
+/// Why a vault of the mint couldn't be relied on, if so. `transfer_checked` doesn't pass the extra accounts
+/// a transfer hook program needs, a permanent delegate can transfer the locked tokens out of the vault and a
+/// vault that starts out frozen can't pay out. Extensions can't be added to a mint after it is initialized.
+pub fn unsupported_extension(mint_data: &[u8]) -> Option<&'static str> {
+    for (extension_type, data) in extensions(mint_data) {
+        match extension_type {
+            TRANSFER_HOOK => return Some("mints with a transfer hook are not supported"),
+            // the delegate is zeroed once it is removed, it can't be set again afterwards
+            PERMANENT_DELEGATE if data.iter().any(|byte| *byte != 0) => {
+                return Some("mints with a permanent delegate are not supported");
+            }
+            DEFAULT_ACCOUNT_STATE if data.first() == Some(&(AccountState::Frozen as u8)) => {
+                return Some("mints freezing new accounts are not supported");
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The size of a token account for the mint, including the account extensions its mint extensions require.
+pub fn account_len(mint_data: &[u8]) -> usize {
+    let mut extensions_len = 0;
+    for (extension_type, _) in extensions(mint_data) {
+        match extension_type {
+            // withheld fees of the account
+            TRANSFER_FEE_CONFIG => extensions_len += EXTENSION_HEADER_LEN + 8,
+            // the non-transferable account and immutable owner markers
+            NON_TRANSFERABLE => extensions_len += 2 * EXTENSION_HEADER_LEN,
+            // transferring flag
+            TRANSFER_HOOK => extensions_len += EXTENSION_HEADER_LEN + 1,
+            _ => {}
+        }
+    }
+    if extensions_len == 0 {
+        return spl_token::state::Account::LEN;
+    }
+    spl_token::state::Account::LEN + 1 + extensions_len
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn account_len_for_mint_extensions() {
+        let mut mint_data = vec![0; spl_token::state::Account::LEN + 1];
+        assert_eq!(account_len(&mint_data[..spl_token::state::Mint::LEN]), 165);
+        assert_eq!(account_len(&mint_data), 165);
+
+        // interest bearing config, no account extension needed
+        mint_data.extend_from_slice(&10u16.to_le_bytes());
+        mint_data.extend_from_slice(&52u16.to_le_bytes());
+        mint_data.extend_from_slice(&[0; 52]);
+        assert_eq!(account_len(&mint_data), 165);
+
+        // transfer fee config
+        mint_data.extend_from_slice(&TRANSFER_FEE_CONFIG.to_le_bytes());
+        mint_data.extend_from_slice(&108u16.to_le_bytes());
+        mint_data.extend_from_slice(&[0; 108]);
+        assert_eq!(account_len(&mint_data), 178);
+
+        // non-transferable, the account is non-transferable and has an immutable owner
+        mint_data.extend_from_slice(&NON_TRANSFERABLE.to_le_bytes());
+        mint_data.extend_from_slice(&0u16.to_le_bytes());
+        assert_eq!(account_len(&mint_data), 186);
+
+        // transfer hook with its authority and program id
+        mint_data.extend_from_slice(&TRANSFER_HOOK.to_le_bytes());
+        mint_data.extend_from_slice(&64u16.to_le_bytes());
+        mint_data.extend_from_slice(&[0; 64]);
+        assert_eq!(account_len(&mint_data), 191);
+    }
+
+    fn mint_with_extension(extension_type: u16, data: &[u8]) -> Vec<u8> {
+        let mut mint_data = vec![0; spl_token::state::Account::LEN + 1];
+        mint_data.extend_from_slice(&extension_type.to_le_bytes());
+        mint_data.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        mint_data.extend_from_slice(data);
+        mint_data
+    }
+
+    #[test]
+    fn unsupported_mint_extensions() {
+        assert_eq!(unsupported_extension(&[0; spl_token::state::Mint::LEN]), None);
+        assert_eq!(unsupported_extension(&mint_with_extension(TRANSFER_FEE_CONFIG, &[0; 108])), None);
+        assert!(unsupported_extension(&mint_with_extension(TRANSFER_HOOK, &[0; 64])).is_some());
+
+        assert!(unsupported_extension(&mint_with_extension(PERMANENT_DELEGATE, &[7; 32])).is_some());
+        // a removed permanent delegate
+        assert_eq!(unsupported_extension(&mint_with_extension(PERMANENT_DELEGATE, &[0; 32])), None);
+
+        assert!(unsupported_extension(&mint_with_extension(DEFAULT_ACCOUNT_STATE, &[AccountState::Frozen as u8])).is_some());
+        assert_eq!(unsupported_extension(&mint_with_extension(DEFAULT_ACCOUNT_STATE, &[AccountState::Initialized as u8])), None);
+    }
+}
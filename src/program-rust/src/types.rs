@@ -1,11 +1,22 @@
 //! State transition types
-use std::collections::HashMap;
+use std::{collections::BTreeSet, mem::size_of, ops::{Deref, DerefMut}};
 use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+use bytemuck::{try_cast_slice_mut, try_from_bytes_mut, Pod, Zeroable};
 use solana_program::{
-    account_info::{AccountInfo}};
+    entrypoint::MAX_PERMITTED_DATA_INCREASE, program_error::ProgramError};
 use solana_program::pubkey::Pubkey;    
 
-#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, BorshSchema)]
+/// How the unlocked tokens of a canceled timelock are handled
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, BorshSchema)]
+pub enum CancelMode {
+    /// Pay the unlocked tokens out to the recipient and close the timelock
+    PayOut,
+    /// Stop vesting at the cancellation time and leave the unlocked tokens claimable
+    StopVesting,
+}
+
+#[repr(C)]
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, BorshSchema)]
 pub struct ReleaseSchedule {
     pub release_count: u32,
     pub delay_until_first_release_in_seconds: u32,
@@ -13,66 +24,565 @@ pub struct ReleaseSchedule {
     pub period_between_releases_in_seconds: u32,
 }
 
-#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, BorshSchema)]
+// Safety: repr(C) of u32s, without padding
+unsafe impl Zeroable for ReleaseSchedule {}
+unsafe impl Pod for ReleaseSchedule {}
+
+impl ReleaseSchedule{
+    pub const LEN: usize = size_of::<ReleaseSchedule>();
+}
+
+/// A timelock record, stored in place in the token lock account data.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Timelock {
     pub schedule_id: u32,
     pub commencement_timestamp: u32,
     pub tokens_transferred: u32,
     pub total_amount: u32,
-    pub cancelable_by: Vec<Pubkey>,
+    /// Timestamp vesting was stopped by a cancellation, 0 while still vesting
+    pub vesting_stopped_at: u32,
+    /// The address the tokens are released to
+    pub recipient: Pubkey,
+    cancelable_by: [Pubkey; Timelock::MAX_CANCELABLE_BY],
+    cancelable_by_count: u8,
+    /// Number of cancelableBy approvals needed to cancel, 0 or 1 lets any canceler cancel alone
+    pub cancel_threshold: u8,
+    _reserved: [u8; 2],
 }
+
+// Safety: repr(C) of integers and byte arrays, without padding
+unsafe impl Zeroable for Timelock {}
+unsafe impl Pod for Timelock {}
+
 impl Timelock{
-    pub fn set_cancelable_by(&mut self, cancelable_by: &[Pubkey]) -> (){
-        self.cancelable_by = cancelable_by.to_vec();
+    pub const MAX_CANCELABLE_BY: usize = 10;
+    pub const LEN: usize = size_of::<Timelock>();
+    /// The fields in the order they are stored, including the private ones, typed in the notation of the IDL
+    pub const FIELDS: &'static [(&'static str, &'static str)] = &[
+        ("schedule_id", "u32"),
+        ("commencement_timestamp", "u32"),
+        ("tokens_transferred", "u32"),
+        ("total_amount", "u32"),
+        ("vesting_stopped_at", "u32"),
+        ("recipient", "publicKey"),
+        ("cancelable_by", "[publicKey; 10]"),
+        ("cancelable_by_count", "u8"),
+        ("cancel_threshold", "u8"),
+        ("_reserved", "[u8; 2]"),
+    ];
+
+    pub fn new(schedule_id: u32, commencement_timestamp: u32, total_amount: u32) -> Self{
+        Timelock {
+            schedule_id,
+            commencement_timestamp,
+            total_amount,
+            ..Zeroable::zeroed()
+        }
+    }
+
+    pub fn cancelable_by(&self) -> &[Pubkey]{
+        // bounded in case the stored count is corrupt
+        &self.cancelable_by[..(self.cancelable_by_count as usize).min(Timelock::MAX_CANCELABLE_BY)]
+    }
+    /// Sets the cancelers, at most `MAX_CANCELABLE_BY` of them.
+    pub fn set_cancelable_by(&mut self, cancelable_by: &[Pubkey]) {
+        self.cancelable_by = [Pubkey::default(); Timelock::MAX_CANCELABLE_BY];
+        self.cancelable_by[..cancelable_by.len()].copy_from_slice(cancelable_by);
+        self.cancelable_by_count = cancelable_by.len() as u8;
+    }
+    pub fn set_cancel_threshold(&mut self, cancel_threshold: u8) {
+        self.cancel_threshold = cancel_threshold;
+    }
+    /// Adds a canceler, the timelock must have less than `MAX_CANCELABLE_BY`.
+    pub fn add_cancelable_by(&mut self, cancelable_by: &Pubkey) {
+        self.cancelable_by[self.cancelable_by_count as usize] = *cancelable_by;
+        self.cancelable_by_count += 1;
+    }
+    /// Removes a canceler, lowering the cancel threshold if it could no longer be reached.
+    pub fn remove_cancelable_by(&mut self, cancelable_by: &Pubkey) -> bool{
+        let len = self.cancelable_by_count as usize;
+        let position = match self.cancelable_by().iter().position(|item| item == cancelable_by) {
+            Some(position) => position,
+            None => return false,
+        };
+        self.cancelable_by.copy_within(position + 1..len, position);
+        self.cancelable_by[len - 1] = Pubkey::default();
+        self.cancelable_by_count -= 1;
+        if self.cancel_threshold > self.cancelable_by_count {
+            self.cancel_threshold = self.cancelable_by_count;
+        }
+        return true;
     }
     pub fn has_cancelable_by(&self, cancelable_by: &Pubkey) -> bool{
-        for item in &self.cancelable_by{
-            if item == cancelable_by {
-                return true;
-            }
+        self.cancelable_by().contains(cancelable_by)
+    }
+}
+
+
+/// The fixed part at the start of a token lock account, followed by `MAX_RELEASE_SCHEDULES`
+/// release schedules and as many timelock records as the account has room for.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TokenLockHeader {
+    pub discriminator: [u8; 8],
+    /// The SPL mint of the locked tokens, or the native mint for lockups of native SOL
+    pub mint: Pubkey,
+    treasury_authority: Pubkey,
+    original_funder: Pubkey,
+    authority: Pubkey,
+    pub version: u8,
+    /// Tells apart several lockups of the same mint
+    pub nonce: u8,
+    has_treasury_authority: u8,
+    has_original_funder: u8,
+    pub counter: u32,
+    pub max_release_delay: u32,
+    pub min_timelock_amount: u32,
+    release_schedule_count: u32,
+    timelock_count: u32,
+    surplus_claimable_at: u32,
+    surplus_claim_amount: [u8; 8],
+    _reserved: [u8; 4],
+}
+
+// Safety: repr(C) of integers and byte arrays, without padding
+unsafe impl Zeroable for TokenLockHeader {}
+unsafe impl Pod for TokenLockHeader {}
+
+impl TokenLockHeader{
+    pub const LEN: usize = size_of::<TokenLockHeader>();
+    /// The fields in the order they are stored, including the private ones, typed in the notation of the IDL
+    pub const FIELDS: &'static [(&'static str, &'static str)] = &[
+        ("discriminator", "[u8; 8]"),
+        ("mint", "publicKey"),
+        ("treasury_authority", "publicKey"),
+        ("original_funder", "publicKey"),
+        ("authority", "publicKey"),
+        ("version", "u8"),
+        ("nonce", "u8"),
+        ("has_treasury_authority", "u8"),
+        ("has_original_funder", "u8"),
+        ("counter", "u32"),
+        ("max_release_delay", "u32"),
+        ("min_timelock_amount", "u32"),
+        ("release_schedule_count", "u32"),
+        ("timelock_count", "u32"),
+        ("surplus_claimable_at", "u32"),
+        // stored as bytes to keep the header free of padding, little endian like every other field
+        ("surplus_claim_amount", "u64"),
+        ("_reserved", "[u8; 4]"),
+    ];
+
+    /// Whether the lockup holds native lamports rather than SPL tokens
+    pub fn is_native(&self) -> bool{
+        self.mint == spl_token::native_mint::id()
+    }
+
+    /// May sweep surplus tokens out of the vault, set at initialization
+    pub fn treasury_authority(&self) -> Option<Pubkey>{
+        if self.has_treasury_authority == 0 {
+            return None;
         }
-        return false;
+        Some(self.treasury_authority)
     }
+    pub fn set_treasury_authority(&mut self, treasury_authority: Option<Pubkey>) {
+        self.has_treasury_authority = treasury_authority.is_some() as u8;
+        self.treasury_authority = treasury_authority.unwrap_or_default();
+    }
+
+    /// Initialized the lockup and is part of its address, see `TokenLockAccount::find_address`
+    pub fn authority(&self) -> Pubkey{
+        self.authority
+    }
+
+    /// The first funder, who can claim the surplus when there is no treasury authority
+    pub fn original_funder(&self) -> Option<Pubkey>{
+        if self.has_original_funder == 0 {
+            return None;
+        }
+        Some(self.original_funder)
+    }
+    pub fn set_original_funder(&mut self, original_funder: &Pubkey) {
+        self.has_original_funder = 1;
+        self.original_funder = *original_funder;
+    }
+
+    /// The original funder's pending claim of the surplus
+    pub fn surplus_claim(&self) -> Option<SurplusClaim>{
+        let amount = u64::from_le_bytes(self.surplus_claim_amount);
+        if amount == 0 {
+            return None;
+        }
+        Some(SurplusClaim { amount, claimable_at: self.surplus_claimable_at })
+    }
+    pub fn set_surplus_claim(&mut self, surplus_claim: Option<SurplusClaim>) {
+        let claim = surplus_claim.unwrap_or(SurplusClaim { amount: 0, claimable_at: 0 });
+        self.surplus_claim_amount = claim.amount.to_le_bytes();
+        self.surplus_claimable_at = claim.claimable_at;
+    }
+}
+
+/// Surplus the original funder may sweep once the claim delay has passed
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, BorshSchema)]
+pub struct SurplusClaim {
+    pub amount: u64,
+    pub claimable_at: u32,
+}
+
+/// Zero-copy view of a token lock account. The header, a release schedule or a timelock is read and
+/// written in place, so the cost of an instruction doesn't grow with the number of timelocks.
+///
+/// Timelocks are indexed by their position among all timelocks of the account, instructions name the
+/// recipient as well, which has to match the timelock's recipient. The account is allocated once with
+/// room for at most `MAX_TIMELOCKS` timelocks and never resized: timelocks are appended until it is full,
+/// and a drained timelock keeps its slot, so an index always refers to the same timelock.
+pub struct TokenLockAccount<'a> {
+    header: &'a mut TokenLockHeader,
+    release_schedules: &'a mut [ReleaseSchedule],
+    timelocks: &'a mut [Timelock],
 }
 
+impl<'a> Deref for TokenLockAccount<'a>{
+    type Target = TokenLockHeader;
 
-#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, BorshSchema)]
-pub struct TokenLockAccount {
-    pub counter: u32,    
-    pub max_release_delay: u32,    
-    pub min_timelock_amount: u32,    
-    pub release_schedules: Vec<ReleaseSchedule>,
-    pub timelocks: HashMap<Pubkey, Vec<Timelock>>,
-    pub total_tokens_unlocked: HashMap<Pubkey, u32>,
-    pub allowances: HashMap<Pubkey, HashMap<Pubkey, u32>>,
+    fn deref(&self) -> &TokenLockHeader{
+        self.header
+    }
+}
+
+impl<'a> DerefMut for TokenLockAccount<'a>{
+    fn deref_mut(&mut self) -> &mut TokenLockHeader{
+        self.header
+    }
 }
 
-impl TokenLockAccount{
-    pub fn add_timelock(&mut self, addr: &Pubkey, time_lock: Timelock)-> i32{
-        if let Some(timelocks_for_addr) = self.timelocks.get_mut(addr){
-            timelocks_for_addr.push(time_lock);
-            return (timelocks_for_addr.len() - 1) as i32;
+impl<'a> TokenLockAccount<'a>{
+    pub const SEED: &'static [u8] = b"tokenlock";
+    pub const VAULT_SEED: &'static [u8] = b"vault";
+    pub const DISCRIMINATOR: [u8; 8] = *b"tokenlck";
+    pub const VERSION: u8 = 1;
+    pub const MAX_RELEASE_SCHEDULES: usize = 32;
+    pub const RELEASE_SCHEDULES_OFFSET: usize = TokenLockHeader::LEN;
+    pub const TIMELOCKS_OFFSET: usize = Self::RELEASE_SCHEDULES_OFFSET + Self::MAX_RELEASE_SCHEDULES * ReleaseSchedule::LEN;
+    /// Timelocks fitting the largest account the program can create. The runtime of solana-program 1.7.9
+    /// caps the data of accounts created by a program at `MAX_PERMITTED_DATA_INCREASE` and can't resize
+    /// them later, so token lock accounts can't grow as timelocks are added.
+    pub const MAX_TIMELOCKS: usize = (MAX_PERMITTED_DATA_INCREASE - Self::TIMELOCKS_OFFSET) / Timelock::LEN;
+    /// Seconds between claiming the surplus and sweeping it without a treasury authority
+    pub const SURPLUS_CLAIM_DELAY: u32 = 7 * 24 * 60 * 60;
+
+    /// The token lock account PDA of a mint and the authority that initializes it, one per nonce.
+    /// Nobody else can take the address of an authority's lockup.
+    pub fn find_address(mint: &Pubkey, authority: &Pubkey, nonce: u8, program_id: &Pubkey) -> (Pubkey, u8){
+        Pubkey::find_program_address(&[Self::SEED, mint.as_ref(), authority.as_ref(), &[nonce]], program_id)
+    }
+
+    /// The token account holding the locked tokens of a token lock account.
+    /// It is a PDA that is also its own token owner, so the program can sign for it.
+    /// For native SOL lockups it is a data-less account owned by the program holding the lamports.
+    pub fn find_vault(token: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8){
+        Pubkey::find_program_address(&[Self::VAULT_SEED, token.as_ref()], program_id)
+    }
+
+    /// Bytes needed for a token lock account with room for `max_timelocks` timelocks
+    pub fn space(max_timelocks: usize) -> usize{
+        Self::TIMELOCKS_OFFSET + max_timelocks * Timelock::LEN
+    }
+
+    /// Views the data of an initialized token lock account.
+    pub fn load_mut(data: &'a mut [u8]) -> Result<Self, ProgramError>{
+        if data.len() < Self::TIMELOCKS_OFFSET {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let (header, rest) = data.split_at_mut(TokenLockHeader::LEN);
+        let (release_schedules, timelocks) = rest.split_at_mut(Self::TIMELOCKS_OFFSET - Self::RELEASE_SCHEDULES_OFFSET);
+        let capacity = timelocks.len() / Timelock::LEN;
+        let account = TokenLockAccount {
+            header: try_from_bytes_mut(header).map_err(|_| ProgramError::InvalidAccountData)?,
+            release_schedules: try_cast_slice_mut(release_schedules).map_err(|_| ProgramError::InvalidAccountData)?,
+            timelocks: try_cast_slice_mut(&mut timelocks[..capacity * Timelock::LEN]).map_err(|_| ProgramError::InvalidAccountData)?,
+        };
+        if account.discriminator != Self::DISCRIMINATOR || account.version != Self::VERSION {
+            return Err(ProgramError::InvalidAccountData);
         }
-        return -1;
+        if account.release_schedule_count as usize > Self::MAX_RELEASE_SCHEDULES || account.timelock_count as usize > capacity {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(account)
     }
 
-    pub fn get_timelock(&self, addr: &Pubkey, idx: usize)-> Option<&Timelock>{
-        if let Some(timelocks_for_addr) = self.timelocks.get(addr){
-            return timelocks_for_addr.get(idx);
+    /// Writes the header of a new token lock account into zeroed data and views it.
+    pub fn init(data: &'a mut [u8], mint: &Pubkey, authority: &Pubkey, nonce: u8, max_release_delay: u32, min_timelock_amount: u32, treasury_authority: Option<Pubkey>) -> Result<Self, ProgramError>{
+        if data.len() < Self::TIMELOCKS_OFFSET {
+            return Err(ProgramError::AccountDataTooSmall);
         }
-        return None;
+        if data.iter().any(|byte| *byte != 0) {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        let header: &mut TokenLockHeader = try_from_bytes_mut(&mut data[..TokenLockHeader::LEN]).map_err(|_| ProgramError::InvalidAccountData)?;
+        header.discriminator = Self::DISCRIMINATOR;
+        header.version = Self::VERSION;
+        header.mint = *mint;
+        header.authority = *authority;
+        header.nonce = nonce;
+        header.max_release_delay = max_release_delay;
+        header.min_timelock_amount = min_timelock_amount;
+        header.set_treasury_authority(treasury_authority);
+        Self::load_mut(data)
+    }
+
+    /// Number of timelocks the account has room for
+    pub fn timelock_capacity(&self) -> usize{
+        self.timelocks.len()
+    }
+
+    pub fn release_schedules(&self) -> &[ReleaseSchedule]{
+        &self.release_schedules[..self.header.release_schedule_count as usize]
+    }
+
+    /// All timelocks in the order they were added
+    pub fn timelocks(&self) -> &[Timelock]{
+        &self.timelocks[..self.header.timelock_count as usize]
+    }
+
+    /// The timelocks of `addr` with their indices
+    pub fn timelocks_of<'s>(&'s self, addr: &'s Pubkey) -> impl Iterator<Item = (usize, &'s Timelock)> + 's{
+        self.timelocks().iter().enumerate().filter(move |(_, timelock)| &timelock.recipient == addr)
+    }
+
+    pub fn add_timelock(&mut self, addr: &Pubkey, mut time_lock: Timelock)-> Result<usize, ProgramError>{
+        let idx = self.header.timelock_count as usize;
+        if idx >= self.timelocks.len() {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        time_lock.recipient = *addr;
+        self.timelocks[idx] = time_lock;
+        self.header.timelock_count += 1;
+        return Ok(idx);
+    }
+
+    /// Takes a timelock out, leaving an empty one in its slot so the indices of the other timelocks don't change.
+    pub fn take_timelock(&mut self, addr: &Pubkey, idx: usize)-> Option<Timelock>{
+        let timelock = self.get_timelock_mut(addr, idx)?;
+        let mut empty = Timelock::new(timelock.schedule_id, timelock.commencement_timestamp, 0);
+        empty.recipient = timelock.recipient;
+        return Some(std::mem::replace(timelock, empty));
+    }
+
+    pub fn get_timelock(&self, addr: &Pubkey, idx: usize)-> Option<&Timelock>{
+        self.timelocks().get(idx).filter(|timelock| &timelock.recipient == addr)
     }
 
     pub fn get_timelock_mut(&mut self, addr: &Pubkey, idx: usize)-> Option<&mut Timelock>{
-        if let Some(timelocks_for_addr) = self.timelocks.get_mut(addr){
-            return timelocks_for_addr.get_mut(idx);
+        let count = self.header.timelock_count as usize;
+        self.timelocks[..count].get_mut(idx).filter(|timelock| &timelock.recipient == addr)
+    }
+
+    pub fn add_release_schedule(&mut self, release_schedule: ReleaseSchedule) -> Result<u32, ProgramError>{
+        let idx = self.header.release_schedule_count;
+        if idx as usize >= Self::MAX_RELEASE_SCHEDULES {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        self.release_schedules[idx as usize] = release_schedule;
+        self.header.release_schedule_count += 1;
+        return Ok(idx);
+    }
+}
+
+
+/// A pending cancellation of a timelock that needs more than one canceler to approve.
+/// Stored in a PDA derived from the token lock account, the target and the timelock index,
+/// until it is executed or closed.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, BorshSchema)]
+pub struct CancelProposal {
+    pub target: Pubkey,
+    pub timelock_index: u32,
+    pub reclaim_token_to: Pubkey,
+    pub mode: CancelMode,
+    /// Paid for the proposal account and gets the rent back when it is executed or closed
+    pub proposer: Pubkey,
+    /// It can no longer be approved or executed from then on, only closed
+    pub expires_at: u32,
+    pub approvals: Vec<Pubkey>,
+}
+
+impl CancelProposal{
+    pub const SEED: &'static [u8] = b"cancel";
+    pub const MAX_APPROVALS: usize = Timelock::MAX_CANCELABLE_BY;
+    pub const LEN: usize = 32 + 4 + 32 + 1 + 32 + 4 + 4 + 32 * Self::MAX_APPROVALS;
+    /// Seconds a proposal stays open
+    pub const LIFETIME: u32 = 30 * 24 * 60 * 60;
+
+    pub fn find_address(token: &Pubkey, target: &Pubkey, timelock_index: u32, program_id: &Pubkey) -> (Pubkey, u8){
+        Pubkey::find_program_address(
+            &[Self::SEED, token.as_ref(), target.as_ref(), &timelock_index.to_le_bytes()],
+            program_id,
+        )
+    }
+
+    pub fn has_approval(&self, canceler: &Pubkey) -> bool{
+        self.approvals.iter().any(|approval| approval == canceler)
+    }
+}
+
+
+/// Name, symbol and uri of a mint created by InitializeToken.
+/// Stored in a PDA derived from the mint.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, BorshSchema)]
+pub struct TokenMetadata {
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+}
+
+impl TokenMetadata{
+    pub const SEED: &'static [u8] = b"metadata";
+    pub const MAX_NAME_LENGTH: usize = 32;
+    pub const MAX_SYMBOL_LENGTH: usize = 10;
+    pub const MAX_URI_LENGTH: usize = 200;
+    /// Seed of the PDA that is the mint authority until the supply is minted
+    pub const MINT_AUTHORITY_SEED: &'static [u8] = b"mint";
+
+    pub fn find_address(mint: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8){
+        Pubkey::find_program_address(&[Self::SEED, mint.as_ref()], program_id)
+    }
+
+    pub fn find_mint_authority(mint: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8){
+        Pubkey::find_program_address(&[Self::MINT_AUTHORITY_SEED, mint.as_ref()], program_id)
+    }
+}
+
+
+/// The vault balance compared with what the timelocks still owe. Built by the Reconcile instruction
+/// on-chain, and off-chain from a fetched token lock account and vault balance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReconcileReport {
+    /// Balance of the vault, without the rent-exempt minimum of a native SOL vault
+    pub vault_balance: u64,
+    /// Sum of `total_amount - tokens_transferred` over all timelocks
+    pub obligations: u64,
+    pub recipients: u32,
+    pub timelocks: u32,
+}
+
+impl ReconcileReport{
+    pub fn new(account: &TokenLockAccount, vault_balance: u64) -> Self{
+        let mut report = ReconcileReport {
+            vault_balance,
+            obligations: 0,
+            recipients: 0,
+            timelocks: 0,
+        };
+        let mut recipients = BTreeSet::new();
+        for timelock in account.timelocks() {
+            recipients.insert(timelock.recipient);
+            report.timelocks += 1;
+            report.obligations += (timelock.total_amount - timelock.tokens_transferred) as u64;
         }
-        return None;
+        report.recipients = recipients.len() as u32;
+        report
+    }
+
+    /// What the vault holds beyond the obligations
+    pub fn surplus(&self) -> u64{
+        self.vault_balance.saturating_sub(self.obligations)
+    }
+
+    /// What the vault is missing to cover the obligations
+    pub fn shortfall(&self) -> u64{
+        self.obligations.saturating_sub(self.vault_balance)
     }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Name, offset and size of every field of `$value`. The pattern doesn't compile when it leaves out a field.
+    macro_rules! fields {
+        ($value:expr, $type:ident { $($field:ident),* $(,)? }) => {{
+            let value = &$value;
+            let $type { $($field),* } = value;
+            let start = value as *const $type as usize;
+            vec![$((stringify!($field), $field as *const _ as usize - start, std::mem::size_of_val($field))),*]
+        }};
+    }
+
+    /// Bytes a field of `notation` takes, `[T; N]` for arrays
+    fn notation_size(notation: &str) -> usize {
+        if let Some((elements, length)) = notation.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')).and_then(|rest| rest.split_once("; ")) {
+            return notation_size(elements) * length.parse::<usize>().unwrap();
+        }
+        match notation {
+            "u8" => 1,
+            "u32" => 4,
+            "u64" => 8,
+            "publicKey" => 32,
+            _ => panic!("unknown type {}", notation),
+        }
+    }
+
+    /// Lays `table` out field after field, as the IDL does
+    fn table_layout(table: &[(&'static str, &str)]) -> Vec<(&'static str, usize, usize)> {
+        let mut offset = 0;
+        table.iter()
+            .map(|(name, notation)| {
+                let field = (*name, offset, notation_size(notation));
+                offset += field.2;
+                field
+            })
+            .collect()
+    }
+
+    #[test]
+    fn field_tables_match_the_structs() {
+        let mut header = fields!(TokenLockHeader::zeroed(), TokenLockHeader {
+            discriminator, mint, treasury_authority, original_funder, authority, version, nonce, has_treasury_authority,
+            has_original_funder, counter, max_release_delay, min_timelock_amount, release_schedule_count, timelock_count,
+            surplus_claimable_at, surplus_claim_amount, _reserved,
+        });
+        header.sort_by_key(|(_, offset, _)| *offset);
+        assert_eq!(table_layout(TokenLockHeader::FIELDS), header);
+
+        let mut timelock = fields!(Timelock::zeroed(), Timelock {
+            schedule_id, commencement_timestamp, tokens_transferred, total_amount, vesting_stopped_at, recipient,
+            cancelable_by, cancelable_by_count, cancel_threshold, _reserved,
+        });
+        timelock.sort_by_key(|(_, offset, _)| *offset);
+        assert_eq!(table_layout(Timelock::FIELDS), timelock);
+    }
+
+    #[test]
+    fn zero_copy_layout() {
+        assert_eq!(TokenLockHeader::LEN, 176);
+        assert_eq!(ReleaseSchedule::LEN, 16);
+        assert_eq!(Timelock::LEN, 376);
+        assert_eq!(TokenLockAccount::space(2), 176 + 32 * 16 + 2 * 376);
+        assert_eq!(TokenLockAccount::MAX_TIMELOCKS, 25);
+
+        let mint = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let mut data = vec![0; TokenLockAccount::space(2)];
+        {
+            let mut account = TokenLockAccount::init(&mut data, &mint, &owner, 3, 100, 1, None).unwrap();
+            assert_eq!(account.timelock_capacity(), 2);
+            let mut timelock = Timelock::new(0, 10, 500);
+            timelock.set_cancelable_by(&[owner]);
+            account.add_timelock(&owner, timelock).unwrap();
+            account.add_timelock(&owner, Timelock::new(0, 10, 500)).unwrap();
+            assert!(account.add_timelock(&owner, Timelock::new(0, 10, 500)).is_err());
+        }
+        assert!(TokenLockAccount::init(&mut data, &mint, &owner, 3, 100, 1, None).is_err());
+
+        let account = TokenLockAccount::load_mut(&mut data).unwrap();
+        assert_eq!((account.mint, account.authority(), account.nonce, account.max_release_delay), (mint, owner, 3, 100));
+        assert_eq!(account.timelocks_of(&owner).count(), 2);
+        assert_eq!(account.get_timelock(&owner, 0).unwrap().cancelable_by(), &[owner]);
+        assert!(account.get_timelock(&mint, 0).is_none());
 
-    pub fn add_release_schedule(&mut self, release_schedule: ReleaseSchedule) ->i32{
-        self.release_schedules.push(release_schedule);
-        return (self.release_schedules.len()-1) as i32;
+        data[0] = 0;
+        assert!(TokenLockAccount::load_mut(&mut data).is_err());
     }
-   
 }
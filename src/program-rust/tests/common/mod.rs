@@ -0,0 +1,241 @@
+//! Shared setup of the integration tests: a program test with the token lock program and SPL Token,
+//! mints and token accounts, and a lockup ready to be funded
+#![allow(dead_code)]
+use tokenlock::{
+    entrypoint::process_instruction,
+    instruction::TokenLockInstruction,
+    types::{ReleaseSchedule, Timelock, TokenLockAccount},
+};
+
+use solana_program::{
+    clock::{Clock, DEFAULT_MS_PER_SLOT},
+    instruction::{Instruction, InstructionError},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_instruction,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+    transport::TransportError,
+};
+
+pub const MAX_RELEASE_DELAY: u32 = 365 * 24 * 60 * 60;
+pub const MIN_TIMELOCK_AMOUNT: u32 = 100;
+pub const HOUR: u32 = 60 * 60;
+/// Warps needed to reach a timestamp, the clock doesn't follow the slots exactly
+const MAX_WARPS: usize = 100;
+
+/// The token lock program, built natively. SPL Token is loaded by `ProgramTest` itself.
+pub fn program_test() -> ProgramTest {
+    ProgramTest::new(
+        "tokenlock", // Run the BPF version with `cargo test-bpf`
+        tokenlock::id(),
+        processor!(process_instruction), // Run the native version with `cargo test`
+    )
+}
+
+/// Sends the instructions in one transaction paid by the context's payer and signed by `signers` as well
+pub async fn process(context: &mut ProgramTestContext, instructions: &[Instruction], signers: &[&Keypair]) -> Result<(), TransportError> {
+    let transaction = sign(context, instructions, signers).await;
+    context.banks_client.process_transaction(transaction).await
+}
+
+pub async fn sign(context: &mut ProgramTestContext, instructions: &[Instruction], signers: &[&Keypair]) -> Transaction {
+    let recent_blockhash = context.banks_client.get_recent_blockhash().await.unwrap();
+    let mut keypairs = vec![&context.payer];
+    keypairs.extend_from_slice(signers);
+    Transaction::new_signed_with_payer(instructions, Some(&context.payer.pubkey()), &keypairs, recent_blockhash)
+}
+
+/// Asserts the transaction failed in its first instruction with `error`
+pub fn assert_instruction_error(result: Result<(), TransportError>, error: InstructionError) {
+    assert_eq!(result.unwrap_err().unwrap(), TransactionError::InstructionError(0, error));
+}
+
+/// A new keypair with lamports to pay for the rent of the accounts it signs for
+pub async fn funded_keypair(context: &mut ProgramTestContext) -> Keypair {
+    let keypair = Keypair::new();
+    let transfer = system_instruction::transfer(&context.payer.pubkey(), &keypair.pubkey(), 1_000_000_000);
+    process(context, &[transfer], &[]).await.unwrap();
+    keypair
+}
+
+pub async fn create_mint(context: &mut ProgramTestContext, authority: &Pubkey, decimals: u8) -> Pubkey {
+    let mint = Keypair::new();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let instructions = [
+        system_instruction::create_account(
+            &context.payer.pubkey(),
+            &mint.pubkey(),
+            rent.minimum_balance(spl_token::state::Mint::LEN),
+            spl_token::state::Mint::LEN as u64,
+            &spl_token::id(),
+        ),
+        spl_token::instruction::initialize_mint(&spl_token::id(), &mint.pubkey(), authority, None, decimals).unwrap(),
+    ];
+    process(context, &instructions, &[&mint]).await.unwrap();
+    mint.pubkey()
+}
+
+pub async fn create_token_account(context: &mut ProgramTestContext, mint: &Pubkey, owner: &Pubkey) -> Pubkey {
+    let account = Keypair::new();
+    let rent = context.banks_client.get_rent().await.unwrap();
+    let instructions = [
+        system_instruction::create_account(
+            &context.payer.pubkey(),
+            &account.pubkey(),
+            rent.minimum_balance(spl_token::state::Account::LEN),
+            spl_token::state::Account::LEN as u64,
+            &spl_token::id(),
+        ),
+        spl_token::instruction::initialize_account(&spl_token::id(), &account.pubkey(), mint, owner).unwrap(),
+    ];
+    process(context, &instructions, &[&account]).await.unwrap();
+    account.pubkey()
+}
+
+pub async fn mint_to(context: &mut ProgramTestContext, mint: &Pubkey, authority: &Keypair, account: &Pubkey, amount: u64) {
+    let instruction = spl_token::instruction::mint_to(&spl_token::id(), mint, account, &authority.pubkey(), &[], amount).unwrap();
+    process(context, &[instruction], &[authority]).await.unwrap();
+}
+
+pub async fn token_balance(context: &mut ProgramTestContext, account: &Pubkey) -> u64 {
+    let account = context.banks_client.get_account(*account).await.unwrap().expect("token account not found");
+    spl_token::state::Account::unpack(&account.data).unwrap().amount
+}
+
+/// The timelocks of a token lock account in index order
+pub async fn timelocks(context: &mut ProgramTestContext, token_lock: &Pubkey) -> Vec<Timelock> {
+    let mut account = context.banks_client.get_account(*token_lock).await.unwrap().expect("token lock not found");
+    TokenLockAccount::load_mut(&mut account.data).unwrap().timelocks().to_vec()
+}
+
+pub async fn now(context: &mut ProgramTestContext) -> u32 {
+    let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp as u32
+}
+
+/// Warps forward until the clock reads at least `timestamp`
+pub async fn warp_to_timestamp(context: &mut ProgramTestContext, timestamp: u32) {
+    for _ in 0..MAX_WARPS {
+        let clock: Clock = context.banks_client.get_sysvar().await.unwrap();
+        if clock.unix_timestamp >= timestamp as i64 {
+            return;
+        }
+        let slots = ((timestamp as i64 - clock.unix_timestamp) as u64 * 1000 / DEFAULT_MS_PER_SLOT).max(1);
+        context.warp_to_slot(clock.slot + slots).unwrap();
+    }
+    panic!("clock did not reach {} after {} warps", timestamp, MAX_WARPS);
+}
+
+/// An SPL token lockup with one release schedule and a funder holding tokens of its mint
+pub struct Lockup {
+    pub context: ProgramTestContext,
+    pub mint: Pubkey,
+    pub mint_authority: Keypair,
+    pub token_lock: Pubkey,
+    pub vault: Pubkey,
+    pub schedule: ReleaseSchedule,
+    pub funder: Keypair,
+    pub funder_tokens: Pubkey,
+}
+
+impl Lockup {
+    /// 4 releases an hour apart, a quarter released right at commencement
+    pub const SCHEDULE: ReleaseSchedule = ReleaseSchedule {
+        release_count: 4,
+        delay_until_first_release_in_seconds: 0,
+        initial_release_portion_in_bips: 2500,
+        period_between_releases_in_seconds: HOUR,
+    };
+    pub const FUNDER_BALANCE: u64 = 1_000_000;
+
+    pub async fn new(max_timelocks: usize) -> Self {
+        Self::start(program_test(), max_timelocks).await
+    }
+
+    /// Initializes the lockup with room for `max_timelocks` and creates `SCHEDULE` as schedule 0
+    pub async fn start(program_test: ProgramTest, max_timelocks: usize) -> Self {
+        let mut context = program_test.start_with_context().await;
+        let mint_authority = Keypair::new();
+        let mint = create_mint(&mut context, &mint_authority.pubkey(), 6).await;
+        let payer = context.payer.pubkey();
+        let (token_lock, _) = TokenLockAccount::find_address(&mint, &payer, 0, &tokenlock::id());
+        let (vault, _) = TokenLockAccount::find_vault(&token_lock, &tokenlock::id());
+
+        let schedule = Self::SCHEDULE;
+        let instructions = [
+            TokenLockInstruction::initialize_token_lock(
+                &payer,
+                &mint,
+                &spl_token::id(),
+                0,
+                MAX_RELEASE_DELAY,
+                MIN_TIMELOCK_AMOUNT,
+                TokenLockAccount::space(max_timelocks) as u32,
+                None,
+            ),
+            TokenLockInstruction::create_release_schedule(
+                &token_lock,
+                &payer,
+                schedule.release_count,
+                schedule.delay_until_first_release_in_seconds,
+                schedule.initial_release_portion_in_bips,
+                schedule.period_between_releases_in_seconds,
+            ),
+        ];
+        process(&mut context, &instructions, &[]).await.unwrap();
+
+        let funder = funded_keypair(&mut context).await;
+        let funder_tokens = create_token_account(&mut context, &mint, &funder.pubkey()).await;
+        mint_to(&mut context, &mint, &mint_authority, &funder_tokens, Self::FUNDER_BALANCE).await;
+        Lockup { context, mint, mint_authority, token_lock, vault, schedule, funder, funder_tokens }
+    }
+
+    /// Funds a timelock for `to` on schedule 0, which has to get `timelock_index`
+    pub fn fund_instruction(&self, to: &Pubkey, amount: u32, commencement_timestamp: u32, cancelable_by: &[Pubkey], timelock_index: u32) -> Instruction {
+        TokenLockInstruction::fund_release_schedule(
+            &self.token_lock,
+            &self.funder.pubkey(),
+            &self.funder_tokens,
+            &self.mint,
+            &spl_token::id(),
+            to,
+            amount,
+            commencement_timestamp,
+            0,
+            cancelable_by,
+            0,
+            timelock_index,
+        )
+    }
+
+    /// Funds a timelock for `to` on schedule 0
+    pub async fn fund(&mut self, to: &Pubkey, amount: u32, commencement_timestamp: u32, cancelable_by: &[Pubkey]) -> Result<(), TransportError> {
+        let timelock_index = self.timelocks().await.len() as u32;
+        let instruction = self.fund_instruction(to, amount, commencement_timestamp, cancelable_by, timelock_index);
+        process(&mut self.context, &[instruction], &[&self.funder]).await
+    }
+
+    /// Transfers out of `owner`'s timelock to `destination`, a token account of `to`
+    pub fn transfer_instruction(&self, owner: &Pubkey, to: &Pubkey, destination: &Pubkey, value: u32, timelock_index: u32) -> Instruction {
+        TokenLockInstruction::transfer_timelock(&self.token_lock, owner, &self.mint, &spl_token::id(), to, destination, value, timelock_index)
+    }
+
+    /// A new token account of the lockup's mint
+    pub async fn token_account(&mut self, owner: &Pubkey) -> Pubkey {
+        let mint = self.mint;
+        create_token_account(&mut self.context, &mint, owner).await
+    }
+
+    pub async fn balance(&mut self, account: &Pubkey) -> u64 {
+        token_balance(&mut self.context, account).await
+    }
+
+    pub async fn timelocks(&mut self) -> Vec<Timelock> {
+        let token_lock = self.token_lock;
+        timelocks(&mut self.context, &token_lock).await
+    }
+}
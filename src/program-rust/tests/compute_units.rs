@@ -0,0 +1,429 @@
+//! Compute units of each instruction as timelocks pile up, with all of them held by the recipient the
+//! instruction works on and with them spread over the recipients of the lockup.
+//!
+//! The lockups have room for one more timelock, so funding and splitting fill the account's last free
+//! record, and instructions draining a timelock drain one other than the last. The largest lockup is the
+//! fullest one the program can initialize, `TokenLockAccount::MAX_TIMELOCKS`, with a record left to fill.
+//!
+//! Only the BPF build is metered, run it with `cargo test-bpf --test compute_units`. The report goes to
+//! `target/compute_units.md` and the run fails if an instruction got more expensive than its baseline in
+//! `tests/compute_units.baseline` by more than `CU_REGRESSION_THRESHOLD` percent, 5 by default. Units
+//! the baseline has no entry for yet are added to it. `CU_UPDATE_BASELINE=1` records all measured units
+//! as the new baseline.
+#![cfg(feature = "test-bpf")]
+mod common;
+
+use std::{
+    collections::BTreeMap,
+    env,
+    fmt::Write,
+    fs,
+    path::Path,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+use common::*;
+use tokenlock::{
+    instruction::{BurnSource, TokenLockInstruction},
+    types::{CancelMode, ReleaseSchedule, Timelock, TokenLockAccount},
+};
+
+use solana_program::{
+    instruction::Instruction,
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_program,
+};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    signature::{Keypair, Signer},
+};
+
+/// Up to the largest lockup that still has room for the timelock funding and splitting add
+const TIMELOCKS: [usize; 3] = [1, 10, TokenLockAccount::MAX_TIMELOCKS - 1];
+/// High enough to measure instructions beyond the limit of the cluster
+const MAX_UNITS: u64 = 10_000_000;
+/// The compute budget of an instruction on the cluster
+const INSTRUCTION_UNITS: u64 = 200_000;
+const DEFAULT_THRESHOLD_PERCENT: f64 = 5.0;
+const BASELINE_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/compute_units.baseline");
+const REPORT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/target/compute_units.md");
+
+const AMOUNT: u32 = 1000;
+/// Commencing at 0 half of every timelock is unlocked until 2038
+const SCHEDULE: ReleaseSchedule = ReleaseSchedule {
+    release_count: 4,
+    delay_until_first_release_in_seconds: 0,
+    initial_release_portion_in_bips: 2500,
+    period_between_releases_in_seconds: 1 << 30,
+};
+
+/// The compute units the runtime last logged for the token lock program
+static CONSUMED: AtomicU64 = AtomicU64::new(0);
+/// How often the runtime logged the units of the token lock program
+static CONSUMED_COUNT: AtomicUsize = AtomicUsize::new(0);
+static LOGGER: UnitsLogger = UnitsLogger;
+
+/// Picks `Program <id> consumed <units> of <max> compute units` of the token lock program out of the
+/// runtime's log. Installed before `ProgramTest` sets up its own logger, which then stays unused.
+struct UnitsLogger;
+
+impl log::Log for UnitsLogger {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        let message = record.args().to_string();
+        let prefix = format!("Program {} consumed ", tokenlock::id());
+        let units = message.strip_prefix(&prefix).and_then(|rest| rest.split(' ').next()).and_then(|units| units.parse().ok());
+        if let Some(units) = units {
+            CONSUMED.store(units, Ordering::SeqCst);
+            CONSUMED_COUNT.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Layout {
+    /// All timelocks are held by the recipient
+    Recipient,
+    /// Each timelock has its own recipient, except for the last two held by the recipient
+    Lockup,
+}
+
+impl Layout {
+    fn name(&self) -> &'static str {
+        match self {
+            Layout::Recipient => "recipient",
+            Layout::Lockup => "lockup",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Bench {
+    CreateReleaseSchedule,
+    FundReleaseSchedule,
+    TransferTimelock,
+    SplitTimelock,
+    MergeTimelocks,
+    TransferTimelockOwnership,
+    CancelTimelock,
+    Burn,
+    Reconcile,
+}
+
+impl Bench {
+    const ALL: [Bench; 9] = [
+        Bench::CreateReleaseSchedule,
+        Bench::FundReleaseSchedule,
+        Bench::TransferTimelock,
+        Bench::SplitTimelock,
+        Bench::MergeTimelocks,
+        Bench::TransferTimelockOwnership,
+        Bench::CancelTimelock,
+        Bench::Burn,
+        Bench::Reconcile,
+    ];
+
+    fn name(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    /// Merging needs two timelocks of the recipient
+    fn min_timelocks(&self) -> usize {
+        match self {
+            Bench::MergeTimelocks => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// An SPL token lockup with `timelocks` half unlocked timelocks of `AMOUNT` cancelable by `canceler` and
+/// room for one more, its accounts added to the program test as they are after funding
+struct Fixture {
+    mint: Pubkey,
+    token_lock: Pubkey,
+    funder: Keypair,
+    funder_tokens: Pubkey,
+    recipient: Keypair,
+    recipient_tokens: Pubkey,
+    /// The indices of the recipient's timelocks
+    recipient_timelocks: Vec<u32>,
+    /// Timelocks funded so far
+    timelocks: u32,
+    canceler: Keypair,
+}
+
+impl Fixture {
+    fn add_to(program_test: &mut ProgramTest, layout: Layout, timelocks: usize) -> Self {
+        let rent = Rent::default();
+        let mint = Pubkey::new_unique();
+        // the funder initialized the lockup
+        let funder = Keypair::new();
+        let authority = funder.pubkey();
+        let (token_lock, _) = TokenLockAccount::find_address(&mint, &authority, 0, &tokenlock::id());
+        let (vault, _) = TokenLockAccount::find_vault(&token_lock, &tokenlock::id());
+        let recipient = Keypair::new();
+        let canceler = Keypair::new();
+
+        let mut data = vec![0; TokenLockAccount::space(timelocks + 1)];
+        let mut recipient_timelocks = vec![];
+        {
+            let mut account = TokenLockAccount::init(&mut data, &mint, &authority, 0, MAX_RELEASE_DELAY, MIN_TIMELOCK_AMOUNT, None).unwrap();
+            account.add_release_schedule(SCHEDULE).unwrap();
+            for idx in 0..timelocks {
+                let recipient_holds = layout == Layout::Recipient || idx + 2 >= timelocks;
+                let owner = if recipient_holds { recipient.pubkey() } else { Pubkey::new_unique() };
+                let mut timelock = Timelock::new(0, 0, AMOUNT);
+                timelock.set_cancelable_by(&[canceler.pubkey()]);
+                account.add_timelock(&owner, timelock).unwrap();
+                if recipient_holds {
+                    recipient_timelocks.push(idx as u32);
+                }
+            }
+        }
+        program_test.add_account(token_lock, Account {
+            lamports: rent.minimum_balance(data.len()),
+            data,
+            owner: tokenlock::id(),
+            ..Account::default()
+        });
+
+        let locked = AMOUNT as u64 * timelocks as u64;
+        let funder_tokens = Pubkey::new_unique();
+        let recipient_tokens = Pubkey::new_unique();
+        let token_account = |owner: Pubkey, amount: u64| spl_token::state::Account {
+            mint,
+            owner,
+            amount,
+            state: spl_token::state::AccountState::Initialized,
+            ..spl_token::state::Account::default()
+        };
+        let mint_data = spl_token::state::Mint {
+            mint_authority: COption::None,
+            supply: locked + Lockup::FUNDER_BALANCE,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        };
+        let token_rent = rent.minimum_balance(spl_token::state::Account::LEN);
+        add_packed(program_test, mint, rent.minimum_balance(spl_token::state::Mint::LEN), &mint_data, &spl_token::id());
+        add_packed(program_test, vault, token_rent, &token_account(vault, locked), &spl_token::id());
+        add_packed(program_test, funder_tokens, token_rent, &token_account(funder.pubkey(), Lockup::FUNDER_BALANCE), &spl_token::id());
+        add_packed(program_test, recipient_tokens, token_rent, &token_account(recipient.pubkey(), 0), &spl_token::id());
+        for signer in [&funder, &recipient, &canceler].iter() {
+            program_test.add_account(signer.pubkey(), Account::new(1_000_000_000, 0, &system_program::id()));
+        }
+        Fixture { mint, token_lock, funder, funder_tokens, recipient, recipient_tokens, recipient_timelocks, timelocks: timelocks as u32, canceler }
+    }
+
+    /// The instruction to measure and who besides the payer signs it
+    fn instruction(&self, bench: Bench) -> (Instruction, Vec<&Keypair>) {
+        let token_program = spl_token::id();
+        let recipient = self.recipient.pubkey();
+        let last = *self.recipient_timelocks.last().unwrap();
+        // drained by canceling, with later timelocks after it unless the lockup has just one
+        let first = self.recipient_timelocks[0];
+        match bench {
+            Bench::CreateReleaseSchedule => (
+                TokenLockInstruction::create_release_schedule(&self.token_lock, &self.funder.pubkey(), 12, 0, 0, 30 * 24 * 60 * 60),
+                vec![&self.funder],
+            ),
+            Bench::FundReleaseSchedule => (
+                TokenLockInstruction::fund_release_schedule(
+                    &self.token_lock,
+                    &self.funder.pubkey(),
+                    &self.funder_tokens,
+                    &self.mint,
+                    &token_program,
+                    &recipient,
+                    AMOUNT,
+                    0,
+                    0,
+                    &[self.canceler.pubkey()],
+                    0,
+                    self.timelocks,
+                ),
+                vec![&self.funder],
+            ),
+            Bench::TransferTimelock => (
+                TokenLockInstruction::transfer_timelock(&self.token_lock, &recipient, &self.mint, &token_program, &recipient, &self.recipient_tokens, 1, last),
+                vec![&self.recipient],
+            ),
+            Bench::SplitTimelock => (
+                TokenLockInstruction::split_timelock(&self.token_lock, &recipient, &[], last, AMOUNT / 2, &recipient),
+                vec![&self.recipient],
+            ),
+            Bench::MergeTimelocks => {
+                let other = self.recipient_timelocks[self.recipient_timelocks.len() - 2];
+                (TokenLockInstruction::merge_timelocks(&self.token_lock, &recipient, last, other), vec![&self.recipient])
+            }
+            Bench::TransferTimelockOwnership => (
+                TokenLockInstruction::transfer_timelock_ownership(&self.token_lock, &recipient, None, &[self.canceler.pubkey()], last, &Pubkey::new_unique()),
+                vec![&self.recipient, &self.canceler],
+            ),
+            Bench::CancelTimelock => (
+                TokenLockInstruction::cancel_timelock(
+                    &self.token_lock,
+                    &self.canceler.pubkey(),
+                    &self.mint,
+                    &token_program,
+                    &recipient,
+                    &self.recipient_tokens,
+                    first,
+                    &self.funder.pubkey(),
+                    &self.funder_tokens,
+                    CancelMode::PayOut,
+                ),
+                vec![&self.canceler],
+            ),
+            Bench::Burn => (
+                TokenLockInstruction::burn(&self.token_lock, &recipient, &self.mint, &token_program, 1, BurnSource::Unlocked),
+                vec![&self.recipient],
+            ),
+            Bench::Reconcile => (TokenLockInstruction::reconcile(&self.token_lock, &token_program), vec![]),
+        }
+    }
+}
+
+/// Adds an account holding `state` packed, as the program owning it left it
+fn add_packed<T: Pack>(program_test: &mut ProgramTest, address: Pubkey, lamports: u64, state: &T, owner: &Pubkey) {
+    let mut data = vec![0; T::LEN];
+    state.pack_into_slice(&mut data);
+    program_test.add_account(address, Account { lamports, data, owner: *owner, ..Account::default() });
+}
+
+async fn measure(bench: Bench, layout: Layout, timelocks: usize) -> u64 {
+    let mut program_test = program_test();
+    program_test.set_bpf_compute_max_units(MAX_UNITS);
+    let fixture = Fixture::add_to(&mut program_test, layout, timelocks);
+    let mut context = program_test.start_with_context().await;
+    let (instruction, signers) = fixture.instruction(bench);
+
+    // setting up the program test lowers the level to its own filter
+    log::set_max_level(log::LevelFilter::Debug);
+    CONSUMED_COUNT.store(0, Ordering::SeqCst);
+    if let Err(err) = process(&mut context, &[instruction], &signers).await {
+        panic!("{:?} with {} timelocks per {} failed: {}", bench, timelocks, layout.name(), err);
+    }
+    assert_eq!(CONSUMED_COUNT.load(Ordering::SeqCst), 1, "expected the units of one instruction in the log");
+    CONSUMED.load(Ordering::SeqCst)
+}
+
+fn key(bench: Bench, layout: Layout, timelocks: usize) -> String {
+    format!("{} {} {}", bench.name(), layout.name(), timelocks)
+}
+
+/// Lines of `<instruction> <layout> <timelocks> <units>`, `#` starts a comment
+fn read_baseline(path: &Path) -> BTreeMap<String, u64> {
+    let text = fs::read_to_string(path)
+        .unwrap_or_else(|err| panic!("{}: {}, record the baseline with CU_UPDATE_BASELINE=1", path.display(), err));
+    text.lines()
+        .map(|line| line.split('#').next().unwrap().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let (key, units) = line.rsplit_once(' ').expect("baseline line without units");
+            (key.to_string(), units.parse().expect("baseline units are not a number"))
+        })
+        .collect()
+}
+
+fn write_baseline(path: &Path, measured: &BTreeMap<String, u64>) {
+    let mut text = String::from("# <instruction> <timelocks held by the recipient or across the lockup> <timelocks> <compute units>\n");
+    for (key, units) in measured {
+        writeln!(text, "{} {}", key, units).unwrap();
+    }
+    fs::write(path, text).unwrap();
+}
+
+fn report(measured: &BTreeMap<String, u64>, baseline: &BTreeMap<String, u64>) -> String {
+    let mut text = String::from("# Compute units per instruction\n\n");
+    writeln!(text, "Changes are relative to `tests/compute_units.baseline`. Entries marked with ! exceed the compute budget of {} units per instruction.\n", INSTRUCTION_UNITS).unwrap();
+    write!(text, "| Instruction | Timelocks held by |").unwrap();
+    for timelocks in TIMELOCKS.iter() {
+        write!(text, " {} |", timelocks).unwrap();
+    }
+    write!(text, "\n|---|---|").unwrap();
+    for _ in TIMELOCKS.iter() {
+        write!(text, "---:|").unwrap();
+    }
+    text.push('\n');
+    for bench in Bench::ALL.iter() {
+        for layout in [Layout::Recipient, Layout::Lockup].iter() {
+            write!(text, "| {} | {} |", bench.name(), layout.name()).unwrap();
+            for &timelocks in TIMELOCKS.iter() {
+                let key = key(*bench, *layout, timelocks);
+                match (measured.get(&key), baseline.get(&key)) {
+                    (None, _) => write!(text, " - |").unwrap(),
+                    (Some(units), base) => {
+                        let over = if *units > INSTRUCTION_UNITS { "!" } else { "" };
+                        let change = base.map(|base| format!(" ({:+.1}%)", change_percent(*units, *base))).unwrap_or_default();
+                        write!(text, " {}{}{} |", units, over, change).unwrap();
+                    }
+                }
+            }
+            text.push('\n');
+        }
+    }
+    text
+}
+
+fn change_percent(units: u64, base: u64) -> f64 {
+    (units as f64 - base as f64) * 100.0 / base.max(1) as f64
+}
+
+#[tokio::test]
+async fn compute_units() {
+    if env::var("BPF_OUT_DIR").is_err() {
+        panic!("compute units are only metered for the BPF build, run `cargo test-bpf --test compute_units`");
+    }
+    log::set_logger(&LOGGER).unwrap();
+    let threshold: f64 = env::var("CU_REGRESSION_THRESHOLD").map(|threshold| threshold.parse().expect("CU_REGRESSION_THRESHOLD is not a number")).unwrap_or(DEFAULT_THRESHOLD_PERCENT);
+
+    let mut measured = BTreeMap::new();
+    for bench in Bench::ALL.iter() {
+        for layout in [Layout::Recipient, Layout::Lockup].iter() {
+            for &timelocks in TIMELOCKS.iter().filter(|&&timelocks| timelocks >= bench.min_timelocks()) {
+                measured.insert(key(*bench, *layout, timelocks), measure(*bench, *layout, timelocks).await);
+            }
+        }
+    }
+
+    let baseline_path = Path::new(BASELINE_PATH);
+    let update_baseline = env::var("CU_UPDATE_BASELINE").is_ok();
+    let baseline = if update_baseline && !baseline_path.exists() { BTreeMap::new() } else { read_baseline(baseline_path) };
+    let report = report(&measured, &baseline);
+    fs::create_dir_all(Path::new(REPORT_PATH).parent().unwrap()).unwrap();
+    fs::write(REPORT_PATH, &report).unwrap();
+    println!("{}", report);
+
+    if update_baseline {
+        write_baseline(baseline_path, &measured);
+        return;
+    }
+    let unrecorded: Vec<&str> = measured.keys().filter(|key| !baseline.contains_key(*key)).map(String::as_str).collect();
+    if !unrecorded.is_empty() {
+        println!("added to the baseline:\n{}", unrecorded.join("\n"));
+        let mut recorded = baseline.clone();
+        recorded.extend(measured.iter().filter(|(key, _)| !baseline.contains_key(*key)).map(|(key, &units)| (key.clone(), units)));
+        write_baseline(baseline_path, &recorded);
+    }
+    let regressions: Vec<String> = measured.iter()
+        .filter_map(|(key, &units)| {
+            let base = *baseline.get(key)?;
+            let change = change_percent(units, base);
+            if change > threshold {
+                Some(format!("{}: {} -> {} ({:+.1}%)", key, base, units, change))
+            } else {
+                None
+            }
+        })
+        .collect();
+    assert!(regressions.is_empty(), "compute units up by more than {}%:\n{}", threshold, regressions.join("\n"));
+}
@@ -1,112 +1,297 @@
-use tokenlock::{entrypoint::process_instruction, types::TokenLockAccount};
+mod common;
+
+use common::*;
+use tokenlock::{instruction::TokenLockInstruction, processor::Processor, types::{CancelMode, CancelProposal}};
 
 use borsh::BorshDeserialize;
+use solana_program::{instruction::InstructionError, pubkey::Pubkey, system_instruction};
 use solana_program_test::*;
 use solana_sdk::{
-    account::Account,
-    instruction::{AccountMeta, Instruction},
-    pubkey::Pubkey,
-    signature::Signer,
+    signature::{Keypair, Signer},
     transaction::Transaction,
+    transport::TransportError,
 };
-use std::mem;
 
+const AMOUNT: u32 = 1000;
+
+/// A lockup with a cancelable timelock of `AMOUNT` for a recipient, commencing now
+struct Scenario {
+    lockup: Lockup,
+    recipient: Keypair,
+    recipient_tokens: Pubkey,
+    cancelers: [Keypair; 2],
+    commenced: u32,
+}
+
+impl Scenario {
+    async fn new() -> Self {
+        let mut lockup = Lockup::new(4).await;
+        let recipient = funded_keypair(&mut lockup.context).await;
+        let recipient_tokens = lockup.token_account(&recipient.pubkey()).await;
+        let cancelers = [funded_keypair(&mut lockup.context).await, funded_keypair(&mut lockup.context).await];
+        let commenced = now(&mut lockup.context).await;
+        let cancelable_by = [cancelers[0].pubkey(), cancelers[1].pubkey()];
+        lockup.fund(&recipient.pubkey(), AMOUNT, commenced, &cancelable_by).await.unwrap();
+        Scenario { lockup, recipient, recipient_tokens, cancelers, commenced }
+    }
+
+    async fn transfer(&mut self, value: u32) -> Result<(), TransportError> {
+        let instruction = self.lockup.transfer_instruction(&self.recipient.pubkey(), &self.recipient.pubkey(), &self.recipient_tokens, value, 0);
+        process(&mut self.lockup.context, &[instruction], &[&self.recipient]).await
+    }
+
+    async fn cancel(&mut self, canceler: usize) -> Result<(), TransportError> {
+        let lockup = &self.lockup;
+        let instruction = TokenLockInstruction::cancel_timelock(
+            &lockup.token_lock,
+            &self.cancelers[canceler].pubkey(),
+            &lockup.mint,
+            &spl_token::id(),
+            &self.recipient.pubkey(),
+            &self.recipient_tokens,
+            0,
+            &lockup.funder.pubkey(),
+            &lockup.funder_tokens,
+            CancelMode::PayOut,
+        );
+        process(&mut self.lockup.context, &[instruction], &[&self.cancelers[canceler]]).await
+    }
+
+    /// What the timelock has unlocked by now
+    async fn unlocked(&mut self) -> u32 {
+        let now = now(&mut self.lockup.context).await;
+        Processor::calculate_unlocked(self.commenced, now, AMOUNT, &self.lockup.schedule)
+    }
+
+    async fn vault_balance(&mut self) -> u64 {
+        let vault = self.lockup.vault;
+        self.lockup.balance(&vault).await
+    }
+}
 
 #[tokio::test]
-async fn test_helloworld() {
-    let program_id = Pubkey::new_unique();
-    let greeted_pubkey = Pubkey::new_unique();
-
-    let mut program_test = ProgramTest::new(
-        "tokenlock", // Run the BPF version with `cargo test-bpf`
-        program_id,
-        processor!(process_instruction), // Run the native version with `cargo test`
-    );
-    program_test.add_account(
-        greeted_pubkey,
-        Account {
-            lamports: 5,
-            //data: vec![0_u8; mem::size_of::<u32>()],
-            data: vec![0_u8; 20],
-            owner: program_id,
-            ..Account::default()
-        },
-    );
-    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
-
-    // Verify account has zero greetings
-    let greeted_account = banks_client
-        .get_account(greeted_pubkey)
-        .await
-        .expect("get_account")
-        .expect("greeted_account not found");
-    assert_eq!(
-        TokenLockAccount::try_from_slice(&greeted_account.data)
-            .unwrap()
-            .counter,
-        0
-        // GreetingAccount::try_from_slice(&greeted_account.data)
-        //     .unwrap()
-        //     .counter,
-        // 0
-    );
+async fn vest_transfer_cancel_and_reclaim() {
+    let mut scenario = Scenario::new().await;
+    let funder_tokens = scenario.lockup.funder_tokens;
+    let recipient_tokens = scenario.recipient_tokens;
+    assert_eq!(scenario.vault_balance().await, AMOUNT as u64);
+    assert_eq!(scenario.lockup.balance(&funder_tokens).await, Lockup::FUNDER_BALANCE - AMOUNT as u64);
+    let timelocks = scenario.lockup.timelocks().await;
+    assert_eq!(timelocks.len(), 1);
+    assert_eq!(timelocks[0].recipient, scenario.recipient.pubkey());
+    assert_eq!(timelocks[0].total_amount, AMOUNT);
 
-    // Greet once
-    let mut transaction = Transaction::new_with_payer(
-        &[Instruction::new_with_bincode(
-            program_id,
-            &[0_u8], // ignored but makes the instruction unique in the slot
-            vec![AccountMeta::new(greeted_pubkey, false)],
-        )],
-        Some(&payer.pubkey()),
-    );
-    transaction.sign(&[&payer], recent_blockhash);
-    banks_client.process_transaction(transaction).await.unwrap();
-
-    // Verify account has one greeting
-    let greeted_account = banks_client
-        .get_account(greeted_pubkey)
-        .await
-        .expect("get_account")
-        .expect("greeted_account not found");
-    assert_eq!(        
-        TokenLockAccount::try_from_slice(&greeted_account.data)
-            .unwrap()
-            .counter,
-        1
-
-        // GreetingAccount::try_from_slice(&greeted_account.data)
-        //     .unwrap()
-        //     .counter,
-        // 1
-    );
+    // the initial release is unlocked right away, the next one isn't
+    assert_eq!(scenario.unlocked().await, AMOUNT / 4);
+    scenario.transfer(100).await.unwrap();
+    assert_instruction_error(scenario.transfer(AMOUNT / 4).await, InstructionError::InsufficientFunds);
+
+    // after the second release
+    warp_to_timestamp(&mut scenario.lockup.context, scenario.commenced + HOUR).await;
+    let unlocked = scenario.unlocked().await;
+    assert!((AMOUNT / 2..AMOUNT).contains(&unlocked), "{} unlocked", unlocked);
+    scenario.transfer(150).await.unwrap();
+    assert_eq!(scenario.lockup.balance(&recipient_tokens).await, 250);
+    assert_eq!(scenario.lockup.timelocks().await[0].tokens_transferred, 250);
+
+    // the recipient gets what unlocked and hasn't been transferred yet, the funder reclaims the rest
+    scenario.cancel(0).await.unwrap();
+    assert_eq!(scenario.lockup.balance(&recipient_tokens).await, unlocked as u64);
+    assert_eq!(scenario.lockup.balance(&funder_tokens).await, Lockup::FUNDER_BALANCE - unlocked as u64);
+    assert_eq!(scenario.vault_balance().await, 0);
+    // the canceled timelock stays in the account, drained
+    let timelocks = scenario.lockup.timelocks().await;
+    assert_eq!(timelocks.len(), 1);
+    assert_eq!(timelocks[0].tokens_transferred, timelocks[0].total_amount);
+
+    let token_lock = scenario.lockup.token_lock;
+    let reconcile = TokenLockInstruction::reconcile(&token_lock, &spl_token::id());
+    process(&mut scenario.lockup.context, &[reconcile], &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn everything_unlocks_after_the_last_release() {
+    let mut scenario = Scenario::new().await;
+    warp_to_timestamp(&mut scenario.lockup.context, scenario.commenced + 3 * HOUR).await;
+    assert_eq!(scenario.unlocked().await, AMOUNT);
+    scenario.transfer(AMOUNT).await.unwrap();
+
+    let recipient_tokens = scenario.recipient_tokens;
+    assert_eq!(scenario.lockup.balance(&recipient_tokens).await, AMOUNT as u64);
+    assert_eq!(scenario.vault_balance().await, 0);
+    // nothing left to cancel
+    assert_instruction_error(scenario.cancel(0).await, InstructionError::InvalidArgument);
+
+    // the drained timelock keeps its index, the next funding gets a new one
+    let recipient = scenario.recipient.pubkey();
+    let commenced = scenario.commenced;
+    scenario.lockup.fund(&recipient, AMOUNT, commenced, &[]).await.unwrap();
+    let timelocks = scenario.lockup.timelocks().await;
+    assert_eq!(timelocks.len(), 2);
+    assert_eq!((timelocks[0].tokens_transferred, timelocks[1].tokens_transferred), (AMOUNT, 0));
+    assert_instruction_error(scenario.transfer(1).await, InstructionError::InsufficientFunds);
+    assert_eq!(scenario.vault_balance().await, AMOUNT as u64);
+}
+
+#[tokio::test]
+async fn transfer_needs_the_recipients_signature() {
+    let mut scenario = Scenario::new().await;
+    let recipient = scenario.recipient.pubkey();
+    let recipient_tokens = scenario.recipient_tokens;
+
+    // the recipient listed without signing
+    let mut instruction = scenario.lockup.transfer_instruction(&recipient, &recipient, &recipient_tokens, 100, 0);
+    instruction.accounts[1].is_signer = false;
+    let result = process(&mut scenario.lockup.context, &[instruction], &[]).await;
+    assert_instruction_error(result, InstructionError::MissingRequiredSignature);
+
+    // someone else signing for the recipient's timelock index
+    let attacker = funded_keypair(&mut scenario.lockup.context).await;
+    let attacker_tokens = scenario.lockup.token_account(&attacker.pubkey()).await;
+    let instruction = scenario.lockup.transfer_instruction(&attacker.pubkey(), &attacker.pubkey(), &attacker_tokens, 100, 0);
+    let result = process(&mut scenario.lockup.context, &[instruction], &[&attacker]).await;
+    assert_instruction_error(result, InstructionError::InvalidArgument);
 
-    // Greet again
-    let mut transaction = Transaction::new_with_payer(
-        &[Instruction::new_with_bincode(
-            program_id,
-            &[0_u8], // ignored but makes the instruction unique in the slot
-            vec![AccountMeta::new(greeted_pubkey, false)],
-        )],
-        Some(&payer.pubkey()),
+    assert_eq!(scenario.lockup.balance(&attacker_tokens).await, 0);
+    assert_eq!(scenario.vault_balance().await, AMOUNT as u64);
+}
+
+#[tokio::test]
+async fn only_cancelers_cancel() {
+    let mut scenario = Scenario::new().await;
+    let lockup = &scenario.lockup;
+    let recipient = scenario.recipient.pubkey();
+    let recipient_tokens = scenario.recipient_tokens;
+    // the recipient trying to reclaim the locked tokens for themselves
+    let instruction = TokenLockInstruction::cancel_timelock(
+        &lockup.token_lock,
+        &recipient,
+        &lockup.mint,
+        &spl_token::id(),
+        &recipient,
+        &recipient_tokens,
+        0,
+        &recipient,
+        &recipient_tokens,
+        CancelMode::PayOut,
     );
-    transaction.sign(&[&payer], recent_blockhash);
-    banks_client.process_transaction(transaction).await.unwrap();
-
-    // Verify account has two greetings
-    let greeted_account = banks_client
-        .get_account(greeted_pubkey)
-        .await
-        .expect("get_account")
-        .expect("greeted_account not found");
-    assert_eq!(
-        TokenLockAccount::try_from_slice(&greeted_account.data)
-            .unwrap()
-            .counter,
-        2
-        // GreetingAccount::try_from_slice(&greeted_account.data)
-        //     .unwrap()
-        //     .counter,
-        // 2
+    let result = process(&mut scenario.lockup.context, &[instruction], &[&scenario.recipient]).await;
+    assert_instruction_error(result, InstructionError::InvalidArgument);
+    assert_eq!(scenario.vault_balance().await, AMOUNT as u64);
+    assert_eq!(scenario.lockup.timelocks().await[0].tokens_transferred, 0);
+}
+
+#[tokio::test]
+async fn cancel_only_once() {
+    let mut scenario = Scenario::new().await;
+    scenario.cancel(0).await.unwrap();
+    let funder_tokens = scenario.lockup.funder_tokens;
+    let reclaimed = scenario.lockup.balance(&funder_tokens).await;
+
+    // the other canceler can't cancel what is already canceled
+    assert_instruction_error(scenario.cancel(1).await, InstructionError::InvalidArgument);
+    assert_eq!(scenario.lockup.balance(&funder_tokens).await, reclaimed);
+    assert_eq!(scenario.vault_balance().await, 0);
+    assert_eq!(scenario.lockup.timelocks().await.len(), 1);
+}
+
+#[tokio::test]
+async fn vault_cannot_be_swapped() {
+    let mut scenario = Scenario::new().await;
+    let attacker = funded_keypair(&mut scenario.lockup.context).await;
+    let attacker_tokens = scenario.lockup.token_account(&attacker.pubkey()).await;
+    let recipient = scenario.recipient.pubkey();
+    let recipient_tokens = scenario.recipient_tokens;
+
+    // funding into a token account of the attacker instead of the vault
+    let mut instruction = scenario.lockup.fund_instruction(&recipient, AMOUNT, scenario.commenced, &[], 1);
+    instruction.accounts[3].pubkey = attacker_tokens;
+    let result = process(&mut scenario.lockup.context, &[instruction], &[&scenario.lockup.funder]).await;
+    assert_instruction_error(result, InstructionError::InvalidSeeds);
+
+    // paying out of a token account the attacker controls
+    mint_to(&mut scenario.lockup.context, &scenario.lockup.mint, &scenario.lockup.mint_authority, &attacker_tokens, AMOUNT as u64).await;
+    let mut instruction = scenario.lockup.transfer_instruction(&recipient, &recipient, &recipient_tokens, 100, 0);
+    instruction.accounts[2].pubkey = attacker_tokens;
+    let result = process(&mut scenario.lockup.context, &[instruction], &[&scenario.recipient]).await;
+    assert_instruction_error(result, InstructionError::InvalidSeeds);
+
+    assert_eq!(scenario.lockup.balance(&attacker_tokens).await, AMOUNT as u64);
+    assert_eq!(scenario.vault_balance().await, AMOUNT as u64);
+    assert_eq!(scenario.lockup.timelocks().await.len(), 1);
+}
+
+#[tokio::test]
+async fn funding_needs_the_lockups_mint() {
+    let mut scenario = Scenario::new().await;
+    let authority = Keypair::new();
+    let other_mint = create_mint(&mut scenario.lockup.context, &authority.pubkey(), 6).await;
+    let funder = scenario.lockup.funder.pubkey();
+    let other_tokens = create_token_account(&mut scenario.lockup.context, &other_mint, &funder).await;
+    mint_to(&mut scenario.lockup.context, &other_mint, &authority, &other_tokens, AMOUNT as u64).await;
+
+    let lockup = &scenario.lockup;
+    let instruction = TokenLockInstruction::fund_release_schedule(
+        &lockup.token_lock,
+        &funder,
+        &other_tokens,
+        &other_mint,
+        &spl_token::id(),
+        &scenario.recipient.pubkey(),
+        AMOUNT,
+        scenario.commenced,
+        0,
+        &[],
+        0,
+        1,
     );
+    let result = process(&mut scenario.lockup.context, &[instruction], &[&scenario.lockup.funder]).await;
+    assert_instruction_error(result, InstructionError::InvalidArgument);
+    assert_eq!(token_balance(&mut scenario.lockup.context, &other_tokens).await, AMOUNT as u64);
+    assert_eq!(scenario.lockup.timelocks().await.len(), 1);
+}
+
+#[tokio::test]
+async fn replayed_funding_is_rejected() {
+    let mut lockup = Lockup::new(4).await;
+    let recipient = Keypair::new().pubkey();
+    let commenced = now(&mut lockup.context).await;
+    let instruction = lockup.fund_instruction(&recipient, AMOUNT, commenced, &[], 0);
+    process(&mut lockup.context, std::slice::from_ref(&instruction), &[&lockup.funder]).await.unwrap();
+
+    // the same funding in a transaction with a new blockhash, which the runtime doesn't see as a duplicate
+    let blockhash = lockup.context.banks_client.get_recent_blockhash().await.unwrap();
+    let (new_blockhash, _) = lockup.context.banks_client.get_new_blockhash(&blockhash).await.unwrap();
+    let payer = &lockup.context.payer;
+    let transaction = Transaction::new_signed_with_payer(&[instruction], Some(&payer.pubkey()), &[payer, &lockup.funder], new_blockhash);
+    let result = lockup.context.banks_client.process_transaction(transaction).await;
+    assert_instruction_error(result, InstructionError::InvalidArgument);
+
+    let vault = lockup.vault;
+    let funder_tokens = lockup.funder_tokens;
+    assert_eq!(lockup.timelocks().await.len(), 1);
+    assert_eq!(lockup.balance(&vault).await, AMOUNT as u64);
+    assert_eq!(lockup.balance(&funder_tokens).await, Lockup::FUNDER_BALANCE - AMOUNT as u64);
+}
+
+#[tokio::test]
+async fn prefunded_proposal_address_cannot_block_the_proposal() {
+    let mut scenario = Scenario::new().await;
+    let token_lock = scenario.lockup.token_lock;
+    let funder = scenario.lockup.funder.pubkey();
+    let recipient = scenario.recipient.pubkey();
+    let canceler = scenario.cancelers[0].pubkey();
+    let (proposal, _) = CancelProposal::find_address(&token_lock, &recipient, 0, &tokenlock::id());
+
+    // anyone can send lamports to the address before the cancelers propose
+    let attacker = funded_keypair(&mut scenario.lockup.context).await;
+    let transfer = system_instruction::transfer(&attacker.pubkey(), &proposal, 1);
+    process(&mut scenario.lockup.context, &[transfer], &[&attacker]).await.unwrap();
+
+    let instruction = TokenLockInstruction::propose_cancel(&token_lock, &canceler, &recipient, 0, &funder, CancelMode::PayOut);
+    process(&mut scenario.lockup.context, &[instruction], &[&scenario.cancelers[0]]).await.unwrap();
+    let account = scenario.lockup.context.banks_client.get_account(proposal).await.unwrap().unwrap();
+    assert_eq!(account.owner, tokenlock::id());
+    assert_eq!(account.data.len(), CancelProposal::LEN);
+    assert_eq!(CancelProposal::deserialize(&mut &account.data[..]).unwrap().approvals, vec![canceler]);
 }
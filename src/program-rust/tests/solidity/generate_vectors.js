@@ -0,0 +1,245 @@
+// Writes calculate_unlocked.json, reference vectors of calculateUnlocked in TokenLockup.sol for
+// tests/solidity_reference.rs. Run with `npm run generate:vectors`.
+//
+// The contract is compiled with solc and deployed to a local anvil node the script starts, the expected
+// value of every vector is what an eth_call of calculateUnlocked returns, so nothing here reimplements the
+// Solidity arithmetic. Both tools are taken from the PATH unless SOLC and ANVIL point to them. Inputs are
+// drawn from the ranges the program stores, u32 timestamps, amounts and schedule fields, with the
+// schedule checks of createReleaseSchedule applied. The sha256 of the contract source is recorded with the
+// vectors and the Rust test checks it, so the vectors can't silently go stale when the contract changes.
+const {execFileSync, spawn} = require('child_process');
+const crypto = require('crypto');
+const fs = require('fs');
+const http = require('http');
+const path = require('path');
+
+const SOURCE = path.join(__dirname, 'TokenLockup.sol');
+const OUTPUT = path.join(__dirname, 'calculate_unlocked.json');
+const SOLC = process.env.SOLC || 'solc';
+const ANVIL = process.env.ANVIL || 'anvil';
+const ANVIL_PORT = 8547;
+const CALCULATE_UNLOCKED = 'calculateUnlocked(uint256,uint256,uint256,(uint256,uint256,uint256,uint256))';
+const U32_MAX = 0xffffffffn;
+const BIPS_PRECISION = 10000n;
+const RANDOM_VECTORS = 2000;
+
+// the creation bytecode and the selector of calculateUnlocked, with the compiler version
+function compile(source) {
+  const input = {
+    language: 'Solidity',
+    sources: {'TokenLockup.sol': {content: source}},
+    settings: {outputSelection: {'*': {'*': ['evm.bytecode.object', 'evm.methodIdentifiers']}}},
+  };
+  const output = JSON.parse(execFileSync(SOLC, ['--standard-json'], {input: JSON.stringify(input)}).toString());
+  const errors = (output.errors || []).filter(error => error.severity === 'error');
+  if (errors.length > 0) {
+    throw new Error(errors.map(error => error.formattedMessage).join('\n'));
+  }
+  const contract = output.contracts['TokenLockup.sol'].TokenLockup;
+  // the last line of `solc --version` reads `Version: 0.8.x+commit...`
+  const version = execFileSync(SOLC, ['--version']).toString().trim().split('\n').pop().replace('Version: ', '');
+  return {bytecode: '0x' + contract.evm.bytecode.object, selector: contract.evm.methodIdentifiers[CALCULATE_UNLOCKED], version};
+}
+
+let rpcId = 0;
+function rpc(method, params) {
+  const body = JSON.stringify({jsonrpc: '2.0', id: ++rpcId, method, params});
+  return new Promise((resolve, reject) => {
+    const options = {host: '127.0.0.1', port: ANVIL_PORT, method: 'POST', headers: {'Content-Type': 'application/json'}};
+    const request = http.request(options, response => {
+      let data = '';
+      response.on('data', chunk => (data += chunk));
+      response.on('end', () => {
+        const reply = JSON.parse(data);
+        if (reply.error) {
+          reject(new Error(`${method}: ${reply.error.message}`));
+        } else {
+          resolve(reply.result);
+        }
+      });
+    });
+    request.on('error', reject);
+    request.end(body);
+  });
+}
+
+async function startAnvil() {
+  const node = spawn(ANVIL, ['--port', String(ANVIL_PORT), '--silent'], {stdio: 'ignore'});
+  node.on('error', error => {
+    throw new Error(`${ANVIL} could not be started: ${error.message}`);
+  });
+  for (let attempt = 0; attempt < 100; attempt++) {
+    try {
+      await rpc('eth_chainId', []);
+      return node;
+    } catch (error) {
+      await new Promise(resolve => setTimeout(resolve, 100));
+    }
+  }
+  node.kill();
+  throw new Error(`anvil did not answer on port ${ANVIL_PORT}`);
+}
+
+// deploys from an account anvil unlocks, anvil mines the transaction right away
+async function deploy(bytecode) {
+  const [from] = await rpc('eth_accounts', []);
+  const hash = await rpc('eth_sendTransaction', [{from, data: bytecode, gas: '0x1000000'}]);
+  const receipt = await rpc('eth_getTransactionReceipt', [hash]);
+  if (receipt.status !== '0x1') {
+    throw new Error('deploying TokenLockup failed');
+  }
+  return receipt.contractAddress;
+}
+
+// the ABI encoding of the arguments, uint256 words with the static ReleaseSchedule tuple in place
+function callData(selector, commenced, current, amount, releaseSchedule) {
+  const words = [
+    commenced,
+    current,
+    amount,
+    releaseSchedule.releaseCount,
+    releaseSchedule.delayUntilFirstReleaseInSeconds,
+    releaseSchedule.initialReleasePortionInBips,
+    releaseSchedule.periodBetweenReleasesInSeconds,
+  ];
+  return '0x' + selector + words.map(word => word.toString(16).padStart(64, '0')).join('');
+}
+
+function schedule(releaseCount, delayUntilFirstReleaseInSeconds, initialReleasePortionInBips, periodBetweenReleasesInSeconds) {
+  return {
+    releaseCount: BigInt(releaseCount),
+    delayUntilFirstReleaseInSeconds: BigInt(delayUntilFirstReleaseInSeconds),
+    initialReleasePortionInBips: BigInt(initialReleasePortionInBips),
+    periodBetweenReleasesInSeconds: BigInt(periodBetweenReleasesInSeconds),
+  };
+}
+
+// mulberry32, so the vectors don't change between runs
+function random(seed) {
+  let state = seed >>> 0;
+  return () => {
+    state = (state + 0x6d2b79f5) >>> 0;
+    let t = state;
+    t = Math.imul(t ^ (t >>> 15), t | 1);
+    t ^= t + Math.imul(t ^ (t >>> 7), t | 61);
+    return ((t ^ (t >>> 14)) >>> 0) / 4294967296;
+  };
+}
+
+const next = random(1);
+// a u32 below `limit` with small values as likely as large ones
+function logUniform(limit) {
+  const bits = Math.floor(next() * 33);
+  const value = BigInt(Math.floor(next() * 2 ** bits));
+  return value < limit ? value : limit;
+}
+function uniform(limit) {
+  return BigInt(Math.floor(next() * (Number(limit) + 1)));
+}
+function clamp(value) {
+  return value < 0n ? 0n : value > U32_MAX ? U32_MAX : value;
+}
+
+const inputs = [];
+function add(commenced, current, amount, releaseSchedule) {
+  inputs.push({commenced, current, amount, releaseSchedule});
+}
+
+// around every release of schedules at the edges of the accepted range
+const DAY = 24 * 60 * 60;
+const SCHEDULES = [
+  schedule(1, 0, 10000, 0),
+  schedule(1, 3600, 10000, 0),
+  schedule(2, 0, 0, 1),
+  schedule(2, 0, 5000, 2 ** 31),
+  schedule(3, 7, 3333, 11),
+  schedule(4, 0, 2500, 3600),
+  schedule(4, 1, 10000, 1),
+  schedule(12, 30 * DAY, 1000, 30 * DAY),
+  schedule(24, 365 * DAY, 0, 30 * DAY),
+  schedule(100, 0, 1, DAY),
+  schedule(5, 0xffffffff, 9999, 0xffffffff),
+  schedule(0xffffffff, 0, 1, 1),
+];
+const COMMENCED = [0n, 1600000000n];
+const AMOUNTS = [0n, 1n, 999n, 1000000007n, U32_MAX];
+for (const releaseSchedule of SCHEDULES) {
+  const {releaseCount, delayUntilFirstReleaseInSeconds: delay, periodBetweenReleasesInSeconds: period} = releaseSchedule;
+  const releases = [0n, 1n, 2n, releaseCount - 2n, releaseCount - 1n].filter(release => release >= 0n && release < releaseCount);
+  const offsets = new Set([0n, U32_MAX]);
+  for (const release of releases) {
+    const at = delay + period * release;
+    [at - 1n, at, at + 1n].forEach(offset => offsets.add(offset));
+  }
+  for (const commenced of COMMENCED) {
+    const currents = new Set([...offsets].map(offset => clamp(commenced + offset)));
+    if (commenced > 0n) {
+      currents.add(commenced - 1n);
+    }
+    for (const current of [...currents].sort((a, b) => (a < b ? -1 : a > b ? 1 : 0))) {
+      for (const amount of AMOUNTS) {
+        add(commenced, current, amount, releaseSchedule);
+      }
+    }
+  }
+}
+
+// random schedules, mostly within their vesting period
+for (let i = 0; i < RANDOM_VECTORS; i++) {
+  const releaseCount = next() < 0.9 ? 1n + uniform(119n) : 1n + logUniform(U32_MAX - 1n);
+  const initialReleasePortionInBips = releaseCount === 1n ? BIPS_PRECISION : next() < 0.2 ? 0n : uniform(BIPS_PRECISION);
+  const period = releaseCount === 1n ? logUniform(U32_MAX) : 1n + logUniform(U32_MAX - 1n);
+  const releaseSchedule = schedule(releaseCount, logUniform(U32_MAX), initialReleasePortionInBips, period);
+  const end = releaseSchedule.delayUntilFirstReleaseInSeconds + period * (releaseCount - 1n);
+  const commenced = next() < 0.5 ? uniform(2000000000n) : uniform(U32_MAX);
+  const current = next() < 0.9 ? clamp(commenced + uniform(end + end / 4n)) : uniform(U32_MAX);
+  add(commenced, current, logUniform(U32_MAX), releaseSchedule);
+}
+
+async function main() {
+  const source = fs.readFileSync(SOURCE);
+  const {bytecode, selector, version} = compile(source.toString());
+  const anvil = await startAnvil();
+  try {
+    const contract = await deploy(bytecode);
+    const vectors = [];
+    for (const {commenced, current, amount, releaseSchedule} of inputs) {
+      const data = callData(selector, commenced, current, amount, releaseSchedule);
+      const unlocked = BigInt(await rpc('eth_call', [{to: contract, data}, 'latest']));
+      vectors.push([
+        commenced,
+        current,
+        amount,
+        releaseSchedule.releaseCount,
+        releaseSchedule.delayUntilFirstReleaseInSeconds,
+        releaseSchedule.initialReleasePortionInBips,
+        releaseSchedule.periodBetweenReleasesInSeconds,
+        unlocked,
+      ]);
+    }
+
+    const lines = vectors.map(vector => '    [' + vector.join(', ') + ']');
+    const json = [
+      '{',
+      '  "source": "TokenLockup.calculateUnlocked, tests/solidity/TokenLockup.sol",',
+      `  "sourceSha256": "${crypto.createHash('sha256').update(source).digest('hex')}",`,
+      `  "compiler": "solc ${version}",`,
+      '  "evm": "anvil",',
+      '  "fields": ["commencedTimestamp", "currentTimestamp", "amount", "releaseCount", "delayUntilFirstReleaseInSeconds", "initialReleasePortionInBips", "periodBetweenReleasesInSeconds", "unlocked"],',
+      '  "vectors": [',
+      lines.join(',\n'),
+      '  ]',
+      '}',
+      '',
+    ].join('\n');
+    fs.writeFileSync(OUTPUT, json);
+    console.log(`${vectors.length} vectors written to ${OUTPUT}`);
+  } finally {
+    anvil.kill();
+  }
+}
+
+main().catch(error => {
+  console.error(error);
+  process.exit(1);
+});
@@ -0,0 +1,49 @@
+//! The vesting math against `calculateUnlocked` of the Solidity TokenLockup contract it was ported from.
+//! The vectors in `tests/solidity/calculate_unlocked.json` are written by `tests/solidity/generate_vectors.js`,
+//! which runs the contract compiled with solc in an EVM, for the contract source whose sha256 they record.
+use std::convert::TryFrom;
+use serde_json::Value;
+use solana_program::hash::hash;
+use tokenlock::processor::Processor;
+
+const VECTORS: &str = include_str!("solidity/calculate_unlocked.json");
+const SOURCE: &[u8] = include_bytes!("solidity/TokenLockup.sol");
+const FIELDS: [&str; 8] = [
+    "commencedTimestamp",
+    "currentTimestamp",
+    "amount",
+    "releaseCount",
+    "delayUntilFirstReleaseInSeconds",
+    "initialReleasePortionInBips",
+    "periodBetweenReleasesInSeconds",
+    "unlocked",
+];
+
+#[test]
+fn calculate_unlocked_matches_solidity() {
+    let reference: Value = serde_json::from_str(VECTORS).unwrap();
+    let fields: Vec<&str> = reference["fields"].as_array().unwrap().iter().map(|field| field.as_str().unwrap()).collect();
+    assert_eq!(fields, FIELDS);
+
+    let vectors = reference["vectors"].as_array().unwrap();
+    assert!(vectors.len() > 1000, "only {} vectors", vectors.len());
+    let mut mismatches = vec![];
+    for vector in vectors {
+        let values: Vec<u32> = vector.as_array().unwrap().iter()
+            .map(|value| u32::try_from(value.as_u64().unwrap()).unwrap())
+            .collect();
+        let (inputs, expected) = (&values[..7], values[7]);
+        let unlocked = Processor::calculate_unlocked_0(inputs[0], inputs[1], inputs[2], inputs[3], inputs[4], inputs[5], inputs[6]);
+        if unlocked != expected {
+            mismatches.push(format!("{:?}: {} instead of {}", inputs, unlocked, expected));
+        }
+    }
+    assert!(mismatches.is_empty(), "{} of {} vectors differ:\n{}", mismatches.len(), vectors.len(), mismatches.join("\n"));
+}
+
+#[test]
+fn vectors_are_of_the_contract_source() {
+    let reference: Value = serde_json::from_str(VECTORS).unwrap();
+    let sha256: String = hash(SOURCE).to_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+    assert_eq!(reference["sourceSha256"], sha256, "TokenLockup.sol changed, regenerate the vectors with `npm run generate:vectors`");
+}